@@ -0,0 +1,185 @@
+use ::cpu::Cpu;
+use ::memory_map;
+use ::memory_map::MemoryMapper;
+
+// [Addressing Modes]
+// Every instruction gets its operand through one of these. The resolver below
+// works out the effective address one cycle at a time (immediates resolve to
+// the address of the operand byte itself), doing the same bus accesses the
+// 6502 does along the way, dummy reads included. Instruction modules only
+// ever see the values read from / written to that address.
+//
+// Relative and Indirect are only used by branches and jmp, which walk
+// through their own cycles.
+//
+// [Resources]
+// addressing modes => http://www.obelisk.me.uk/6502/addressing.html
+// cycle by cycle => http://nesdev.com/6502_cpu.txt
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AddressingMode {
+    Implied,
+    Accumulator,
+    Immediate,
+    ZeroPage,
+    ZeroPageX,
+    ZeroPageY,
+    Absolute,
+    AbsoluteX,
+    AbsoluteY,
+    Indirect,
+    IndirectX,
+    IndirectY,
+    Relative,
+}
+
+impl AddressingMode {
+    // Number of operand bytes following the opcode
+    pub fn operand_len(&self) -> u16 {
+        match *self {
+            AddressingMode::Implied | AddressingMode::Accumulator => 0,
+            AddressingMode::Absolute | AddressingMode::AbsoluteX | AddressingMode::AbsoluteY | AddressingMode::Indirect => 2,
+            _ => 1,
+        }
+    }
+}
+
+// Runs the given cycle (1 being the first cycle after the opcode fetch) of
+// resolving the operand address into cpu.state.address.
+//
+// Returns false if this cycle's bus access was used up getting there, or true
+// once the address is ready and the bus is free for the instruction to use
+// this same cycle.
+//
+// Indexed reads that stay on the same page skip the fix-up cycle, since the
+// read with the unfixed high byte already hit the right address.
+pub fn step<T: MemoryMapper>(cpu: &mut Cpu<T>, mode: AddressingMode, cycle: u8, is_read: bool) -> bool {
+    match (mode, cycle) {
+        (AddressingMode::Immediate, _) => {
+            let address = cpu.reg_program_counter;
+            cpu.reg_program_counter = address.wrapping_add(1);
+            cpu.state.address = address;
+
+            true
+        }
+
+        (AddressingMode::ZeroPage, 1) => fetch_zero_page(cpu),
+        (AddressingMode::ZeroPage, _) => true,
+
+        (AddressingMode::ZeroPageX, 1) | (AddressingMode::ZeroPageY, 1) => fetch_zero_page(cpu),
+        (AddressingMode::ZeroPageX, 2) => index_zero_page(cpu, cpu.reg_index_x),
+        (AddressingMode::ZeroPageY, 2) => index_zero_page(cpu, cpu.reg_index_y),
+        (AddressingMode::ZeroPageX, _) | (AddressingMode::ZeroPageY, _) => true,
+
+        (AddressingMode::Absolute, 1) => fetch_lo(cpu),
+        (AddressingMode::Absolute, 2) => fetch_hi(cpu, 0),
+        (AddressingMode::Absolute, _) => true,
+
+        (AddressingMode::AbsoluteX, 1) | (AddressingMode::AbsoluteY, 1) => fetch_lo(cpu),
+        (AddressingMode::AbsoluteX, 2) => fetch_hi(cpu, cpu.reg_index_x),
+        (AddressingMode::AbsoluteY, 2) => fetch_hi(cpu, cpu.reg_index_y),
+        (AddressingMode::AbsoluteX, 3) | (AddressingMode::AbsoluteY, 3) => fix_high_byte(cpu, is_read),
+        (AddressingMode::AbsoluteX, _) | (AddressingMode::AbsoluteY, _) => true,
+
+        (AddressingMode::IndirectX, 1) => fetch_pointer(cpu),
+        (AddressingMode::IndirectX, 2) => {
+            // dummy read from the unindexed pointer while x gets added
+            let pointer = cpu.state.pointer;
+            cpu.read(pointer as u16);
+            cpu.state.pointer = pointer.wrapping_add(cpu.reg_index_x);
+
+            false
+        }
+        (AddressingMode::IndirectX, 3) => read_pointer_lo(cpu),
+        (AddressingMode::IndirectX, 4) => read_pointer_hi(cpu, 0),
+        (AddressingMode::IndirectX, _) => true,
+
+        (AddressingMode::IndirectY, 1) => fetch_pointer(cpu),
+        (AddressingMode::IndirectY, 2) => read_pointer_lo(cpu),
+        (AddressingMode::IndirectY, 3) => read_pointer_hi(cpu, cpu.reg_index_y),
+        (AddressingMode::IndirectY, 4) => fix_high_byte(cpu, is_read),
+        (AddressingMode::IndirectY, _) => true,
+
+        (mode, _) => panic!("{:?} operands are resolved by their instructions", mode),
+    }
+}
+
+fn fetch_zero_page<T: MemoryMapper>(cpu: &mut Cpu<T>) -> bool {
+    cpu.state.address = cpu.next_word() as u16;
+
+    false
+}
+
+fn index_zero_page<T: MemoryMapper>(cpu: &mut Cpu<T>, index: u8) -> bool {
+    // dummy read while the index gets added; zero page indexing wraps
+    // around within the zero page
+    let address = cpu.state.address;
+    cpu.read(address);
+    cpu.state.address = (address as u8).wrapping_add(index) as u16;
+
+    false
+}
+
+fn fetch_lo<T: MemoryMapper>(cpu: &mut Cpu<T>) -> bool {
+    cpu.state.address = cpu.next_word() as u16;
+
+    false
+}
+
+fn fetch_hi<T: MemoryMapper>(cpu: &mut Cpu<T>, index: u8) -> bool {
+    let hi = cpu.next_word() as u16;
+    index_address(cpu, hi, index);
+
+    false
+}
+
+fn fetch_pointer<T: MemoryMapper>(cpu: &mut Cpu<T>) -> bool {
+    cpu.state.pointer = cpu.next_word();
+
+    false
+}
+
+fn read_pointer_lo<T: MemoryMapper>(cpu: &mut Cpu<T>) -> bool {
+    let pointer = cpu.state.pointer;
+    cpu.state.address = cpu.read(pointer as u16) as u16;
+
+    false
+}
+
+fn read_pointer_hi<T: MemoryMapper>(cpu: &mut Cpu<T>, index: u8) -> bool {
+    // pointers stored in the zero page wrap around instead of spilling into
+    // the stack page
+    let pointer = cpu.state.pointer.wrapping_add(1);
+    let hi = cpu.read(pointer as u16) as u16;
+    index_address(cpu, hi, index);
+
+    false
+}
+
+// Adds the index to the low byte only; fix_high_byte carries into the high
+// byte a cycle later if it needs to
+fn index_address<T: MemoryMapper>(cpu: &mut Cpu<T>, hi: u16, index: u8) {
+    let base = (hi << 8) | (cpu.state.address & 0x00ff);
+    let address = base.wrapping_add(index as u16);
+
+    cpu.state.address = address;
+    cpu.state.page_crossed = memory_map::crosses_page_boundary(base, address);
+}
+
+fn fix_high_byte<T: MemoryMapper>(cpu: &mut Cpu<T>, is_read: bool) -> bool {
+    let address = cpu.state.address;
+    let page_crossed = cpu.state.page_crossed;
+
+    if is_read && !page_crossed {
+        return true;
+    }
+
+    // the read before the fix-up still goes out with the old high byte
+    let unfixed = match page_crossed {
+        true => address.wrapping_sub(0x100),
+        false => address,
+    };
+    cpu.read(unfixed);
+
+    false
+}
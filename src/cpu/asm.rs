@@ -0,0 +1,531 @@
+use ::cpu::addressing::AddressingMode;
+use ::cpu::opcodes;
+use ::memory_map;
+use ::memory_map::MemoryMapper;
+
+use std::collections::HashMap;
+use std::fmt;
+
+// [Assembler]
+// A small two-pass 6502 assembler, mostly so tests can be written as
+// assembly instead of opcode bytes. It takes the same syntax the disassembler
+// puts out, plus:
+//
+// label:           defines a label at the current address
+// .org $c000       moves the current address (starting a new segment)
+// .byte $01, 2     raw bytes
+// .word label      raw little-endian words
+// ; comment
+//
+// Numbers are $hex, %binary or decimal. Anywhere a number goes, a label can go
+// instead, optionally with a +/- offset, and <expr / >expr take the low / high
+// byte of it. Mnemonics can have a * in front for readability; unofficial
+// opcodes are picked when there's no official one with the same mnemonic and
+// mode.
+//
+// Operands that fit in the zero page use the zero page mode when there is
+// one. Labels that haven't been defined yet are assumed not to, since the
+// first pass has to settle every instruction's size.
+//
+// [Resources]
+// syntax => http://www.obelisk.me.uk/6502/addressing.html
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AsmError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Segment {
+    pub origin: u16,
+    pub bytes: Vec<u8>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Program {
+    pub segments: Vec<Segment>,
+}
+
+impl Program {
+    // Every byte of every segment, in order; handy for snippets without .org
+    pub fn bytes(&self) -> Vec<u8> {
+        self.segments.iter().flat_map(|segment| segment.bytes.iter().cloned()).collect()
+    }
+
+    pub fn load_into<T: MemoryMapper>(&self, memory_map: &mut T) {
+        for segment in &self.segments {
+            for (i, byte) in segment.bytes.iter().enumerate() {
+                memory_map.write(segment.origin.wrapping_add(i as u16), *byte);
+            }
+        }
+    }
+
+    // Lays the program out in a start..start + len image (e.g. a prg bank),
+    // with anything not covered by a segment left as $ff
+    pub fn image(&self, start: u16, len: usize) -> Vec<u8> {
+        let mut image = vec![0xff; len];
+
+        for segment in &self.segments {
+            for (i, byte) in segment.bytes.iter().enumerate() {
+                let offset = segment.origin.wrapping_add(i as u16).wrapping_sub(start) as usize;
+
+                if offset < len {
+                    image[offset] = *byte;
+                }
+            }
+        }
+
+        image
+    }
+}
+
+// Assembles source starting at the beginning of prg rom
+pub fn assemble(source: &str) -> Result<Program, AsmError> {
+    assemble_at(source, memory_map::PRG_ROM_START)
+}
+
+pub fn assemble_at(source: &str, origin: u16) -> Result<Program, AsmError> {
+    let mut labels: HashMap<String, u16> = HashMap::new();
+    let mut statements = vec![];
+
+    // first pass: work out where everything goes
+    let mut address = origin;
+    for (i, text) in source.lines().enumerate() {
+        let line = i + 1;
+        let error = |message: String| AsmError { line, message };
+
+        let mut text = text.split(';').next().unwrap().trim();
+
+        while let Some(end) = label_end(text) {
+            let label = &text[..end];
+            if labels.insert(label.to_string(), address).is_some() {
+                return Err(error(format!("{} is defined twice", label)));
+            }
+
+            text = text[end + 1..].trim();
+        }
+
+        if text.is_empty() {
+            continue;
+        }
+
+        let statement = parse_statement(text, &labels).map_err(&error)?;
+        if let Statement::Org(ref expr) = statement {
+            address = expr.eval(&labels).ok_or_else(|| error(format!("{} isn't defined before .org needs it", expr)))?;
+        }
+
+        statements.push((line, address, statement.clone()));
+        address = address.wrapping_add(statement.len());
+    }
+
+    // second pass: now every label is known, fill in the bytes
+    let mut program = Program::default();
+    let mut segment = Segment {
+        origin,
+        bytes: vec![],
+    };
+
+    for (line, address, statement) in statements {
+        let error = |message: String| AsmError { line, message };
+        let eval = |expr: &Expr| expr.eval(&labels).ok_or_else(|| error(format!("{} isn't defined", expr)));
+
+        match statement {
+            Statement::Org(_) => {
+                if !segment.bytes.is_empty() {
+                    program.segments.push(segment);
+                }
+
+                segment = Segment {
+                    origin: address,
+                    bytes: vec![],
+                };
+            }
+            Statement::Bytes(exprs) => {
+                for expr in &exprs {
+                    let value = eval(expr)?;
+                    segment.bytes.push(byte(value).ok_or_else(|| error(format!("{} doesn't fit in a byte", expr)))?);
+                }
+            }
+            Statement::Words(exprs) => {
+                for expr in &exprs {
+                    let value = eval(expr)?;
+                    segment.bytes.push(value as u8);
+                    segment.bytes.push((value >> 8) as u8);
+                }
+            }
+            Statement::Instruction(opcode, mode, operand) => {
+                segment.bytes.push(opcode);
+
+                let value = match operand {
+                    Some(ref expr) => eval(expr)?,
+                    None => continue,
+                };
+
+                match mode {
+                    AddressingMode::Relative => {
+                        let offset = value.wrapping_sub(address.wrapping_add(2)) as i16;
+                        if !(-128..=127).contains(&offset) {
+                            return Err(error(format!("branch to {:#06x} is out of range", value)));
+                        }
+
+                        segment.bytes.push(offset as u8);
+                    }
+                    _ if mode.operand_len() == 1 => {
+                        let operand = operand.unwrap();
+                        segment.bytes.push(byte(value).ok_or_else(|| error(format!("{} doesn't fit in a byte", operand)))?);
+                    }
+                    _ => {
+                        segment.bytes.push(value as u8);
+                        segment.bytes.push((value >> 8) as u8);
+                    }
+                }
+            }
+        }
+    }
+
+    if !segment.bytes.is_empty() {
+        program.segments.push(segment);
+    }
+
+    Ok(program)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Part {
+    Whole,
+    Lo,
+    Hi,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Value {
+    Number(u16),
+    Label(String, i32),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Expr {
+    value: Value,
+    part: Part,
+}
+
+impl Expr {
+    fn eval(&self, labels: &HashMap<String, u16>) -> Option<u16> {
+        let value = match self.value {
+            Value::Number(number) => number,
+            Value::Label(ref label, offset) => labels.get(label)?.wrapping_add(offset as u16),
+        };
+
+        Some(match self.part {
+            Part::Whole => value,
+            Part::Lo => value & 0x00ff,
+            Part::Hi => value >> 8,
+        })
+    }
+
+    // Whether this is known to fit in a byte before every label is defined
+    fn fits_in_byte(&self, labels: &HashMap<String, u16>) -> bool {
+        match self.part {
+            Part::Lo | Part::Hi => true,
+            Part::Whole => self.eval(labels).is_some_and(|value| value <= 0xff),
+        }
+    }
+}
+
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let part = match self.part {
+            Part::Whole => "",
+            Part::Lo => "<",
+            Part::Hi => ">",
+        };
+
+        match self.value {
+            Value::Number(number) => write!(f, "{}${:x}", part, number),
+            Value::Label(ref label, 0) => write!(f, "{}{}", part, label),
+            Value::Label(ref label, offset) => write!(f, "{}{}{:+}", part, label, offset),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Statement {
+    Org(Expr),
+    Bytes(Vec<Expr>),
+    Words(Vec<Expr>),
+    Instruction(u8, AddressingMode, Option<Expr>),
+}
+
+impl Statement {
+    fn len(&self) -> u16 {
+        match *self {
+            Statement::Org(_) => 0,
+            Statement::Bytes(ref exprs) => exprs.len() as u16,
+            Statement::Words(ref exprs) => exprs.len() as u16 * 2,
+            Statement::Instruction(_, mode, _) => mode.operand_len() + 1,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Index {
+    None,
+    X,
+    Y,
+}
+
+// Where the colon is if text starts with a label definition
+fn label_end(text: &str) -> Option<usize> {
+    let end = text.find(':')?;
+
+    match is_label(&text[..end]) {
+        true => Some(end),
+        false => None,
+    }
+}
+
+fn is_label(text: &str) -> bool {
+    let starts_well = text.chars().next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_');
+
+    starts_well && text.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+fn byte(value: u16) -> Option<u8> {
+    match value <= 0xff {
+        true => Some(value as u8),
+        false => None,
+    }
+}
+
+fn parse_statement(text: &str, labels: &HashMap<String, u16>) -> Result<Statement, String> {
+    let (name, rest) = match text.find(char::is_whitespace) {
+        Some(end) => (&text[..end], text[end..].trim()),
+        None => (text, ""),
+    };
+
+    let name = name.to_lowercase();
+    let list = || rest.split(',').map(parse_expr).collect::<Result<Vec<Expr>, String>>();
+
+    match name.as_str() {
+        ".org" => Ok(Statement::Org(parse_expr(rest)?)),
+        ".byte" => Ok(Statement::Bytes(list()?)),
+        ".word" => Ok(Statement::Words(list()?)),
+        _ if name.starts_with('.') => Err(format!("unknown directive {}", name)),
+        _ => parse_instruction(name.trim_start_matches('*'), rest, labels),
+    }
+}
+
+fn parse_instruction(mnemonic: &str, operand: &str, labels: &HashMap<String, u16>) -> Result<Statement, String> {
+    if !opcodes::OPCODES.iter().any(|decoded| decoded.instruction.mnemonic() == mnemonic) {
+        return Err(format!("unknown instruction {}", mnemonic));
+    }
+
+    let operand = operand.chars().filter(|c| !c.is_whitespace()).collect::<String>();
+    let upper = operand.to_uppercase();
+
+    let find = |mode: AddressingMode| find_opcode(mnemonic, mode).map(|opcode| (opcode, mode));
+    let missing = |mode: &str| format!("{} has no {} mode", mnemonic, mode);
+
+    if operand.is_empty() || upper == "A" {
+        let (opcode, mode) = find(AddressingMode::Implied).or_else(|| find(AddressingMode::Accumulator)).ok_or_else(|| missing("no operand"))?;
+        return Ok(Statement::Instruction(opcode, mode, None));
+    }
+
+    if let Some(value) = operand.strip_prefix('#') {
+        let (opcode, mode) = find(AddressingMode::Immediate).ok_or_else(|| missing("immediate"))?;
+        return Ok(Statement::Instruction(opcode, mode, Some(parse_expr(value)?)));
+    }
+
+    if operand.starts_with('(') {
+        let (inner, mode) = match () {
+            _ if upper.ends_with(",X)") => (&operand[1..operand.len() - 3], AddressingMode::IndirectX),
+            _ if upper.ends_with("),Y") => (&operand[1..operand.len() - 3], AddressingMode::IndirectY),
+            _ if upper.ends_with(')') => (&operand[1..operand.len() - 1], AddressingMode::Indirect),
+            _ => return Err(format!("unclosed ( in {}", operand)),
+        };
+
+        let (opcode, mode) = find(mode).ok_or_else(|| missing("indirect"))?;
+        return Ok(Statement::Instruction(opcode, mode, Some(parse_expr(inner)?)));
+    }
+
+    let (address, index) = match () {
+        _ if upper.ends_with(",X") => (&operand[..operand.len() - 2], Index::X),
+        _ if upper.ends_with(",Y") => (&operand[..operand.len() - 2], Index::Y),
+        _ => (&operand[..], Index::None),
+    };
+
+    let expr = parse_expr(address)?;
+
+    if index == Index::None {
+        if let Some((opcode, mode)) = find(AddressingMode::Relative) {
+            return Ok(Statement::Instruction(opcode, mode, Some(expr)));
+        }
+    }
+
+    let (zero_page, absolute) = match index {
+        Index::None => (AddressingMode::ZeroPage, AddressingMode::Absolute),
+        Index::X => (AddressingMode::ZeroPageX, AddressingMode::AbsoluteX),
+        Index::Y => (AddressingMode::ZeroPageY, AddressingMode::AbsoluteY),
+    };
+
+    let preferred = match expr.fits_in_byte(labels) {
+        true => find(zero_page).or_else(|| find(absolute)),
+        false => find(absolute).or_else(|| find(zero_page)),
+    };
+
+    let name = match index {
+        Index::None => "absolute",
+        Index::X => "x indexed",
+        Index::Y => "y indexed",
+    };
+
+    let (opcode, mode) = preferred.ok_or_else(|| missing(name))?;
+    Ok(Statement::Instruction(opcode, mode, Some(expr)))
+}
+
+// Official opcodes win over unofficial ones with the same mnemonic and mode
+fn find_opcode(mnemonic: &str, mode: AddressingMode) -> Option<u8> {
+    let find = |official: bool| {
+        opcodes::OPCODES
+            .iter()
+            .position(|decoded| decoded.instruction.mnemonic() == mnemonic && decoded.mode == mode && decoded.official == official)
+    };
+
+    find(true).or_else(|| find(false)).map(|opcode| opcode as u8)
+}
+
+fn parse_expr(text: &str) -> Result<Expr, String> {
+    let text = text.trim();
+
+    let (part, text) = match text.chars().next() {
+        Some('<') => (Part::Lo, &text[1..]),
+        Some('>') => (Part::Hi, &text[1..]),
+        _ => (Part::Whole, text),
+    };
+
+    let value = match text.chars().next() {
+        Some('$') => Value::Number(parse_number(&text[1..], 16)?),
+        Some('%') => Value::Number(parse_number(&text[1..], 2)?),
+        Some(c) if c.is_ascii_digit() => Value::Number(parse_number(text, 10)?),
+        _ => {
+            let (label, offset) = match text.find(['+', '-']) {
+                Some(at) => (&text[..at], text[at..].trim_start_matches('+').parse::<i32>().map_err(|_| format!("bad offset in {}", text))?),
+                None => (text, 0),
+            };
+
+            if !is_label(label) {
+                return Err(format!("expected a number or label, got \"{}\"", text));
+            }
+
+            Value::Label(label.to_string(), offset)
+        }
+    };
+
+    Ok(Expr { value, part })
+}
+
+fn parse_number(text: &str, radix: u32) -> Result<u16, String> {
+    u16::from_str_radix(text, radix).map_err(|_| format!("bad number \"{}\"", text))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    pub fn test_addressing_modes() {
+        let program = assemble("
+            lda #$10
+            lda $10
+            lda $10,x
+            ldx $10,y
+            lda $1234
+            lda $1234,x
+            lda $1234,y
+            jmp ($1234)
+            lda ($10,x)
+            lda ($10),y
+            asl a
+            asl
+            rts
+        ").unwrap();
+
+        assert_eq!(program.bytes(),
+                   vec![0xa9, 0x10, 0xa5, 0x10, 0xb5, 0x10, 0xb6, 0x10, 0xad, 0x34, 0x12, 0xbd, 0x34, 0x12, 0xb9, 0x34, 0x12, 0x6c, 0x34, 0x12, 0xa1, 0x10, 0xb1,
+                        0x10, 0x0a, 0x0a, 0x60]);
+    }
+
+    #[test]
+    pub fn test_labels_and_branches() {
+        let program = assemble("
+            start:
+                ldx #3
+            loop: dex
+                bne loop
+                beq done   ; forward
+                jmp start
+            done:
+                lda #<done
+                lda #>done
+        ").unwrap();
+
+        assert_eq!(program.bytes(), vec![0xa2, 0x03, 0xca, 0xd0, 0xfd, 0xf0, 0x03, 0x4c, 0x00, 0x80, 0xa9, 0x0a, 0xa9, 0x80]);
+    }
+
+    #[test]
+    pub fn test_forward_labels_stay_absolute() {
+        // zp isn't defined yet on the first pass, so lda gets the absolute mode
+        let program = assemble("
+            lda zp
+            .org $0010
+            zp: .byte 1
+        ").unwrap();
+
+        assert_eq!(program.segments[0].bytes, vec![0xad, 0x10, 0x00]);
+    }
+
+    #[test]
+    pub fn test_directives_and_segments() {
+        let program = assemble("
+            reset: sei
+            .org $fffa
+            .word reset, reset+1, $1234
+            .byte %101, 10
+        ").unwrap();
+
+        assert_eq!(program.segments,
+                   vec![Segment {
+                            origin: 0x8000,
+                            bytes: vec![0x78],
+                        },
+                        Segment {
+                            origin: 0xfffa,
+                            bytes: vec![0x00, 0x80, 0x01, 0x80, 0x34, 0x12, 0x05, 0x0a],
+                        }]);
+
+        let image = program.image(0xc000, 0x4000);
+        assert_eq!(image[0x3ffa], 0x00);
+        assert_eq!(image[0x0000], 0xff);
+    }
+
+    #[test]
+    pub fn test_unofficial_opcodes() {
+        // plain nop stays official, nop with an operand can only be unofficial
+        assert_eq!(assemble("nop\n*nop $10\nlax ($10),y\nsbc #1").unwrap().bytes(), vec![0xea, 0x04, 0x10, 0xb3, 0x10, 0xe9, 0x01]);
+    }
+
+    #[test]
+    pub fn test_errors() {
+        assert_eq!(assemble("nop\nfoo").unwrap_err(), AsmError { line: 2, message: "unknown instruction foo".to_string() });
+        assert_eq!(assemble("jmp nowhere").unwrap_err().message, "nowhere isn't defined");
+        assert_eq!(assemble("sta #1").unwrap_err().message, "sta has no immediate mode");
+        assert_eq!(assemble("a:\na:").unwrap_err().message, "a is defined twice");
+    }
+}
@@ -0,0 +1,439 @@
+use ::cpu::Cpu;
+use ::cpu::CpuStack;
+use ::cpu::addressing;
+use ::cpu::addressing::AddressingMode;
+use ::cpu::instructions::Operation;
+use ::cpu::interrupt;
+use ::cpu::interrupt::Interrupt;
+use ::cpu::opcodes;
+use ::cpu::opcodes::Opcode;
+use ::memory_map;
+use ::memory_map::MemoryMapper;
+use ::state::SaveState;
+use ::state::StateError;
+use ::state::StateReader;
+use ::state::StateWriter;
+
+// [Cycles]
+// The cpu runs one cycle per tick, and every cycle is exactly one bus access,
+// just like the real 2A03. That includes the accesses that don't do anything
+// useful: dummy reads while indexing, the read of the next byte by implied
+// instructions, and the extra write of the unmodified value that every
+// read-modify-write instruction does before writing the result.
+//
+// An instruction goes through up to three stages:
+//
+// 1. the opcode fetch
+// 2. resolving its operand address (see addressing::step)
+// 3. the operation's own accesses (read, write, read+write+write, pushes...)
+//
+// Stage 3 starts on the same cycle stage 2 finishes when the bus is free.
+//
+// [Interrupt polling]
+// The cpu samples its interrupt lines at the end of every cycle, but what
+// decides whether an interrupt runs after an instruction is the sample from
+// its second to last cycle. That's what makes cli/sei/plp take an extra
+// instruction to affect irqs (they change i on their last cycle) while rti's
+// new i applies right away.
+//
+// [Resources]
+// cycle by cycle => http://nesdev.com/6502_cpu.txt
+// interrupt polling => http://wiki.nesdev.com/w/index.php/CPU_interrupts#Branch_instructions_and_interrupts
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Sequence {
+    Instruction(Opcode),
+    Interrupt(Interrupt),
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct CycleState {
+    // What we're in the middle of, or None between instructions
+    pub sequence: Option<Sequence>,
+
+    // Cycle within the sequence, starting at 1 for the opcode fetch
+    pub cycle: u8,
+
+    // Cycles spent on the operation itself, 0 while still addressing
+    pub operation_cycle: u8,
+
+    // Where the current instruction started
+    pub pc: u16,
+
+    // Scratch registers for whatever's in flight
+    pub address: u16,
+    pub pointer: u8,
+    pub data: u8,
+    pub page_crossed: bool,
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct InterruptSamples {
+    pub nmi: bool,
+    pub irq: bool,
+    pub prev_nmi: bool,
+    pub prev_irq: bool,
+}
+
+// Sequences are saved as a kind (0 for none) and the opcode byte or
+// interrupt that identifies them
+impl SaveState for CycleState {
+    fn save_state(&self, state: &mut StateWriter) {
+        let (kind, id) = match self.sequence {
+            None => (0, 0),
+            Some(Sequence::Instruction(decoded)) => (1, opcodes::OPCODES.iter().position(|opcode| *opcode == decoded).unwrap_or(0) as u8),
+            Some(Sequence::Interrupt(interrupt)) => (2, interrupt as u8),
+        };
+
+        state.u8(kind);
+        state.u8(id);
+        state.u8(self.cycle);
+        state.u8(self.operation_cycle);
+        state.u16(self.pc);
+        state.u16(self.address);
+        state.u8(self.pointer);
+        state.u8(self.data);
+        state.bool(self.page_crossed);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), StateError> {
+        let kind = state.u8();
+        let id = state.u8();
+
+        self.sequence = match (kind, id) {
+            (0, _) => None,
+            (1, opcode) => Some(Sequence::Instruction(opcodes::OPCODES[opcode as usize])),
+            (2, 0) => Some(Sequence::Interrupt(Interrupt::Reset)),
+            (2, 1) => Some(Sequence::Interrupt(Interrupt::Nmi)),
+            (2, 2) => Some(Sequence::Interrupt(Interrupt::Irq)),
+            (2, 3) => Some(Sequence::Interrupt(Interrupt::Brk)),
+            _ => return Err(StateError::Mismatch("cpu sequence")),
+        };
+
+        self.cycle = state.u8();
+        self.operation_cycle = state.u8();
+        self.pc = state.u16();
+        self.address = state.u16();
+        self.pointer = state.u8();
+        self.data = state.u8();
+        self.page_crossed = state.bool();
+
+        Ok(())
+    }
+}
+
+impl SaveState for InterruptSamples {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.bool(self.nmi);
+        state.bool(self.irq);
+        state.bool(self.prev_nmi);
+        state.bool(self.prev_irq);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), StateError> {
+        self.nmi = state.bool();
+        self.irq = state.bool();
+        self.prev_nmi = state.bool();
+        self.prev_irq = state.bool();
+
+        Ok(())
+    }
+}
+
+// Runs a single cycle, returning true if it finished an instruction (or
+// interrupt sequence)
+pub fn tick<T: MemoryMapper>(cpu: &mut Cpu<T>) -> bool {
+    // dma halts the cpu on a read, and the only read we're sure of is the
+    // next opcode fetch
+    if cpu.state.sequence.is_none() && cpu.memory_map.dma_pending() {
+        let cycle = cpu.cycles;
+        cpu.memory_map.dma_cycle(cycle);
+        end_cycle(cpu);
+
+        return false;
+    }
+
+    // an illegal opcode or a jam stops the cpu (interrupts and all) until it's
+    // reset, but the rest of the console carries on
+    if cpu.illegal_opcode.is_some() || cpu.jammed {
+        end_cycle(cpu);
+
+        return true;
+    }
+
+    cpu.state.cycle += 1;
+
+    let done = match cpu.state.sequence {
+        None => {
+            begin(cpu);
+            false
+        }
+        Some(Sequence::Instruction(decoded)) => instruction_cycle(cpu, decoded),
+        Some(Sequence::Interrupt(interrupt)) => interrupt::step(cpu, interrupt, cpu.state.cycle),
+    };
+
+    end_cycle(cpu);
+
+    done
+}
+
+fn end_cycle<T: MemoryMapper>(cpu: &mut Cpu<T>) {
+    cpu.cycles += 1;
+    cpu.memory_map.tick();
+    poll_interrupts(cpu);
+}
+
+// Starts a sequence from scratch, e.g. for reset
+pub fn start<T: MemoryMapper>(cpu: &mut Cpu<T>, sequence: Sequence) {
+    cpu.state = CycleState {
+        sequence: Some(sequence),
+        pc: cpu.reg_program_counter,
+        ..CycleState::default()
+    };
+}
+
+fn begin<T: MemoryMapper>(cpu: &mut Cpu<T>) {
+    let pc = cpu.reg_program_counter;
+    cpu.state.pc = pc;
+
+    let interrupt = match () {
+        _ if cpu.interrupt_samples.prev_nmi => Some(Interrupt::Nmi),
+        _ if cpu.interrupt_samples.prev_irq => Some(Interrupt::Irq),
+        _ => None,
+    };
+
+    match interrupt {
+        Some(interrupt) => {
+            // the opcode fetch still happens, but gets thrown away
+            cpu.state.sequence = Some(Sequence::Interrupt(interrupt));
+            interrupt::step(cpu, interrupt, 1);
+        }
+        None => {
+            let opcode = cpu.next_word();
+            match opcodes::decode(opcode, cpu.opcode_set) {
+                Some(decoded) => cpu.state.sequence = Some(Sequence::Instruction(decoded)),
+                None => {
+                    cpu.illegal_opcode = Some((opcode, pc));
+                    cpu.reg_program_counter = pc;
+                }
+            }
+        }
+    }
+}
+
+fn instruction_cycle<T: MemoryMapper>(cpu: &mut Cpu<T>, decoded: Opcode) -> bool {
+    let cycle = cpu.state.cycle;
+
+    match decoded.instruction.operation() {
+        Operation::Implied(implied) => {
+            dummy_read_pc(cpu);
+            implied(cpu);
+
+            true
+        }
+        Operation::ReadModifyWrite(modify) if decoded.mode == AddressingMode::Accumulator => {
+            dummy_read_pc(cpu);
+
+            let acc = cpu.reg_accumulator;
+            cpu.reg_accumulator = modify(cpu, acc);
+
+            true
+        }
+        Operation::Push(push) => {
+            match cycle {
+                2 => {
+                    dummy_read_pc(cpu);
+                    false
+                }
+                _ => {
+                    let value = push(cpu);
+                    cpu.push(value);
+
+                    true
+                }
+            }
+        }
+        Operation::Pull(pull) => {
+            match cycle {
+                2 => {
+                    dummy_read_pc(cpu);
+                    false
+                }
+                3 => {
+                    let sp = cpu.resolve_stack_pointer();
+                    cpu.read(sp);
+                    false
+                }
+                _ => {
+                    let value = cpu.pop();
+                    pull(cpu, value);
+
+                    true
+                }
+            }
+        }
+        Operation::Branch(should_branch) => branch_cycle(cpu, should_branch, cycle),
+        Operation::Jump(jump) => jump_cycle(cpu, jump, decoded.mode, cycle),
+        Operation::Control(control) => control(cpu, cycle),
+        operation => {
+            if cpu.state.operation_cycle == 0 {
+                let is_read = matches!(operation, Operation::Read(_));
+
+                if !addressing::step(cpu, decoded.mode, cycle - 1, is_read) {
+                    return false;
+                }
+            }
+
+            cpu.state.operation_cycle += 1;
+            operate(cpu, operation)
+        }
+    }
+}
+
+// The accesses instructions make to their resolved operand address
+fn operate<T: MemoryMapper>(cpu: &mut Cpu<T>, operation: Operation<T>) -> bool {
+    let address = cpu.state.address;
+
+    match operation {
+        Operation::Read(read) => {
+            let value = cpu.read(address);
+            read(cpu, value);
+
+            true
+        }
+        Operation::Write(write) => {
+            let value = write(cpu);
+            cpu.write(address, value);
+
+            true
+        }
+        Operation::AddressedWrite(write) => {
+            write(cpu, address);
+
+            true
+        }
+        Operation::ReadModifyWrite(modify) => {
+            match cpu.state.operation_cycle {
+                1 => {
+                    cpu.state.data = cpu.read(address);
+                    false
+                }
+                2 => {
+                    // the unmodified value goes back out while the alu works
+                    let value = cpu.state.data;
+                    cpu.write(address, value);
+                    cpu.state.data = modify(cpu, value);
+
+                    false
+                }
+                _ => {
+                    let result = cpu.state.data;
+                    cpu.write(address, result);
+
+                    true
+                }
+            }
+        }
+        _ => unreachable!(),
+    }
+}
+
+fn branch_cycle<T: MemoryMapper>(cpu: &mut Cpu<T>, should_branch: fn(&::cpu::ProcessorStatus) -> bool, cycle: u8) -> bool {
+    match cycle {
+        2 => {
+            // relative values are signed and taken from the following instruction
+            let relative_address = cpu.next_signed_word();
+            cpu.state.address = cpu.reg_program_counter.wrapping_add(relative_address as i16 as u16);
+
+            !should_branch(&cpu.processor_status)
+        }
+        3 => {
+            // a taken branch that stays on its page doesn't poll interrupts on
+            // its last cycle, so anything that just came in waits an
+            // instruction longer
+            let samples = &mut cpu.interrupt_samples;
+            samples.irq = samples.irq && samples.prev_irq;
+            samples.nmi = samples.nmi && samples.prev_nmi;
+
+            dummy_read_pc(cpu);
+
+            let pc = cpu.reg_program_counter;
+            let target = cpu.state.address;
+
+            match memory_map::crosses_page_boundary(pc, target) {
+                false => {
+                    cpu.reg_program_counter = target;
+                    true
+                }
+                true => {
+                    cpu.reg_program_counter = (pc & 0xff00) | (target & 0x00ff);
+                    false
+                }
+            }
+        }
+        _ => {
+            dummy_read_pc(cpu);
+            cpu.reg_program_counter = cpu.state.address;
+
+            true
+        }
+    }
+}
+
+fn jump_cycle<T: MemoryMapper>(cpu: &mut Cpu<T>, jump: fn(&mut Cpu<T>, u16), mode: AddressingMode, cycle: u8) -> bool {
+    match (mode, cycle) {
+        (_, 2) => {
+            cpu.state.address = cpu.next_word() as u16;
+            false
+        }
+        (AddressingMode::Absolute, _) => {
+            let hi = cpu.read(cpu.reg_program_counter) as u16;
+            let address = (hi << 8) | cpu.state.address;
+            jump(cpu, address);
+
+            true
+        }
+        (_, 3) => {
+            let hi = cpu.next_word() as u16;
+            cpu.state.address |= hi << 8;
+            false
+        }
+        (_, 4) => {
+            let pointer = cpu.state.address;
+            cpu.state.data = cpu.read(pointer);
+            false
+        }
+        _ => {
+            // the 6502 never carries into the high byte of the pointer, so
+            // jmp ($10ff) reads its high byte from $1000
+            let pointer = cpu.state.address;
+            let hi = cpu.read((pointer & 0xff00) | (pointer.wrapping_add(1) & 0x00ff)) as u16;
+            let address = (hi << 8) | (cpu.state.data as u16);
+            jump(cpu, address);
+
+            true
+        }
+    }
+}
+
+fn dummy_read_pc<T: MemoryMapper>(cpu: &mut Cpu<T>) {
+    let pc = cpu.reg_program_counter;
+    cpu.read(pc);
+}
+
+fn poll_interrupts<T: MemoryMapper>(cpu: &mut Cpu<T>) {
+    let memory_map_nmi_line = cpu.memory_map.nmi();
+    if memory_map_nmi_line && !cpu.memory_map_nmi_line {
+        cpu.nmi_pending = true;
+    }
+    cpu.memory_map_nmi_line = memory_map_nmi_line;
+
+    let nmi = cpu.nmi_pending;
+    let irq = (cpu.irq_line || cpu.memory_map.irq()) && !cpu.processor_status.interrupts_disabled;
+
+    let samples = &mut cpu.interrupt_samples;
+    samples.prev_nmi = samples.nmi;
+    samples.prev_irq = samples.irq;
+    samples.nmi = nmi;
+    samples.irq = irq;
+}
@@ -1,5 +1,6 @@
-pub trait CpuDebug {
-    fn exec_instr(&mut self, instruction: &[u8]);
-    fn set_last_instr_disasm(&mut self, disassembly: String);
-    fn set_last_instr_disasm_str(&mut self, disassembly: &'static str);
+pub trait CpuDebug {
+    fn exec_instr(&mut self, instruction: &[u8]);
+    fn exec_asm(&mut self, source: &str);
+    fn set_last_instr_disasm(&mut self, disassembly: String);
+    fn set_last_instr_disasm_str(&mut self, disassembly: &'static str);
 }
\ No newline at end of file
@@ -0,0 +1,174 @@
+use ::cpu::addressing::AddressingMode;
+use ::cpu::opcodes;
+use ::memory_map::MemoryMapper;
+
+use std::fmt;
+
+// [Disassembler]
+// Turns bytes back into instructions without running anything, so it works
+// on any slice or any range of a MemoryMapper. Output uses the usual 6502
+// syntax:
+//
+// LDA #$10      immediate
+// LDA $10       zero page
+// LDA $10,X     zero page indexed
+// LDA $1234,Y   absolute indexed
+// JMP ($1234)   indirect
+// LDA ($10,X)   indexed indirect
+// LDA ($10),Y   indirect indexed
+// BNE $C0F2     relative (shown as the branch target)
+// ASL A         accumulator
+//
+// Unofficial opcodes get a * in front of their mnemonic. Bytes at the end
+// that don't make up a whole instruction come out as .byte lines.
+//
+// [Resources]
+// syntax => http://www.obelisk.me.uk/6502/addressing.html
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Line {
+    pub address: u16,
+    pub bytes: Vec<u8>,
+    pub text: String,
+}
+
+impl fmt::Display for Line {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let bytes = self.bytes.iter().map(|byte| format!("{:02X}", byte)).collect::<Vec<String>>().join(" ");
+
+        write!(f, "{:04X}  {:<8}  {}", self.address, bytes, self.text)
+    }
+}
+
+// Disassembles the instruction at the start of bytes, or None if bytes runs
+// out before its operand does
+pub fn disassemble_one(bytes: &[u8], address: u16) -> Option<Line> {
+    let decoded = opcodes::OPCODES[*bytes.first()? as usize];
+    let len = decoded.mode.operand_len() as usize + 1;
+
+    if bytes.len() < len {
+        return None;
+    }
+
+    let bytes = &bytes[..len];
+    let unofficial = match decoded.official {
+        true => "",
+        false => "*",
+    };
+
+    let mnemonic = decoded.instruction.mnemonic().to_uppercase();
+    let text = match decoded.mode {
+        AddressingMode::Implied => format!("{}{}", unofficial, mnemonic),
+        mode => format!("{}{} {}", unofficial, mnemonic, operand(mode, bytes, address)),
+    };
+
+    Some(Line {
+        address,
+        bytes: bytes.to_vec(),
+        text,
+    })
+}
+
+// Disassembles bytes as if they were loaded at origin
+pub fn disassemble(bytes: &[u8], origin: u16) -> Vec<Line> {
+    let mut lines = vec![];
+    let mut offset = 0;
+
+    while offset < bytes.len() {
+        let address = origin.wrapping_add(offset as u16);
+
+        let line = match disassemble_one(&bytes[offset..], address) {
+            Some(line) => line,
+            None => {
+                Line {
+                    address,
+                    bytes: vec![bytes[offset]],
+                    text: format!(".byte ${:02X}", bytes[offset]),
+                }
+            }
+        };
+
+        offset += line.bytes.len();
+        lines.push(line);
+    }
+
+    lines
+}
+
+// Disassembles start..=end as seen through a memory map
+pub fn disassemble_range<T: MemoryMapper>(memory_map: &T, start: u16, end: u16) -> Vec<Line> {
+    let len = end.wrapping_sub(start) as usize + 1;
+    let bytes = (0..len).map(|i| memory_map.peek(start.wrapping_add(i as u16))).collect::<Vec<u8>>();
+
+    disassemble(&bytes, start)
+}
+
+// Formats an operand in the given mode; bytes starts with the opcode
+pub fn operand(mode: AddressingMode, bytes: &[u8], address: u16) -> String {
+    let lo = bytes.get(1).cloned().unwrap_or(0);
+    let word = (bytes.get(2).cloned().unwrap_or(0) as u16) << 8 | lo as u16;
+
+    match mode {
+        AddressingMode::Implied => String::new(),
+        AddressingMode::Accumulator => "A".to_string(),
+        AddressingMode::Immediate => format!("#${:02X}", lo),
+        AddressingMode::ZeroPage => format!("${:02X}", lo),
+        AddressingMode::ZeroPageX => format!("${:02X},X", lo),
+        AddressingMode::ZeroPageY => format!("${:02X},Y", lo),
+        AddressingMode::Absolute => format!("${:04X}", word),
+        AddressingMode::AbsoluteX => format!("${:04X},X", word),
+        AddressingMode::AbsoluteY => format!("${:04X},Y", word),
+        AddressingMode::Indirect => format!("(${:04X})", word),
+        AddressingMode::IndirectX => format!("(${:02X},X)", lo),
+        AddressingMode::IndirectY => format!("(${:02X}),Y", lo),
+        AddressingMode::Relative => format!("${:04X}", address.wrapping_add(2).wrapping_add(lo as i8 as u16)),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn texts(lines: &[Line]) -> Vec<&str> {
+        lines.iter().map(|line| line.text.as_str()).collect()
+    }
+
+    #[test]
+    pub fn test_operand_syntax() {
+        let bytes = [
+            0xa9, 0x10,       // lda #$10
+            0xb5, 0x10,       // lda $10,x
+            0xb6, 0x10,       // ldx $10,y
+            0xb9, 0x34, 0x12, // lda $1234,y
+            0x6c, 0x34, 0x12, // jmp ($1234)
+            0xa1, 0x10,       // lda ($10,x)
+            0xb1, 0x10,       // lda ($10),y
+            0x4a,             // lsr a
+            0xd0, 0xfe,       // bne *
+            0x60,             // rts
+        ];
+
+        assert_eq!(texts(&disassemble(&bytes, 0xc000)),
+                   vec!["LDA #$10", "LDA $10,X", "LDX $10,Y", "LDA $1234,Y", "JMP ($1234)", "LDA ($10,X)", "LDA ($10),Y", "LSR A", "BNE $C011", "RTS"]);
+    }
+
+    #[test]
+    pub fn test_unofficial_and_truncated() {
+        // *nop $a9, then an lda abs cut off after its low byte (which reads as brk)
+        let lines = disassemble(&[0x04, 0xa9, 0xad, 0x00], 0x8000);
+
+        assert_eq!(texts(&lines), vec!["*NOP $A9", ".byte $AD", "BRK"]);
+        assert_eq!(lines[1].address, 0x8002);
+        assert_eq!(lines[0].to_string(), "8000  04 A9     *NOP $A9");
+    }
+
+    #[test]
+    pub fn test_disassemble_range() {
+        let mut map = ::memory_map::FlatMemoryMap::default();
+        map.write(0x8000, 0x8d);
+        map.write_u16(0x8001, 0x2000);
+
+        let lines = disassemble_range(&map, 0x8000, 0x8002);
+        assert_eq!(texts(&lines), vec!["STA $2000"]);
+    }
+}
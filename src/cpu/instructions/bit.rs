@@ -1,18 +1,11 @@
-use ::cpu::Cpu;
-use ::memory_map::MemoryMapper;
-
-use ::cpu::CpuDebug;
-
-pub fn bit_zero_page<T: MemoryMapper>(cpu: &mut Cpu<T>) {
-    let address = cpu.next_word() as u16;
-    let value = cpu.read(address) as i8;
-    let result = cpu.reg_accumulator & value;
-
-    cpu.processor_status.zero = result == 0;
-    cpu.processor_status.negative = result < 0;
-    cpu.processor_status.overflow_flag = ((result & (0b0100_0000)) >> 6) == 1;
-
-    cpu.take_cycles(3);
-
-    cpu.set_last_instr_disasm(format!("bit {:#x}", address));
-}
\ No newline at end of file
+use ::cpu::Cpu;
+use ::memory_map::MemoryMapper;
+
+pub fn bit<T: MemoryMapper>(cpu: &mut Cpu<T>, value: u8) {
+    let result = cpu.reg_accumulator & value;
+
+    // n and v come straight from the operand, not from the result
+    cpu.processor_status.zero = result == 0;
+    cpu.processor_status.negative = value & 0b1000_0000 != 0;
+    cpu.processor_status.overflow_flag = value & 0b0100_0000 != 0;
+}
@@ -0,0 +1,27 @@
+use ::cpu::Cpu;
+use ::memory_map::MemoryMapper;
+
+pub fn cmp<T: MemoryMapper>(cpu: &mut Cpu<T>, value: u8) {
+    let acc = cpu.reg_accumulator;
+
+    compare(cpu, acc, value);
+}
+
+pub fn cpx<T: MemoryMapper>(cpu: &mut Cpu<T>, value: u8) {
+    let x = cpu.reg_index_x;
+
+    compare(cpu, x, value);
+}
+
+pub fn cpy<T: MemoryMapper>(cpu: &mut Cpu<T>, value: u8) {
+    let y = cpu.reg_index_y;
+
+    compare(cpu, y, value);
+}
+
+pub fn compare<T: MemoryMapper>(cpu: &mut Cpu<T>, register: u8, value: u8) {
+    let result = register.wrapping_sub(value);
+
+    cpu.processor_status.carry_flag = register >= value;
+    cpu.set_zero_and_negative(result);
+}
@@ -0,0 +1,30 @@
+use ::cpu::Cpu;
+use ::memory_map::MemoryMapper;
+
+pub fn clc<T: MemoryMapper>(cpu: &mut Cpu<T>) {
+    cpu.processor_status.carry_flag = false;
+}
+
+pub fn sec<T: MemoryMapper>(cpu: &mut Cpu<T>) {
+    cpu.processor_status.carry_flag = true;
+}
+
+pub fn cli<T: MemoryMapper>(cpu: &mut Cpu<T>) {
+    cpu.processor_status.interrupts_disabled = false;
+}
+
+pub fn sei<T: MemoryMapper>(cpu: &mut Cpu<T>) {
+    cpu.processor_status.interrupts_disabled = true;
+}
+
+pub fn cld<T: MemoryMapper>(cpu: &mut Cpu<T>) {
+    cpu.processor_status.decimal_mode = false;
+}
+
+pub fn sed<T: MemoryMapper>(cpu: &mut Cpu<T>) {
+    cpu.processor_status.decimal_mode = true;
+}
+
+pub fn clv<T: MemoryMapper>(cpu: &mut Cpu<T>) {
+    cpu.processor_status.overflow_flag = false;
+}
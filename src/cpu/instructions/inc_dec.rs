@@ -0,0 +1,36 @@
+use ::cpu::Cpu;
+use ::memory_map::MemoryMapper;
+
+pub fn inc<T: MemoryMapper>(cpu: &mut Cpu<T>, value: u8) -> u8 {
+    let result = value.wrapping_add(1);
+    cpu.set_zero_and_negative(result);
+
+    result
+}
+
+pub fn dec<T: MemoryMapper>(cpu: &mut Cpu<T>, value: u8) -> u8 {
+    let result = value.wrapping_sub(1);
+    cpu.set_zero_and_negative(result);
+
+    result
+}
+
+pub fn inx<T: MemoryMapper>(cpu: &mut Cpu<T>) {
+    let x = cpu.reg_index_x;
+    cpu.reg_index_x = inc(cpu, x);
+}
+
+pub fn iny<T: MemoryMapper>(cpu: &mut Cpu<T>) {
+    let y = cpu.reg_index_y;
+    cpu.reg_index_y = inc(cpu, y);
+}
+
+pub fn dex<T: MemoryMapper>(cpu: &mut Cpu<T>) {
+    let x = cpu.reg_index_x;
+    cpu.reg_index_x = dec(cpu, x);
+}
+
+pub fn dey<T: MemoryMapper>(cpu: &mut Cpu<T>) {
+    let y = cpu.reg_index_y;
+    cpu.reg_index_y = dec(cpu, y);
+}
@@ -0,0 +1,107 @@
+use ::cpu::Cpu;
+use ::cpu::CpuStack;
+use ::cpu::interrupt;
+use ::cpu::interrupt::Interrupt;
+use ::memory_map::MemoryMapper;
+
+// Subroutine and interrupt returns move pc through the stack in their own
+// order, so each of these runs one cycle at a time (cycle 2 being the one
+// right after the opcode fetch) and returns true when it's done.
+
+pub fn jmp<T: MemoryMapper>(cpu: &mut Cpu<T>, address: u16) {
+    cpu.reg_program_counter = address;
+}
+
+pub fn jsr<T: MemoryMapper>(cpu: &mut Cpu<T>, cycle: u8) -> bool {
+    match cycle {
+        2 => {
+            cpu.state.data = cpu.next_word();
+        }
+        3 => {
+            // internal operation; the bus just sees a read of the stack
+            let sp = cpu.resolve_stack_pointer();
+            cpu.read(sp);
+        }
+        4 => {
+            // the pushed return address points at the last byte of the jsr
+            let pch = (cpu.reg_program_counter >> 8) as u8;
+            cpu.push(pch);
+        }
+        5 => {
+            let pcl = cpu.reg_program_counter as u8;
+            cpu.push(pcl);
+        }
+        _ => {
+            let pc = cpu.reg_program_counter;
+            let hi = cpu.read(pc) as u16;
+            cpu.reg_program_counter = (hi << 8) | (cpu.state.data as u16);
+
+            return true;
+        }
+    }
+
+    false
+}
+
+pub fn rts<T: MemoryMapper>(cpu: &mut Cpu<T>, cycle: u8) -> bool {
+    match cycle {
+        2 => {
+            let pc = cpu.reg_program_counter;
+            cpu.read(pc);
+        }
+        3 => {
+            let sp = cpu.resolve_stack_pointer();
+            cpu.read(sp);
+        }
+        4 => {
+            cpu.state.data = cpu.pop();
+        }
+        5 => {
+            let hi = cpu.pop() as u16;
+            cpu.reg_program_counter = (hi << 8) | (cpu.state.data as u16);
+        }
+        _ => {
+            // get the stored return address, then add 1 to go to next instr
+            let pc = cpu.reg_program_counter;
+            cpu.read(pc);
+            cpu.reg_program_counter = pc.wrapping_add(1);
+
+            return true;
+        }
+    }
+
+    false
+}
+
+pub fn rti<T: MemoryMapper>(cpu: &mut Cpu<T>, cycle: u8) -> bool {
+    match cycle {
+        2 => {
+            let pc = cpu.reg_program_counter;
+            cpu.read(pc);
+        }
+        3 => {
+            let sp = cpu.resolve_stack_pointer();
+            cpu.read(sp);
+        }
+        4 => {
+            let status = cpu.pop();
+            cpu.set_status_from_stack(status);
+        }
+        5 => {
+            cpu.state.data = cpu.pop();
+        }
+        _ => {
+            // unlike rts, the address on the stack is the exact return address
+            let hi = cpu.pop() as u16;
+            cpu.reg_program_counter = (hi << 8) | (cpu.state.data as u16);
+
+            return true;
+        }
+    }
+
+    false
+}
+
+pub fn brk<T: MemoryMapper>(cpu: &mut Cpu<T>, cycle: u8) -> bool {
+    interrupt::step(cpu, Interrupt::Brk, cycle)
+}
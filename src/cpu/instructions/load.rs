@@ -0,0 +1,17 @@
+use ::cpu::Cpu;
+use ::memory_map::MemoryMapper;
+
+pub fn lda<T: MemoryMapper>(cpu: &mut Cpu<T>, value: u8) {
+    cpu.reg_accumulator = value;
+    cpu.set_zero_and_negative(value);
+}
+
+pub fn ldx<T: MemoryMapper>(cpu: &mut Cpu<T>, value: u8) {
+    cpu.reg_index_x = value;
+    cpu.set_zero_and_negative(value);
+}
+
+pub fn ldy<T: MemoryMapper>(cpu: &mut Cpu<T>, value: u8) {
+    cpu.reg_index_y = value;
+    cpu.set_zero_and_negative(value);
+}
@@ -0,0 +1,23 @@
+use ::cpu::Cpu;
+use ::memory_map::MemoryMapper;
+
+pub fn and<T: MemoryMapper>(cpu: &mut Cpu<T>, value: u8) {
+    let result = cpu.reg_accumulator & value;
+
+    cpu.reg_accumulator = result;
+    cpu.set_zero_and_negative(result);
+}
+
+pub fn ora<T: MemoryMapper>(cpu: &mut Cpu<T>, value: u8) {
+    let result = cpu.reg_accumulator | value;
+
+    cpu.reg_accumulator = result;
+    cpu.set_zero_and_negative(result);
+}
+
+pub fn eor<T: MemoryMapper>(cpu: &mut Cpu<T>, value: u8) {
+    let result = cpu.reg_accumulator ^ value;
+
+    cpu.reg_accumulator = result;
+    cpu.set_zero_and_negative(result);
+}
@@ -1,3 +1,298 @@
-pub mod adc;
-pub mod bit;
-pub mod branch;
+pub mod adc;
+pub mod bit;
+pub mod branch;
+pub mod compare;
+pub mod flags;
+pub mod inc_dec;
+pub mod jump;
+pub mod load;
+pub mod logical;
+pub mod nop;
+pub mod shift;
+pub mod stack;
+pub mod store;
+pub mod transfer;
+pub mod unofficial;
+
+use ::cpu::Cpu;
+use ::cpu::ProcessorStatus;
+use ::memory_map::MemoryMapper;
+
+// [Instructions]
+// Each instruction is one operation function in one of the modules above.
+// Operations are grouped by how they touch their operand so the cpu can do
+// the reading and writing for them:
+//
+// Read            => gets the operand value (lda, adc, cmp, ...)
+// Write           => produces the value to store (sta, stx, sty)
+// ReadModifyWrite => gets the value and returns the new one (asl, inc, ...)
+// Implied         => no operand at all (tax, clc, ...)
+// Push            => produces the value to push (pha, php)
+// Pull            => gets the pulled value (pla, plp)
+// Jump            => gets the effective address (jmp)
+// AddressedWrite  => does its own write to the effective address (shx, ...)
+// Branch          => decides from the flags whether to branch
+// Control         => runs its own cycles, one call per cycle (jsr, rts, ...)
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Instruction {
+    Adc,
+    And,
+    Asl,
+    Bcc,
+    Bcs,
+    Beq,
+    Bit,
+    Bmi,
+    Bne,
+    Bpl,
+    Brk,
+    Bvc,
+    Bvs,
+    Clc,
+    Cld,
+    Cli,
+    Clv,
+    Cmp,
+    Cpx,
+    Cpy,
+    Dec,
+    Dex,
+    Dey,
+    Eor,
+    Inc,
+    Inx,
+    Iny,
+    Jmp,
+    Jsr,
+    Lda,
+    Ldx,
+    Ldy,
+    Lsr,
+    Nop,
+    Ora,
+    Pha,
+    Php,
+    Pla,
+    Plp,
+    Rol,
+    Ror,
+    Rti,
+    Rts,
+    Sbc,
+    Sec,
+    Sed,
+    Sei,
+    Sta,
+    Stx,
+    Sty,
+    Tax,
+    Tay,
+    Tsx,
+    Txa,
+    Txs,
+    Tya,
+
+    // Unofficial
+    Ahx,
+    Alr,
+    Anc,
+    Arr,
+    Axs,
+    Dcp,
+    Ign, // the nops that read (and ignore) an operand
+    Isb,
+    Jam,
+    Las,
+    Lax,
+    Lxa, // lax #imm, which doesn't behave like the other lax modes
+    Rla,
+    Rra,
+    Sax,
+    Shx,
+    Shy,
+    Slo,
+    Sre,
+    Tas,
+    Xaa,
+}
+
+pub enum Operation<T: MemoryMapper> {
+    Read(fn(&mut Cpu<T>, u8)),
+    Write(fn(&mut Cpu<T>) -> u8),
+    ReadModifyWrite(fn(&mut Cpu<T>, u8) -> u8),
+    Implied(fn(&mut Cpu<T>)),
+    Push(fn(&mut Cpu<T>) -> u8),
+    Pull(fn(&mut Cpu<T>, u8)),
+    Jump(fn(&mut Cpu<T>, u16)),
+    AddressedWrite(fn(&mut Cpu<T>, u16)),
+    Branch(fn(&ProcessorStatus) -> bool),
+    Control(fn(&mut Cpu<T>, u8) -> bool),
+}
+
+impl Instruction {
+    pub fn mnemonic(&self) -> &'static str {
+        match *self {
+            Instruction::Adc => "adc",
+            Instruction::And => "and",
+            Instruction::Asl => "asl",
+            Instruction::Bcc => "bcc",
+            Instruction::Bcs => "bcs",
+            Instruction::Beq => "beq",
+            Instruction::Bit => "bit",
+            Instruction::Bmi => "bmi",
+            Instruction::Bne => "bne",
+            Instruction::Bpl => "bpl",
+            Instruction::Brk => "brk",
+            Instruction::Bvc => "bvc",
+            Instruction::Bvs => "bvs",
+            Instruction::Clc => "clc",
+            Instruction::Cld => "cld",
+            Instruction::Cli => "cli",
+            Instruction::Clv => "clv",
+            Instruction::Cmp => "cmp",
+            Instruction::Cpx => "cpx",
+            Instruction::Cpy => "cpy",
+            Instruction::Dec => "dec",
+            Instruction::Dex => "dex",
+            Instruction::Dey => "dey",
+            Instruction::Eor => "eor",
+            Instruction::Inc => "inc",
+            Instruction::Inx => "inx",
+            Instruction::Iny => "iny",
+            Instruction::Jmp => "jmp",
+            Instruction::Jsr => "jsr",
+            Instruction::Lda => "lda",
+            Instruction::Ldx => "ldx",
+            Instruction::Ldy => "ldy",
+            Instruction::Lsr => "lsr",
+            Instruction::Nop => "nop",
+            Instruction::Ora => "ora",
+            Instruction::Pha => "pha",
+            Instruction::Php => "php",
+            Instruction::Pla => "pla",
+            Instruction::Plp => "plp",
+            Instruction::Rol => "rol",
+            Instruction::Ror => "ror",
+            Instruction::Rti => "rti",
+            Instruction::Rts => "rts",
+            Instruction::Sbc => "sbc",
+            Instruction::Sec => "sec",
+            Instruction::Sed => "sed",
+            Instruction::Sei => "sei",
+            Instruction::Sta => "sta",
+            Instruction::Stx => "stx",
+            Instruction::Sty => "sty",
+            Instruction::Tax => "tax",
+            Instruction::Tay => "tay",
+            Instruction::Tsx => "tsx",
+            Instruction::Txa => "txa",
+            Instruction::Txs => "txs",
+            Instruction::Tya => "tya",
+            Instruction::Ahx => "ahx",
+            Instruction::Alr => "alr",
+            Instruction::Anc => "anc",
+            Instruction::Arr => "arr",
+            Instruction::Axs => "axs",
+            Instruction::Dcp => "dcp",
+            Instruction::Ign => "nop",
+            Instruction::Isb => "isb",
+            Instruction::Jam => "jam",
+            Instruction::Las => "las",
+            Instruction::Lax => "lax",
+            Instruction::Lxa => "lax",
+            Instruction::Rla => "rla",
+            Instruction::Rra => "rra",
+            Instruction::Sax => "sax",
+            Instruction::Shx => "shx",
+            Instruction::Shy => "shy",
+            Instruction::Slo => "slo",
+            Instruction::Sre => "sre",
+            Instruction::Tas => "tas",
+            Instruction::Xaa => "xaa",
+        }
+    }
+
+    pub fn operation<T: MemoryMapper>(&self) -> Operation<T> {
+        match *self {
+            Instruction::Adc => Operation::Read(adc::adc),
+            Instruction::And => Operation::Read(logical::and),
+            Instruction::Asl => Operation::ReadModifyWrite(shift::asl),
+            Instruction::Bcc => Operation::Branch(branch::bcc),
+            Instruction::Bcs => Operation::Branch(branch::bcs),
+            Instruction::Beq => Operation::Branch(branch::beq),
+            Instruction::Bit => Operation::Read(bit::bit),
+            Instruction::Bmi => Operation::Branch(branch::bmi),
+            Instruction::Bne => Operation::Branch(branch::bne),
+            Instruction::Bpl => Operation::Branch(branch::bpl),
+            Instruction::Brk => Operation::Control(jump::brk),
+            Instruction::Bvc => Operation::Branch(branch::bvc),
+            Instruction::Bvs => Operation::Branch(branch::bvs),
+            Instruction::Clc => Operation::Implied(flags::clc),
+            Instruction::Cld => Operation::Implied(flags::cld),
+            Instruction::Cli => Operation::Implied(flags::cli),
+            Instruction::Clv => Operation::Implied(flags::clv),
+            Instruction::Cmp => Operation::Read(compare::cmp),
+            Instruction::Cpx => Operation::Read(compare::cpx),
+            Instruction::Cpy => Operation::Read(compare::cpy),
+            Instruction::Dec => Operation::ReadModifyWrite(inc_dec::dec),
+            Instruction::Dex => Operation::Implied(inc_dec::dex),
+            Instruction::Dey => Operation::Implied(inc_dec::dey),
+            Instruction::Eor => Operation::Read(logical::eor),
+            Instruction::Inc => Operation::ReadModifyWrite(inc_dec::inc),
+            Instruction::Inx => Operation::Implied(inc_dec::inx),
+            Instruction::Iny => Operation::Implied(inc_dec::iny),
+            Instruction::Jmp => Operation::Jump(jump::jmp),
+            Instruction::Jsr => Operation::Control(jump::jsr),
+            Instruction::Lda => Operation::Read(load::lda),
+            Instruction::Ldx => Operation::Read(load::ldx),
+            Instruction::Ldy => Operation::Read(load::ldy),
+            Instruction::Lsr => Operation::ReadModifyWrite(shift::lsr),
+            Instruction::Nop => Operation::Implied(nop::nop),
+            Instruction::Ora => Operation::Read(logical::ora),
+            Instruction::Pha => Operation::Push(stack::pha),
+            Instruction::Php => Operation::Push(stack::php),
+            Instruction::Pla => Operation::Pull(stack::pla),
+            Instruction::Plp => Operation::Pull(stack::plp),
+            Instruction::Rol => Operation::ReadModifyWrite(shift::rol),
+            Instruction::Ror => Operation::ReadModifyWrite(shift::ror),
+            Instruction::Rti => Operation::Control(jump::rti),
+            Instruction::Rts => Operation::Control(jump::rts),
+            Instruction::Sbc => Operation::Read(adc::sbc),
+            Instruction::Sec => Operation::Implied(flags::sec),
+            Instruction::Sed => Operation::Implied(flags::sed),
+            Instruction::Sei => Operation::Implied(flags::sei),
+            Instruction::Sta => Operation::Write(store::sta),
+            Instruction::Stx => Operation::Write(store::stx),
+            Instruction::Sty => Operation::Write(store::sty),
+            Instruction::Tax => Operation::Implied(transfer::tax),
+            Instruction::Tay => Operation::Implied(transfer::tay),
+            Instruction::Tsx => Operation::Implied(transfer::tsx),
+            Instruction::Txa => Operation::Implied(transfer::txa),
+            Instruction::Txs => Operation::Implied(transfer::txs),
+            Instruction::Tya => Operation::Implied(transfer::tya),
+
+            Instruction::Ahx => Operation::AddressedWrite(unofficial::ahx),
+            Instruction::Alr => Operation::Read(unofficial::alr),
+            Instruction::Anc => Operation::Read(unofficial::anc),
+            Instruction::Arr => Operation::Read(unofficial::arr),
+            Instruction::Axs => Operation::Read(unofficial::axs),
+            Instruction::Dcp => Operation::ReadModifyWrite(unofficial::dcp),
+            Instruction::Ign => Operation::Read(unofficial::ign),
+            Instruction::Isb => Operation::ReadModifyWrite(unofficial::isb),
+            Instruction::Jam => Operation::Implied(unofficial::jam),
+            Instruction::Las => Operation::Read(unofficial::las),
+            Instruction::Lax => Operation::Read(unofficial::lax),
+            Instruction::Lxa => Operation::Read(unofficial::lxa),
+            Instruction::Rla => Operation::ReadModifyWrite(unofficial::rla),
+            Instruction::Rra => Operation::ReadModifyWrite(unofficial::rra),
+            Instruction::Sax => Operation::Write(unofficial::sax),
+            Instruction::Shx => Operation::AddressedWrite(unofficial::shx),
+            Instruction::Shy => Operation::AddressedWrite(unofficial::shy),
+            Instruction::Slo => Operation::ReadModifyWrite(unofficial::slo),
+            Instruction::Sre => Operation::ReadModifyWrite(unofficial::sre),
+            Instruction::Tas => Operation::AddressedWrite(unofficial::tas),
+            Instruction::Xaa => Operation::Read(unofficial::xaa),
+        }
+    }
+}
@@ -0,0 +1,4 @@
+use ::cpu::Cpu;
+use ::memory_map::MemoryMapper;
+
+pub fn nop<T: MemoryMapper>(_cpu: &mut Cpu<T>) {}
@@ -0,0 +1,43 @@
+use ::cpu::Cpu;
+use ::memory_map::MemoryMapper;
+
+// Each of these returns the shifted value so callers can decide whether it
+// goes back into the accumulator or out to memory.
+
+pub fn asl<T: MemoryMapper>(cpu: &mut Cpu<T>, value: u8) -> u8 {
+    let result = value << 1;
+
+    cpu.processor_status.carry_flag = value & 0b1000_0000 != 0;
+    cpu.set_zero_and_negative(result);
+
+    result
+}
+
+pub fn lsr<T: MemoryMapper>(cpu: &mut Cpu<T>, value: u8) -> u8 {
+    let result = value >> 1;
+
+    cpu.processor_status.carry_flag = value & 0b0000_0001 != 0;
+    cpu.set_zero_and_negative(result);
+
+    result
+}
+
+pub fn rol<T: MemoryMapper>(cpu: &mut Cpu<T>, value: u8) -> u8 {
+    let carry_in = cpu.processor_status.carry_flag as u8;
+    let result = (value << 1) | carry_in;
+
+    cpu.processor_status.carry_flag = value & 0b1000_0000 != 0;
+    cpu.set_zero_and_negative(result);
+
+    result
+}
+
+pub fn ror<T: MemoryMapper>(cpu: &mut Cpu<T>, value: u8) -> u8 {
+    let carry_in = (cpu.processor_status.carry_flag as u8) << 7;
+    let result = (value >> 1) | carry_in;
+
+    cpu.processor_status.carry_flag = value & 0b0000_0001 != 0;
+    cpu.set_zero_and_negative(result);
+
+    result
+}
@@ -0,0 +1,21 @@
+use ::cpu::Cpu;
+use ::cpu::ProcessorStatus;
+use ::memory_map::MemoryMapper;
+
+pub fn pha<T: MemoryMapper>(cpu: &mut Cpu<T>) -> u8 {
+    cpu.reg_accumulator
+}
+
+pub fn php<T: MemoryMapper>(cpu: &mut Cpu<T>) -> u8 {
+    // php always pushes with the b flag set
+    ProcessorStatus::to_stack_u8(&cpu.processor_status, true)
+}
+
+pub fn pla<T: MemoryMapper>(cpu: &mut Cpu<T>, value: u8) {
+    cpu.reg_accumulator = value;
+    cpu.set_zero_and_negative(value);
+}
+
+pub fn plp<T: MemoryMapper>(cpu: &mut Cpu<T>, value: u8) {
+    cpu.set_status_from_stack(value);
+}
@@ -0,0 +1,14 @@
+use ::cpu::Cpu;
+use ::memory_map::MemoryMapper;
+
+pub fn sta<T: MemoryMapper>(cpu: &mut Cpu<T>) -> u8 {
+    cpu.reg_accumulator
+}
+
+pub fn stx<T: MemoryMapper>(cpu: &mut Cpu<T>) -> u8 {
+    cpu.reg_index_x
+}
+
+pub fn sty<T: MemoryMapper>(cpu: &mut Cpu<T>) -> u8 {
+    cpu.reg_index_y
+}
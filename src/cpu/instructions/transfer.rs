@@ -0,0 +1,42 @@
+use ::cpu::Cpu;
+use ::memory_map::MemoryMapper;
+
+pub fn tax<T: MemoryMapper>(cpu: &mut Cpu<T>) {
+    let acc = cpu.reg_accumulator;
+
+    cpu.reg_index_x = acc;
+    cpu.set_zero_and_negative(acc);
+}
+
+pub fn tay<T: MemoryMapper>(cpu: &mut Cpu<T>) {
+    let acc = cpu.reg_accumulator;
+
+    cpu.reg_index_y = acc;
+    cpu.set_zero_and_negative(acc);
+}
+
+pub fn txa<T: MemoryMapper>(cpu: &mut Cpu<T>) {
+    let x = cpu.reg_index_x;
+
+    cpu.reg_accumulator = x;
+    cpu.set_zero_and_negative(x);
+}
+
+pub fn tya<T: MemoryMapper>(cpu: &mut Cpu<T>) {
+    let y = cpu.reg_index_y;
+
+    cpu.reg_accumulator = y;
+    cpu.set_zero_and_negative(y);
+}
+
+pub fn tsx<T: MemoryMapper>(cpu: &mut Cpu<T>) {
+    let sp = cpu.reg_stack_pointer;
+
+    cpu.reg_index_x = sp;
+    cpu.set_zero_and_negative(sp);
+}
+
+pub fn txs<T: MemoryMapper>(cpu: &mut Cpu<T>) {
+    // note: txs is the only transfer that doesn't touch the flags
+    cpu.reg_stack_pointer = cpu.reg_index_x;
+}
@@ -0,0 +1,183 @@
+use ::cpu::Cpu;
+use ::cpu::instructions::adc;
+use ::cpu::instructions::compare;
+use ::cpu::instructions::inc_dec;
+use ::cpu::instructions::load;
+use ::cpu::instructions::logical;
+use ::cpu::instructions::shift;
+use ::memory_map::MemoryMapper;
+
+// [Unofficial Opcodes]
+// Most of these fall out of the 6502's decode logic running two official
+// instructions on the same operand at once, so they're built out of the
+// official operations wherever possible.
+//
+// The "unstable" ones (xaa, lax #imm, ahx, shx, shy, tas) depend on analog
+// effects that vary between chips; we do what most emulators and test roms
+// expect.
+//
+// [Resources]
+// behaviour => http://wiki.nesdev.com/w/index.php/Programming_with_unofficial_opcodes
+// unstable opcodes => http://visual6502.org/wiki/index.php?title=6502_Opcode_8B_(XAA,_ANE)
+
+// The value that leaks into a on xaa / lax #imm; 0xee is what visual6502 shows
+const UNSTABLE_MAGIC: u8 = 0xee;
+
+// Combined read-modify-write + read
+
+pub fn slo<T: MemoryMapper>(cpu: &mut Cpu<T>, value: u8) -> u8 {
+    let result = shift::asl(cpu, value);
+    logical::ora(cpu, result);
+
+    result
+}
+
+pub fn rla<T: MemoryMapper>(cpu: &mut Cpu<T>, value: u8) -> u8 {
+    let result = shift::rol(cpu, value);
+    logical::and(cpu, result);
+
+    result
+}
+
+pub fn sre<T: MemoryMapper>(cpu: &mut Cpu<T>, value: u8) -> u8 {
+    let result = shift::lsr(cpu, value);
+    logical::eor(cpu, result);
+
+    result
+}
+
+pub fn rra<T: MemoryMapper>(cpu: &mut Cpu<T>, value: u8) -> u8 {
+    let result = shift::ror(cpu, value);
+    adc::adc(cpu, result);
+
+    result
+}
+
+pub fn dcp<T: MemoryMapper>(cpu: &mut Cpu<T>, value: u8) -> u8 {
+    let result = inc_dec::dec(cpu, value);
+    compare::cmp(cpu, result);
+
+    result
+}
+
+pub fn isb<T: MemoryMapper>(cpu: &mut Cpu<T>, value: u8) -> u8 {
+    let result = inc_dec::inc(cpu, value);
+    adc::sbc(cpu, result);
+
+    result
+}
+
+// Loads and stores
+
+pub fn lax<T: MemoryMapper>(cpu: &mut Cpu<T>, value: u8) {
+    load::lda(cpu, value);
+    load::ldx(cpu, value);
+}
+
+pub fn lxa<T: MemoryMapper>(cpu: &mut Cpu<T>, value: u8) {
+    let result = (cpu.reg_accumulator | UNSTABLE_MAGIC) & value;
+
+    lax(cpu, result);
+}
+
+pub fn sax<T: MemoryMapper>(cpu: &mut Cpu<T>) -> u8 {
+    cpu.reg_accumulator & cpu.reg_index_x
+}
+
+pub fn las<T: MemoryMapper>(cpu: &mut Cpu<T>, value: u8) {
+    let result = value & cpu.reg_stack_pointer;
+
+    cpu.reg_stack_pointer = result;
+    lax(cpu, result);
+}
+
+// Immediate-only combos
+
+pub fn anc<T: MemoryMapper>(cpu: &mut Cpu<T>, value: u8) {
+    logical::and(cpu, value);
+
+    cpu.processor_status.carry_flag = cpu.processor_status.negative;
+}
+
+pub fn alr<T: MemoryMapper>(cpu: &mut Cpu<T>, value: u8) {
+    logical::and(cpu, value);
+
+    let acc = cpu.reg_accumulator;
+    cpu.reg_accumulator = shift::lsr(cpu, acc);
+}
+
+pub fn arr<T: MemoryMapper>(cpu: &mut Cpu<T>, value: u8) {
+    logical::and(cpu, value);
+
+    let acc = cpu.reg_accumulator;
+    let result = shift::ror(cpu, acc);
+    cpu.reg_accumulator = result;
+
+    // carry and overflow come out of the adder rather than the shift
+    cpu.processor_status.carry_flag = result & 0b0100_0000 != 0;
+    cpu.processor_status.overflow_flag = ((result >> 6) ^ (result >> 5)) & 1 != 0;
+}
+
+pub fn axs<T: MemoryMapper>(cpu: &mut Cpu<T>, value: u8) {
+    let a_and_x = cpu.reg_accumulator & cpu.reg_index_x;
+
+    // compares like cmp (so no borrow in), but keeps the result in x
+    compare::compare(cpu, a_and_x, value);
+    cpu.reg_index_x = a_and_x.wrapping_sub(value);
+}
+
+pub fn xaa<T: MemoryMapper>(cpu: &mut Cpu<T>, value: u8) {
+    let result = (cpu.reg_accumulator | UNSTABLE_MAGIC) & cpu.reg_index_x & value;
+
+    load::lda(cpu, result);
+}
+
+// Stores that and the register with the high byte of the base address + 1;
+// when indexing crosses a page, that value also replaces the high byte of
+// the address being written to
+
+pub fn shy<T: MemoryMapper>(cpu: &mut Cpu<T>, address: u16) {
+    let (y, x) = (cpu.reg_index_y, cpu.reg_index_x);
+
+    store_high_byte_and(cpu, address, x, y);
+}
+
+pub fn shx<T: MemoryMapper>(cpu: &mut Cpu<T>, address: u16) {
+    let (x, y) = (cpu.reg_index_x, cpu.reg_index_y);
+
+    store_high_byte_and(cpu, address, y, x);
+}
+
+pub fn ahx<T: MemoryMapper>(cpu: &mut Cpu<T>, address: u16) {
+    let (a_and_x, y) = (cpu.reg_accumulator & cpu.reg_index_x, cpu.reg_index_y);
+
+    store_high_byte_and(cpu, address, y, a_and_x);
+}
+
+pub fn tas<T: MemoryMapper>(cpu: &mut Cpu<T>, address: u16) {
+    let (a_and_x, y) = (cpu.reg_accumulator & cpu.reg_index_x, cpu.reg_index_y);
+    cpu.reg_stack_pointer = a_and_x;
+
+    store_high_byte_and(cpu, address, y, a_and_x);
+}
+
+fn store_high_byte_and<T: MemoryMapper>(cpu: &mut Cpu<T>, address: u16, index: u8, value: u8) {
+    let base = address.wrapping_sub(index as u16);
+    let result = value & ((base >> 8) as u8).wrapping_add(1);
+
+    let address = match (base & 0xff00) == (address & 0xff00) {
+        true => address,
+        false => ((result as u16) << 8) | (address & 0x00ff),
+    };
+
+    cpu.write(address, result);
+}
+
+// Everything else
+
+pub fn ign<T: MemoryMapper>(_cpu: &mut Cpu<T>, _value: u8) {}
+
+pub fn jam<T: MemoryMapper>(cpu: &mut Cpu<T>) {
+    // the cpu locks up until reset, not even answering interrupts
+    cpu.jammed = true;
+}
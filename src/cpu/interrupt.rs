@@ -0,0 +1,121 @@
+use ::cpu::Cpu;
+use ::cpu::CpuStack;
+use ::cpu::ProcessorStatus;
+use ::memory_map;
+use ::memory_map::MemoryMapper;
+
+// [Interrupts]
+// reset, nmi, irq and brk all run the same 7 cycle sequence: push pc, push p,
+// set i, then load pc from the interrupt's vector. The differences are:
+//
+// - brk fetches (and skips) the byte after its opcode, the others just do
+//   dummy reads without moving pc
+// - brk pushes p with the b flag set, nmi and irq push it clear
+// - reset does the pushes as reads, so sp moves down by 3 but the stack
+//   isn't touched
+// - if an nmi shows up before a brk or irq gets to its vector fetch, the nmi
+//   vector is fetched instead ("hijacking"); the pushed b flag is left as it
+//   was, which is the only way software can tell the difference
+//
+// [Resources]
+// interrupts => http://wiki.nesdev.com/w/index.php/CPU_interrupts
+// b flag => http://wiki.nesdev.com/w/index.php/Status_flags#The_B_flag
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Interrupt {
+    Reset,
+    Nmi,
+    Irq,
+    Brk,
+}
+
+impl Interrupt {
+    pub fn vector(&self) -> u16 {
+        match *self {
+            Interrupt::Reset => memory_map::RESET_VECTOR,
+            Interrupt::Nmi => memory_map::NMI_VECTOR,
+            Interrupt::Irq | Interrupt::Brk => memory_map::IRQ_VECTOR,
+        }
+    }
+}
+
+// Runs the given cycle (1 being the opcode fetch slot) of the interrupt
+// sequence, returning true once pc is pointing at the handler
+pub fn step<T: MemoryMapper>(cpu: &mut Cpu<T>, interrupt: Interrupt, cycle: u8) -> bool {
+    match cycle {
+        1 => {
+            let pc = cpu.reg_program_counter;
+            cpu.read(pc);
+        }
+        2 => {
+            // brk skips over its padding byte
+            match interrupt {
+                Interrupt::Brk => {
+                    cpu.next_word();
+                }
+                _ => {
+                    let pc = cpu.reg_program_counter;
+                    cpu.read(pc);
+                }
+            }
+        }
+        3 => {
+            let pch = (cpu.reg_program_counter >> 8) as u8;
+            push(cpu, interrupt, pch);
+        }
+        4 => {
+            let pcl = cpu.reg_program_counter as u8;
+            push(cpu, interrupt, pcl);
+        }
+        5 => {
+            let status = ProcessorStatus::to_stack_u8(&cpu.processor_status, interrupt == Interrupt::Brk);
+            push(cpu, interrupt, status);
+
+            cpu.processor_status.interrupts_disabled = true;
+            cpu.state.address = select_vector(cpu, interrupt);
+        }
+        6 => {
+            let vector = cpu.state.address;
+            cpu.state.data = cpu.read(vector);
+        }
+        _ => {
+            let vector = cpu.state.address;
+            let hi = cpu.read(vector.wrapping_add(1)) as u16;
+            cpu.reg_program_counter = (hi << 8) | (cpu.state.data as u16);
+
+            return true;
+        }
+    }
+
+    false
+}
+
+fn push<T: MemoryMapper>(cpu: &mut Cpu<T>, interrupt: Interrupt, value: u8) {
+    match interrupt {
+        Interrupt::Reset => {
+            let sp = cpu.resolve_stack_pointer();
+            cpu.read(sp);
+            cpu.reg_stack_pointer = cpu.reg_stack_pointer.wrapping_sub(1);
+        }
+        _ => cpu.push(value),
+    }
+}
+
+fn select_vector<T: MemoryMapper>(cpu: &mut Cpu<T>, interrupt: Interrupt) -> u16 {
+    let vector = match interrupt {
+        Interrupt::Reset => {
+            cpu.nmi_pending = false;
+
+            return interrupt.vector();
+        }
+        // an nmi that came in while we were pushing takes over the vector fetch
+        Interrupt::Brk | Interrupt::Irq if cpu.nmi_pending => memory_map::NMI_VECTOR,
+        _ => interrupt.vector(),
+    };
+
+    if vector == memory_map::NMI_VECTOR {
+        cpu.nmi_pending = false;
+    }
+
+    vector
+}
@@ -0,0 +1,165 @@
+use ::cpu::Cpu;
+use ::cpu::asm;
+use ::cpu::trace;
+use ::memory_map;
+use ::memory_map::Cartridge;
+use ::memory_map::MemoryMapper;
+use ::rom;
+
+use std::fs;
+use std::fs::File;
+
+// [nestest]
+// Runs nestest.nes in automation mode and diffs our trace against the golden
+// log a line at a time. Automation mode just means starting at $c000 instead
+// of going through the reset vector, which runs every test without needing a
+// ppu or controller.
+//
+// The rom and log aren't ours to check in, so the test is ignored by default.
+// Put nestest.nes and nestest.log (with the ppu column) under tests/roms/ and
+// run it with cargo test -- --ignored.
+//
+// What always runs is the same diff against cpu_golden.log, the trace of
+// cpu_golden.s: our own program, assembled in-tree and started the same way.
+// Regenerate the log only after checking by hand that a change to it is right.
+//
+// [Resources]
+// nestest => http://www.qmtpro.com/~nes/misc/nestest.txt
+// golden log => http://www.qmtpro.com/~nes/misc/nestest.log
+
+const ROM_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/roms/nestest.nes");
+const LOG_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/roms/nestest.log");
+
+const GOLDEN_SOURCE: &str = include_str!("../../tests/roms/cpu_golden.s");
+const GOLDEN_LOG: &str = include_str!("../../tests/roms/cpu_golden.log");
+
+const AUTOMATION_START: u16 = 0xc000;
+
+// How many matching lines to show before a divergence
+const CONTEXT_LINES: usize = 5;
+
+// Steps the cpu once per line of the golden log, stopping at the first line
+// our trace doesn't match
+fn run<T: MemoryMapper>(cpu: &mut Cpu<T>, expected: &[&str]) -> Result<(), String> {
+    let mut actual = vec![];
+
+    for (i, expected_line) in expected.iter().enumerate() {
+        actual.push(trace::trace(cpu));
+
+        if actual[i] != *expected_line {
+            return Err(report(expected, &actual, i));
+        }
+
+        cpu.step_instruction();
+    }
+
+    Ok(())
+}
+
+fn report(expected: &[&str], actual: &[String], line: usize) -> String {
+    let mut report = format!("trace diverged from the golden log at line {}:\n", line + 1);
+
+    for context in actual.iter().take(line).skip(line.saturating_sub(CONTEXT_LINES)) {
+        report.push_str(&format!("  {}\n", context));
+    }
+
+    report.push_str(&format!("- {}\n", expected[line]));
+    report.push_str(&format!("+ {}\n", actual[line]));
+
+    report
+}
+
+fn new_automation_cpu(rom: &rom::NesRom) -> Cpu<memory_map::Bus<memory_map::NROMMemoryMap>> {
+    let mut cartridge = memory_map::NROMMemoryMap::default();
+    cartridge.load(rom);
+
+    let mut cpu = Cpu::new(memory_map::Bus::new(cartridge));
+    cpu.power_on();
+    cpu.step_instruction();
+
+    cpu.reg_program_counter = AUTOMATION_START;
+
+    cpu
+}
+
+#[test]
+#[ignore = "needs nestest.nes and nestest.log in tests/roms/, which can't be checked in"]
+fn test_nestest_golden_log() {
+    let rom_file = File::open(ROM_PATH).unwrap_or_else(|error| panic!("couldn't open {}: {}", ROM_PATH, error));
+    let log = fs::read_to_string(LOG_PATH).unwrap_or_else(|error| panic!("couldn't read {}: {}", LOG_PATH, error));
+
+    let rom = rom::NesRom::from_nes_file(rom_file).unwrap();
+    let mut cpu = new_automation_cpu(&rom);
+
+    let expected = log.lines().map(|line| line.trim_end()).collect::<Vec<&str>>();
+    if let Err(report) = run(&mut cpu, &expected) {
+        panic!("{}", report);
+    }
+
+    // nestest leaves its error codes for the official and unofficial tests here
+    assert_eq!(cpu.read(0x02), 0);
+    assert_eq!(cpu.read(0x03), 0);
+}
+
+#[test]
+fn test_cpu_golden_log() {
+    let program = asm::assemble(GOLDEN_SOURCE).unwrap_or_else(|error| panic!("cpu_golden.s: {}", error));
+
+    let mut rom = rom::NesRom::default();
+    rom.prg_rom = program.image(AUTOMATION_START, memory_map::PRG_ROM_BANK_SIZE);
+    let mut cpu = new_automation_cpu(&rom);
+
+    let expected = GOLDEN_LOG.lines().map(|line| line.trim_end()).collect::<Vec<&str>>();
+    if let Err(report) = run(&mut cpu, &expected) {
+        panic!("{}", report);
+    }
+
+    // the last line is the program spinning on done
+    let done = cpu.reg_program_counter;
+    cpu.step_instruction();
+    assert_eq!(cpu.reg_program_counter, done);
+}
+
+#[test]
+fn test_trace_matches_nintendulator_format() {
+    let mut cpu: Cpu<memory_map::FlatMemoryMap> = Cpu::default();
+    cpu.memory_map.write_u16(memory_map::RESET_VECTOR, AUTOMATION_START);
+    cpu.power_on();
+    cpu.step_instruction();
+
+    // jmp $c5f5
+    cpu.write(0xc000, 0x4c);
+    cpu.memory_map.write_u16(0xc001, 0xc5f5);
+    assert_eq!(trace::trace(&cpu), "C000  4C F5 C5  JMP $C5F5                       A:00 X:00 Y:00 P:24 SP:FD PPU:  0, 21 CYC:7");
+
+    // lda ($89),y
+    cpu.write(0xc000, 0xb1);
+    cpu.write(0xc001, 0x89);
+    cpu.memory_map.write_u16(0x89, 0x0300);
+    cpu.write(0x0300, 0x89);
+    assert_eq!(trace::trace(&cpu), "C000  B1 89     LDA ($89),Y = 0300 @ 0300 = 89  A:00 X:00 Y:00 P:24 SP:FD PPU:  0, 21 CYC:7");
+
+    // unofficial nop $a9
+    cpu.write(0xc000, 0x04);
+    cpu.write(0xc001, 0xa9);
+    assert_eq!(trace::trace(&cpu), "C000  04 A9    *NOP $A9 = 00                    A:00 X:00 Y:00 P:24 SP:FD PPU:  0, 21 CYC:7");
+}
+
+#[test]
+fn test_trace_asks_the_ppu_where_it_is() {
+    let mut cpu = Cpu::new(memory_map::Bus::<memory_map::NROMMemoryMap>::default());
+    cpu.power_on();
+    cpu.step_instruction();
+
+    // the ppu moving on without the cpu shows up in the trace
+    cpu.memory_map.tick();
+    assert!(trace::trace(&cpu).ends_with("PPU:  0, 24 CYC:7"));
+}
+
+#[test]
+fn test_report_shows_context_and_both_lines() {
+    let expected = vec!["a", "b", "c", "d"];
+    let actual = vec!["a".to_string(), "b".to_string(), "x".to_string()];
+
+    assert_eq!(report(&expected, &actual, 2), "trace diverged from the golden log at line 3:\n  a\n  b\n- c\n+ x\n");
+}
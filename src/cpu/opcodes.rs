@@ -0,0 +1,357 @@
+use ::cpu::addressing::AddressingMode;
+use ::cpu::addressing::AddressingMode::*;
+use ::cpu::instructions::Instruction;
+use ::cpu::instructions::Instruction::*;
+
+// [Opcodes]
+// One row per opcode: what it does, how it finds its operand, its base cycle
+// count, and whether crossing a page while indexing costs an extra cycle.
+//
+// Branches list their not-taken cost; taking one adds a cycle and landing on
+// another page adds one more.
+//
+// The unofficial rows are the NMOS 6502's undocumented opcodes. Nothing stops
+// the cpu from running them, so lots of games (and nestest) end up relying on
+// them; OpcodeSet decides whether we go along with it or call them out.
+//
+// [Resources]
+// cycle counts => http://www.obelisk.me.uk/6502/reference.html
+// unofficial opcodes => http://wiki.nesdev.com/w/index.php/CPU_unofficial_opcodes
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Opcode {
+    pub instruction: Instruction,
+    pub mode: AddressingMode,
+    pub cycles: u8,
+    pub page_penalty: bool,
+    pub official: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OpcodeSet {
+    // Only the 151 documented opcodes; anything else is an error
+    Official,
+
+    // Everything a real NMOS 6502 does, undocumented opcodes included
+    #[default]
+    Nmos,
+}
+
+const fn official(instruction: Instruction, mode: AddressingMode, cycles: u8, page_penalty: bool) -> Opcode {
+    Opcode {
+        instruction,
+        mode,
+        cycles,
+        page_penalty,
+        official: true,
+    }
+}
+
+const fn unofficial(instruction: Instruction, mode: AddressingMode, cycles: u8, page_penalty: bool) -> Opcode {
+    Opcode {
+        instruction,
+        mode,
+        cycles,
+        page_penalty,
+        official: false,
+    }
+}
+
+// Looks up an opcode, or None if it isn't part of the given opcode set
+pub fn decode(opcode: u8, opcode_set: OpcodeSet) -> Option<Opcode> {
+    let decoded = OPCODES[opcode as usize];
+
+    match decoded.official || opcode_set == OpcodeSet::Nmos {
+        true => Some(decoded),
+        false => None,
+    }
+}
+
+#[rustfmt::skip]
+pub static OPCODES: [Opcode; 256] = [
+    /* 0x00 */ official(Brk, Implied, 7, false),
+    /* 0x01 */ official(Ora, IndirectX, 6, false),
+    /* 0x02 */ unofficial(Jam, Implied, 2, false),
+    /* 0x03 */ unofficial(Slo, IndirectX, 8, false),
+    /* 0x04 */ unofficial(Ign, ZeroPage, 3, false),
+    /* 0x05 */ official(Ora, ZeroPage, 3, false),
+    /* 0x06 */ official(Asl, ZeroPage, 5, false),
+    /* 0x07 */ unofficial(Slo, ZeroPage, 5, false),
+    /* 0x08 */ official(Php, Implied, 3, false),
+    /* 0x09 */ official(Ora, Immediate, 2, false),
+    /* 0x0a */ official(Asl, Accumulator, 2, false),
+    /* 0x0b */ unofficial(Anc, Immediate, 2, false),
+    /* 0x0c */ unofficial(Ign, Absolute, 4, false),
+    /* 0x0d */ official(Ora, Absolute, 4, false),
+    /* 0x0e */ official(Asl, Absolute, 6, false),
+    /* 0x0f */ unofficial(Slo, Absolute, 6, false),
+    /* 0x10 */ official(Bpl, Relative, 2, false),
+    /* 0x11 */ official(Ora, IndirectY, 5, true),
+    /* 0x12 */ unofficial(Jam, Implied, 2, false),
+    /* 0x13 */ unofficial(Slo, IndirectY, 8, false),
+    /* 0x14 */ unofficial(Ign, ZeroPageX, 4, false),
+    /* 0x15 */ official(Ora, ZeroPageX, 4, false),
+    /* 0x16 */ official(Asl, ZeroPageX, 6, false),
+    /* 0x17 */ unofficial(Slo, ZeroPageX, 6, false),
+    /* 0x18 */ official(Clc, Implied, 2, false),
+    /* 0x19 */ official(Ora, AbsoluteY, 4, true),
+    /* 0x1a */ unofficial(Nop, Implied, 2, false),
+    /* 0x1b */ unofficial(Slo, AbsoluteY, 7, false),
+    /* 0x1c */ unofficial(Ign, AbsoluteX, 4, true),
+    /* 0x1d */ official(Ora, AbsoluteX, 4, true),
+    /* 0x1e */ official(Asl, AbsoluteX, 7, false),
+    /* 0x1f */ unofficial(Slo, AbsoluteX, 7, false),
+    /* 0x20 */ official(Jsr, Absolute, 6, false),
+    /* 0x21 */ official(And, IndirectX, 6, false),
+    /* 0x22 */ unofficial(Jam, Implied, 2, false),
+    /* 0x23 */ unofficial(Rla, IndirectX, 8, false),
+    /* 0x24 */ official(Bit, ZeroPage, 3, false),
+    /* 0x25 */ official(And, ZeroPage, 3, false),
+    /* 0x26 */ official(Rol, ZeroPage, 5, false),
+    /* 0x27 */ unofficial(Rla, ZeroPage, 5, false),
+    /* 0x28 */ official(Plp, Implied, 4, false),
+    /* 0x29 */ official(And, Immediate, 2, false),
+    /* 0x2a */ official(Rol, Accumulator, 2, false),
+    /* 0x2b */ unofficial(Anc, Immediate, 2, false),
+    /* 0x2c */ official(Bit, Absolute, 4, false),
+    /* 0x2d */ official(And, Absolute, 4, false),
+    /* 0x2e */ official(Rol, Absolute, 6, false),
+    /* 0x2f */ unofficial(Rla, Absolute, 6, false),
+    /* 0x30 */ official(Bmi, Relative, 2, false),
+    /* 0x31 */ official(And, IndirectY, 5, true),
+    /* 0x32 */ unofficial(Jam, Implied, 2, false),
+    /* 0x33 */ unofficial(Rla, IndirectY, 8, false),
+    /* 0x34 */ unofficial(Ign, ZeroPageX, 4, false),
+    /* 0x35 */ official(And, ZeroPageX, 4, false),
+    /* 0x36 */ official(Rol, ZeroPageX, 6, false),
+    /* 0x37 */ unofficial(Rla, ZeroPageX, 6, false),
+    /* 0x38 */ official(Sec, Implied, 2, false),
+    /* 0x39 */ official(And, AbsoluteY, 4, true),
+    /* 0x3a */ unofficial(Nop, Implied, 2, false),
+    /* 0x3b */ unofficial(Rla, AbsoluteY, 7, false),
+    /* 0x3c */ unofficial(Ign, AbsoluteX, 4, true),
+    /* 0x3d */ official(And, AbsoluteX, 4, true),
+    /* 0x3e */ official(Rol, AbsoluteX, 7, false),
+    /* 0x3f */ unofficial(Rla, AbsoluteX, 7, false),
+    /* 0x40 */ official(Rti, Implied, 6, false),
+    /* 0x41 */ official(Eor, IndirectX, 6, false),
+    /* 0x42 */ unofficial(Jam, Implied, 2, false),
+    /* 0x43 */ unofficial(Sre, IndirectX, 8, false),
+    /* 0x44 */ unofficial(Ign, ZeroPage, 3, false),
+    /* 0x45 */ official(Eor, ZeroPage, 3, false),
+    /* 0x46 */ official(Lsr, ZeroPage, 5, false),
+    /* 0x47 */ unofficial(Sre, ZeroPage, 5, false),
+    /* 0x48 */ official(Pha, Implied, 3, false),
+    /* 0x49 */ official(Eor, Immediate, 2, false),
+    /* 0x4a */ official(Lsr, Accumulator, 2, false),
+    /* 0x4b */ unofficial(Alr, Immediate, 2, false),
+    /* 0x4c */ official(Jmp, Absolute, 3, false),
+    /* 0x4d */ official(Eor, Absolute, 4, false),
+    /* 0x4e */ official(Lsr, Absolute, 6, false),
+    /* 0x4f */ unofficial(Sre, Absolute, 6, false),
+    /* 0x50 */ official(Bvc, Relative, 2, false),
+    /* 0x51 */ official(Eor, IndirectY, 5, true),
+    /* 0x52 */ unofficial(Jam, Implied, 2, false),
+    /* 0x53 */ unofficial(Sre, IndirectY, 8, false),
+    /* 0x54 */ unofficial(Ign, ZeroPageX, 4, false),
+    /* 0x55 */ official(Eor, ZeroPageX, 4, false),
+    /* 0x56 */ official(Lsr, ZeroPageX, 6, false),
+    /* 0x57 */ unofficial(Sre, ZeroPageX, 6, false),
+    /* 0x58 */ official(Cli, Implied, 2, false),
+    /* 0x59 */ official(Eor, AbsoluteY, 4, true),
+    /* 0x5a */ unofficial(Nop, Implied, 2, false),
+    /* 0x5b */ unofficial(Sre, AbsoluteY, 7, false),
+    /* 0x5c */ unofficial(Ign, AbsoluteX, 4, true),
+    /* 0x5d */ official(Eor, AbsoluteX, 4, true),
+    /* 0x5e */ official(Lsr, AbsoluteX, 7, false),
+    /* 0x5f */ unofficial(Sre, AbsoluteX, 7, false),
+    /* 0x60 */ official(Rts, Implied, 6, false),
+    /* 0x61 */ official(Adc, IndirectX, 6, false),
+    /* 0x62 */ unofficial(Jam, Implied, 2, false),
+    /* 0x63 */ unofficial(Rra, IndirectX, 8, false),
+    /* 0x64 */ unofficial(Ign, ZeroPage, 3, false),
+    /* 0x65 */ official(Adc, ZeroPage, 3, false),
+    /* 0x66 */ official(Ror, ZeroPage, 5, false),
+    /* 0x67 */ unofficial(Rra, ZeroPage, 5, false),
+    /* 0x68 */ official(Pla, Implied, 4, false),
+    /* 0x69 */ official(Adc, Immediate, 2, false),
+    /* 0x6a */ official(Ror, Accumulator, 2, false),
+    /* 0x6b */ unofficial(Arr, Immediate, 2, false),
+    /* 0x6c */ official(Jmp, Indirect, 5, false),
+    /* 0x6d */ official(Adc, Absolute, 4, false),
+    /* 0x6e */ official(Ror, Absolute, 6, false),
+    /* 0x6f */ unofficial(Rra, Absolute, 6, false),
+    /* 0x70 */ official(Bvs, Relative, 2, false),
+    /* 0x71 */ official(Adc, IndirectY, 5, true),
+    /* 0x72 */ unofficial(Jam, Implied, 2, false),
+    /* 0x73 */ unofficial(Rra, IndirectY, 8, false),
+    /* 0x74 */ unofficial(Ign, ZeroPageX, 4, false),
+    /* 0x75 */ official(Adc, ZeroPageX, 4, false),
+    /* 0x76 */ official(Ror, ZeroPageX, 6, false),
+    /* 0x77 */ unofficial(Rra, ZeroPageX, 6, false),
+    /* 0x78 */ official(Sei, Implied, 2, false),
+    /* 0x79 */ official(Adc, AbsoluteY, 4, true),
+    /* 0x7a */ unofficial(Nop, Implied, 2, false),
+    /* 0x7b */ unofficial(Rra, AbsoluteY, 7, false),
+    /* 0x7c */ unofficial(Ign, AbsoluteX, 4, true),
+    /* 0x7d */ official(Adc, AbsoluteX, 4, true),
+    /* 0x7e */ official(Ror, AbsoluteX, 7, false),
+    /* 0x7f */ unofficial(Rra, AbsoluteX, 7, false),
+    /* 0x80 */ unofficial(Ign, Immediate, 2, false),
+    /* 0x81 */ official(Sta, IndirectX, 6, false),
+    /* 0x82 */ unofficial(Ign, Immediate, 2, false),
+    /* 0x83 */ unofficial(Sax, IndirectX, 6, false),
+    /* 0x84 */ official(Sty, ZeroPage, 3, false),
+    /* 0x85 */ official(Sta, ZeroPage, 3, false),
+    /* 0x86 */ official(Stx, ZeroPage, 3, false),
+    /* 0x87 */ unofficial(Sax, ZeroPage, 3, false),
+    /* 0x88 */ official(Dey, Implied, 2, false),
+    /* 0x89 */ unofficial(Ign, Immediate, 2, false),
+    /* 0x8a */ official(Txa, Implied, 2, false),
+    /* 0x8b */ unofficial(Xaa, Immediate, 2, false),
+    /* 0x8c */ official(Sty, Absolute, 4, false),
+    /* 0x8d */ official(Sta, Absolute, 4, false),
+    /* 0x8e */ official(Stx, Absolute, 4, false),
+    /* 0x8f */ unofficial(Sax, Absolute, 4, false),
+    /* 0x90 */ official(Bcc, Relative, 2, false),
+    /* 0x91 */ official(Sta, IndirectY, 6, false),
+    /* 0x92 */ unofficial(Jam, Implied, 2, false),
+    /* 0x93 */ unofficial(Ahx, IndirectY, 6, false),
+    /* 0x94 */ official(Sty, ZeroPageX, 4, false),
+    /* 0x95 */ official(Sta, ZeroPageX, 4, false),
+    /* 0x96 */ official(Stx, ZeroPageY, 4, false),
+    /* 0x97 */ unofficial(Sax, ZeroPageY, 4, false),
+    /* 0x98 */ official(Tya, Implied, 2, false),
+    /* 0x99 */ official(Sta, AbsoluteY, 5, false),
+    /* 0x9a */ official(Txs, Implied, 2, false),
+    /* 0x9b */ unofficial(Tas, AbsoluteY, 5, false),
+    /* 0x9c */ unofficial(Shy, AbsoluteX, 5, false),
+    /* 0x9d */ official(Sta, AbsoluteX, 5, false),
+    /* 0x9e */ unofficial(Shx, AbsoluteY, 5, false),
+    /* 0x9f */ unofficial(Ahx, AbsoluteY, 5, false),
+    /* 0xa0 */ official(Ldy, Immediate, 2, false),
+    /* 0xa1 */ official(Lda, IndirectX, 6, false),
+    /* 0xa2 */ official(Ldx, Immediate, 2, false),
+    /* 0xa3 */ unofficial(Lax, IndirectX, 6, false),
+    /* 0xa4 */ official(Ldy, ZeroPage, 3, false),
+    /* 0xa5 */ official(Lda, ZeroPage, 3, false),
+    /* 0xa6 */ official(Ldx, ZeroPage, 3, false),
+    /* 0xa7 */ unofficial(Lax, ZeroPage, 3, false),
+    /* 0xa8 */ official(Tay, Implied, 2, false),
+    /* 0xa9 */ official(Lda, Immediate, 2, false),
+    /* 0xaa */ official(Tax, Implied, 2, false),
+    /* 0xab */ unofficial(Lxa, Immediate, 2, false),
+    /* 0xac */ official(Ldy, Absolute, 4, false),
+    /* 0xad */ official(Lda, Absolute, 4, false),
+    /* 0xae */ official(Ldx, Absolute, 4, false),
+    /* 0xaf */ unofficial(Lax, Absolute, 4, false),
+    /* 0xb0 */ official(Bcs, Relative, 2, false),
+    /* 0xb1 */ official(Lda, IndirectY, 5, true),
+    /* 0xb2 */ unofficial(Jam, Implied, 2, false),
+    /* 0xb3 */ unofficial(Lax, IndirectY, 5, true),
+    /* 0xb4 */ official(Ldy, ZeroPageX, 4, false),
+    /* 0xb5 */ official(Lda, ZeroPageX, 4, false),
+    /* 0xb6 */ official(Ldx, ZeroPageY, 4, false),
+    /* 0xb7 */ unofficial(Lax, ZeroPageY, 4, false),
+    /* 0xb8 */ official(Clv, Implied, 2, false),
+    /* 0xb9 */ official(Lda, AbsoluteY, 4, true),
+    /* 0xba */ official(Tsx, Implied, 2, false),
+    /* 0xbb */ unofficial(Las, AbsoluteY, 4, true),
+    /* 0xbc */ official(Ldy, AbsoluteX, 4, true),
+    /* 0xbd */ official(Lda, AbsoluteX, 4, true),
+    /* 0xbe */ official(Ldx, AbsoluteY, 4, true),
+    /* 0xbf */ unofficial(Lax, AbsoluteY, 4, true),
+    /* 0xc0 */ official(Cpy, Immediate, 2, false),
+    /* 0xc1 */ official(Cmp, IndirectX, 6, false),
+    /* 0xc2 */ unofficial(Ign, Immediate, 2, false),
+    /* 0xc3 */ unofficial(Dcp, IndirectX, 8, false),
+    /* 0xc4 */ official(Cpy, ZeroPage, 3, false),
+    /* 0xc5 */ official(Cmp, ZeroPage, 3, false),
+    /* 0xc6 */ official(Dec, ZeroPage, 5, false),
+    /* 0xc7 */ unofficial(Dcp, ZeroPage, 5, false),
+    /* 0xc8 */ official(Iny, Implied, 2, false),
+    /* 0xc9 */ official(Cmp, Immediate, 2, false),
+    /* 0xca */ official(Dex, Implied, 2, false),
+    /* 0xcb */ unofficial(Axs, Immediate, 2, false),
+    /* 0xcc */ official(Cpy, Absolute, 4, false),
+    /* 0xcd */ official(Cmp, Absolute, 4, false),
+    /* 0xce */ official(Dec, Absolute, 6, false),
+    /* 0xcf */ unofficial(Dcp, Absolute, 6, false),
+    /* 0xd0 */ official(Bne, Relative, 2, false),
+    /* 0xd1 */ official(Cmp, IndirectY, 5, true),
+    /* 0xd2 */ unofficial(Jam, Implied, 2, false),
+    /* 0xd3 */ unofficial(Dcp, IndirectY, 8, false),
+    /* 0xd4 */ unofficial(Ign, ZeroPageX, 4, false),
+    /* 0xd5 */ official(Cmp, ZeroPageX, 4, false),
+    /* 0xd6 */ official(Dec, ZeroPageX, 6, false),
+    /* 0xd7 */ unofficial(Dcp, ZeroPageX, 6, false),
+    /* 0xd8 */ official(Cld, Implied, 2, false),
+    /* 0xd9 */ official(Cmp, AbsoluteY, 4, true),
+    /* 0xda */ unofficial(Nop, Implied, 2, false),
+    /* 0xdb */ unofficial(Dcp, AbsoluteY, 7, false),
+    /* 0xdc */ unofficial(Ign, AbsoluteX, 4, true),
+    /* 0xdd */ official(Cmp, AbsoluteX, 4, true),
+    /* 0xde */ official(Dec, AbsoluteX, 7, false),
+    /* 0xdf */ unofficial(Dcp, AbsoluteX, 7, false),
+    /* 0xe0 */ official(Cpx, Immediate, 2, false),
+    /* 0xe1 */ official(Sbc, IndirectX, 6, false),
+    /* 0xe2 */ unofficial(Ign, Immediate, 2, false),
+    /* 0xe3 */ unofficial(Isb, IndirectX, 8, false),
+    /* 0xe4 */ official(Cpx, ZeroPage, 3, false),
+    /* 0xe5 */ official(Sbc, ZeroPage, 3, false),
+    /* 0xe6 */ official(Inc, ZeroPage, 5, false),
+    /* 0xe7 */ unofficial(Isb, ZeroPage, 5, false),
+    /* 0xe8 */ official(Inx, Implied, 2, false),
+    /* 0xe9 */ official(Sbc, Immediate, 2, false),
+    /* 0xea */ official(Nop, Implied, 2, false),
+    /* 0xeb */ unofficial(Sbc, Immediate, 2, false),
+    /* 0xec */ official(Cpx, Absolute, 4, false),
+    /* 0xed */ official(Sbc, Absolute, 4, false),
+    /* 0xee */ official(Inc, Absolute, 6, false),
+    /* 0xef */ unofficial(Isb, Absolute, 6, false),
+    /* 0xf0 */ official(Beq, Relative, 2, false),
+    /* 0xf1 */ official(Sbc, IndirectY, 5, true),
+    /* 0xf2 */ unofficial(Jam, Implied, 2, false),
+    /* 0xf3 */ unofficial(Isb, IndirectY, 8, false),
+    /* 0xf4 */ unofficial(Ign, ZeroPageX, 4, false),
+    /* 0xf5 */ official(Sbc, ZeroPageX, 4, false),
+    /* 0xf6 */ official(Inc, ZeroPageX, 6, false),
+    /* 0xf7 */ unofficial(Isb, ZeroPageX, 6, false),
+    /* 0xf8 */ official(Sed, Implied, 2, false),
+    /* 0xf9 */ official(Sbc, AbsoluteY, 4, true),
+    /* 0xfa */ unofficial(Nop, Implied, 2, false),
+    /* 0xfb */ unofficial(Isb, AbsoluteY, 7, false),
+    /* 0xfc */ unofficial(Ign, AbsoluteX, 4, true),
+    /* 0xfd */ official(Sbc, AbsoluteX, 4, true),
+    /* 0xfe */ official(Inc, AbsoluteX, 7, false),
+    /* 0xff */ unofficial(Isb, AbsoluteX, 7, false),
+];
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    pub fn test_official_opcode_count() {
+        let count = OPCODES.iter().filter(|decoded| decoded.official).count();
+
+        assert_eq!(count, 151);
+    }
+
+    #[test]
+    pub fn test_page_penalty_only_on_indexed_reads() {
+        // lda abs,x pays for crossing a page, sta abs,x always takes the long path
+        assert_eq!(decode(0xbd, OpcodeSet::Official), Some(official(Lda, AbsoluteX, 4, true)));
+        assert_eq!(decode(0x9d, OpcodeSet::Official), Some(official(Sta, AbsoluteX, 5, false)));
+        assert_eq!(decode(0x1e, OpcodeSet::Official), Some(official(Asl, AbsoluteX, 7, false)));
+    }
+
+    #[test]
+    pub fn test_unofficial_opcodes_need_nmos_set() {
+        assert_eq!(decode(0xa7, OpcodeSet::Official), None);
+        assert_eq!(decode(0xa7, OpcodeSet::Nmos), Some(unofficial(Lax, ZeroPage, 3, false)));
+
+        // sbc #imm has an undocumented twin at 0xeb
+        assert_eq!(decode(0xeb, OpcodeSet::Nmos).map(|decoded| decoded.instruction), Some(Sbc));
+    }
+}
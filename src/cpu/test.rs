@@ -1,82 +1,444 @@
-#[allow(unused_imports)]
-use ::memory_map;
-
-#[allow(unused_imports)]
-use super::Cpu;
-
-#[allow(unused_imports)]
-use super::CpuDebug;
-
-#[allow(unused_imports)]
-use super::CpuStack;
-
-#[test]
-fn test_push_pop() {
-    let mut cpu: Cpu<memory_map::NROMMemoryMap> = Cpu::default();
-    cpu.init_registers();
-
-    assert_eq!(&cpu.resolve_stack_pointer(), &memory_map::STACK_END);
-
-    cpu.push(42);
-    assert_eq!(&cpu.resolve_stack_pointer(), &(memory_map::STACK_END - 1));
-
-    assert_eq!(&cpu.pop(), &42);
-    assert_eq!(&cpu.resolve_stack_pointer(), &memory_map::STACK_END);
-}
-
-#[test]
-fn test_push_pop_u16() {
-    let mut cpu: Cpu<memory_map::NROMMemoryMap> = Cpu::default();
-    cpu.init_registers();
-
-    assert_eq!(&cpu.resolve_stack_pointer(), &memory_map::STACK_END);
-
-    cpu.push_u16(42);
-    assert_eq!(&cpu.resolve_stack_pointer(), &(memory_map::STACK_END - 2));
-
-    assert_eq!(&cpu.pop_u16(), &42);
-    assert_eq!(&cpu.resolve_stack_pointer(), &memory_map::STACK_END);
-}
-
-#[test]
-fn test_push_pop_mixed() {
-    let mut cpu: Cpu<memory_map::NROMMemoryMap> = Cpu::default();
-    cpu.init_registers();
-
-    assert_eq!(&cpu.resolve_stack_pointer(), &memory_map::STACK_END);
-
-    cpu.push_u16(42);
-    assert_eq!(&cpu.resolve_stack_pointer(), &(memory_map::STACK_END - 2));
-
-    cpu.push(23);
-    assert_eq!(&cpu.resolve_stack_pointer(), &(memory_map::STACK_END - 3));
-
-    cpu.push_u16(1991);
-    assert_eq!(&cpu.resolve_stack_pointer(), &(memory_map::STACK_END - 5));
-
-    assert_eq!(&cpu.pop_u16(), &1991);
-    assert_eq!(&cpu.resolve_stack_pointer(), &(memory_map::STACK_END - 3));
-
-    assert_eq!(&cpu.pop(), &23);
-    assert_eq!(&cpu.resolve_stack_pointer(), &(memory_map::STACK_END - 2));
-
-    assert_eq!(&cpu.pop_u16(), &42);
-    assert_eq!(&cpu.resolve_stack_pointer(), &memory_map::STACK_END);
-}
-
-#[test]
-fn test_exec_instr() {
-    let mut cpu: Cpu<memory_map::NROMMemoryMap> = Cpu::default();
-    cpu.init_registers();
-
-    // lda #$fe
-    cpu.exec_instr(&vec![0xa9, 0xfe]);
-    assert_eq!(cpu.reg_accumulator, 0xfe);
-
-    // wait for cpu to finish executing
-    cpu.step_instruction();
-
-    // sta $05
-    cpu.exec_instr(&vec![0x85, 0x05]);
-    assert_eq!(cpu.read(0x05), 0xfe);
-}
\ No newline at end of file
+#[allow(unused_imports)]
+use ::memory_map;
+
+#[allow(unused_imports)]
+use ::memory_map::MemoryMapper;
+
+#[allow(unused_imports)]
+use super::Cpu;
+
+#[allow(unused_imports)]
+use super::CpuDebug;
+
+#[allow(unused_imports)]
+use super::CpuStack;
+
+#[allow(unused_imports)]
+use super::ProcessorStatus;
+
+#[allow(unused_imports)]
+use super::addressing::AddressingMode;
+
+#[allow(unused_imports)]
+use super::opcodes;
+
+#[allow(unused_imports)]
+use super::opcodes::OpcodeSet;
+
+#[allow(unused_imports)]
+use super::asm;
+
+// Where sp points after power on
+#[allow(dead_code)]
+const STACK_TOP: u16 = memory_map::STACK_START + 0xfd;
+
+// Gets a cpu through reset with pc at the start of prg_rom
+#[allow(dead_code)]
+fn new_cpu() -> Cpu<memory_map::FlatMemoryMap> {
+    let mut cpu: Cpu<memory_map::FlatMemoryMap> = Cpu::default();
+    cpu.memory_map.write_u16(memory_map::RESET_VECTOR, memory_map::PRG_ROM_START);
+    cpu.power_on();
+    cpu.step_instruction();
+
+    cpu
+}
+
+#[test]
+fn test_push_pop() {
+    let mut cpu = new_cpu();
+
+    assert_eq!(&cpu.resolve_stack_pointer(), &STACK_TOP);
+
+    cpu.push(42);
+    assert_eq!(&cpu.resolve_stack_pointer(), &(STACK_TOP - 1));
+
+    assert_eq!(&cpu.pop(), &42);
+    assert_eq!(&cpu.resolve_stack_pointer(), &STACK_TOP);
+}
+
+#[test]
+fn test_push_pop_u16() {
+    let mut cpu = new_cpu();
+
+    assert_eq!(&cpu.resolve_stack_pointer(), &STACK_TOP);
+
+    cpu.push_u16(42);
+    assert_eq!(&cpu.resolve_stack_pointer(), &(STACK_TOP - 2));
+
+    assert_eq!(&cpu.pop_u16(), &42);
+    assert_eq!(&cpu.resolve_stack_pointer(), &STACK_TOP);
+}
+
+#[test]
+fn test_push_pop_mixed() {
+    let mut cpu = new_cpu();
+
+    assert_eq!(&cpu.resolve_stack_pointer(), &STACK_TOP);
+
+    cpu.push_u16(42);
+    assert_eq!(&cpu.resolve_stack_pointer(), &(STACK_TOP - 2));
+
+    cpu.push(23);
+    assert_eq!(&cpu.resolve_stack_pointer(), &(STACK_TOP - 3));
+
+    cpu.push_u16(1991);
+    assert_eq!(&cpu.resolve_stack_pointer(), &(STACK_TOP - 5));
+
+    assert_eq!(&cpu.pop_u16(), &1991);
+    assert_eq!(&cpu.resolve_stack_pointer(), &(STACK_TOP - 3));
+
+    assert_eq!(&cpu.pop(), &23);
+    assert_eq!(&cpu.resolve_stack_pointer(), &(STACK_TOP - 2));
+
+    assert_eq!(&cpu.pop_u16(), &42);
+    assert_eq!(&cpu.resolve_stack_pointer(), &STACK_TOP);
+}
+
+#[test]
+fn test_exec_instr() {
+    let mut cpu = new_cpu();
+
+    // lda #$fe
+    cpu.exec_instr(&[0xa9, 0xfe]);
+    assert_eq!(cpu.reg_accumulator, 0xfe);
+
+    // sta $05
+    cpu.exec_instr(&[0x85, 0x05]);
+    assert_eq!(cpu.read(0x05), 0xfe);
+}
+
+#[test]
+fn test_adc_sbc_flags() {
+    let mut cpu = new_cpu();
+
+    // signed overflow into negative
+    cpu.exec_asm("lda #$7f");
+    cpu.exec_asm("adc #$01");
+    assert_eq!(cpu.reg_accumulator, 0x80);
+    assert!(cpu.processor_status.overflow_flag);
+    assert!(cpu.processor_status.negative);
+    assert!(!cpu.processor_status.carry_flag);
+
+    // 0x7f with no borrow, overflow back to positive
+    cpu.exec_asm("sec");
+    cpu.exec_asm("sbc #$01");
+    assert_eq!(cpu.reg_accumulator, 0x7f);
+    assert!(cpu.processor_status.overflow_flag);
+    assert!(cpu.processor_status.carry_flag);
+}
+
+#[test]
+fn test_branch_backwards() {
+    let mut cpu = new_cpu();
+
+    // zero flag is clear after reset, so this branches back over itself
+    cpu.exec_asm("bne $7ffe");
+    assert_eq!(cpu.reg_program_counter, memory_map::PRG_ROM_START - 2);
+}
+
+#[test]
+fn test_jmp_indirect_page_wrap() {
+    let mut cpu = new_cpu();
+
+    cpu.write(0x02ff, 0x34);
+    cpu.write(0x0200, 0x12);
+    cpu.write(0x0300, 0xff);
+
+    // jmp ($02ff) takes its high byte from $0200, not $0300
+    cpu.exec_asm("jmp ($02ff)");
+    assert_eq!(cpu.reg_program_counter, 0x1234);
+}
+
+#[test]
+fn test_jsr_rts() {
+    let mut cpu = new_cpu();
+
+    cpu.exec_asm("jsr $9000");
+    assert_eq!(cpu.reg_program_counter, 0x9000);
+    let sp = cpu.resolve_stack_pointer();
+    assert_eq!(cpu.memory_map.read_u16(sp + 1), 0x8002);
+
+    cpu.exec_asm("rts");
+    assert_eq!(cpu.reg_program_counter, 0x8003);
+    assert_eq!(cpu.resolve_stack_pointer(), STACK_TOP);
+}
+
+#[test]
+fn test_rol_ror_through_carry() {
+    let mut cpu = new_cpu();
+
+    cpu.write(0x10, 0b1000_0001);
+
+    // carry out of bit 7, nothing carried in
+    cpu.exec_asm("rol $10");
+    assert_eq!(cpu.read(0x10), 0b0000_0010);
+    assert!(cpu.processor_status.carry_flag);
+
+    // carry lands in bit 7
+    cpu.exec_asm("ror a");
+    assert_eq!(cpu.reg_accumulator, 0b1000_0000);
+    assert!(!cpu.processor_status.carry_flag);
+    assert!(cpu.processor_status.negative);
+}
+
+#[test]
+fn test_page_cross_penalty() {
+    let mut cpu = new_cpu();
+    cpu.reg_index_x = 1;
+
+    // 4 cycles + 1 for landing on $0300
+    let start = cpu.cycles();
+    cpu.exec_asm("lda $02ff,x");
+    assert_eq!(cpu.cycles() - start, 5);
+
+    // no penalty when it stays on the page
+    let start = cpu.cycles();
+    cpu.exec_asm("lda $0200,x");
+    assert_eq!(cpu.cycles() - start, 4);
+}
+
+// Runs opcode at pc with both index registers set to index and the status set
+// to status, and returns how many cycles it took. Its operand is $0210, or a
+// pointer to $0210 at $10 for the indirect modes, and relative branches go
+// forward $10.
+#[allow(dead_code)]
+fn opcode_cycles(opcode: u8, pc: u16, index: u8, status: u8) -> u64 {
+    let mut cpu = new_cpu();
+    cpu.memory_map.write_u16(0x0010, 0x0210);
+    cpu.write(pc, opcode);
+    cpu.memory_map.write_u16(pc.wrapping_add(1), 0x0210);
+
+    cpu.reg_program_counter = pc;
+    cpu.reg_index_x = index;
+    cpu.reg_index_y = index;
+    cpu.processor_status = ProcessorStatus::from_u8(status);
+
+    let start = cpu.cycles();
+    cpu.step_instruction();
+
+    cpu.cycles() - start
+}
+
+#[test]
+fn test_cycles_match_the_opcode_table() {
+    for (opcode, decoded) in opcodes::OPCODES.iter().enumerate() {
+        let opcode = opcode as u8;
+        let cycles = decoded.cycles as u64;
+
+        match decoded.mode {
+            // not taken with one set of flags and taken with the other, then
+            // taken from $80f0 onto the next page
+            AddressingMode::Relative => {
+                let mut same_page = [opcode_cycles(opcode, 0x8000, 0, 0x00), opcode_cycles(opcode, 0x8000, 0, 0xff)];
+                same_page.sort();
+                assert_eq!(same_page, [cycles, cycles + 1], "opcode {:#04x}", opcode);
+
+                let other_page = opcode_cycles(opcode, 0x80f0, 0, 0x00).max(opcode_cycles(opcode, 0x80f0, 0, 0xff));
+                assert_eq!(other_page, cycles + 2, "opcode {:#04x}", opcode);
+            }
+
+            // $f0 takes every indexed mode from $0210 onto the next page
+            _ => {
+                assert_eq!(opcode_cycles(opcode, 0x8000, 0, 0x00), cycles, "opcode {:#04x}", opcode);
+                assert_eq!(opcode_cycles(opcode, 0x8000, 0xf0, 0x00),
+                           cycles + decoded.page_penalty as u64,
+                           "opcode {:#04x} crossing a page",
+                           opcode);
+            }
+        }
+    }
+}
+
+#[test]
+fn test_read_modify_write_cycles() {
+    let mut cpu = new_cpu();
+    cpu.reg_index_x = 1;
+
+    // always takes the fix-up cycle, then read, write, write
+    let start = cpu.cycles();
+    cpu.exec_asm("inc $0200,x");
+    assert_eq!(cpu.cycles() - start, 7);
+    assert_eq!(cpu.read(0x0201), 1);
+}
+
+#[test]
+fn test_tick_spreads_accesses_over_cycles() {
+    let mut cpu = new_cpu();
+
+    // fetch opcode, fetch operand, then the write on the last cycle
+    cpu.reg_accumulator = 0x42;
+    asm::assemble("sta $10").unwrap().load_into(&mut cpu.memory_map);
+
+    cpu.tick();
+    cpu.tick();
+    assert_eq!(cpu.read(0x10), 0);
+
+    cpu.tick();
+    assert_eq!(cpu.read(0x10), 0x42);
+    assert_eq!(cpu.reg_program_counter, 0x8002);
+}
+
+#[test]
+fn test_unofficial_lax_sax() {
+    let mut cpu = new_cpu();
+
+    cpu.write(0x10, 0b1100_1100);
+
+    // a = x = value
+    cpu.exec_asm("lax $10");
+    assert_eq!(cpu.reg_accumulator, 0b1100_1100);
+    assert_eq!(cpu.reg_index_x, 0b1100_1100);
+
+    // sax stores a & x
+    cpu.exec_asm("ldx #$0f");
+    cpu.exec_asm("sax $11");
+    assert_eq!(cpu.read(0x11), 0b0000_1100);
+}
+
+#[test]
+fn test_unofficial_dcp() {
+    let mut cpu = new_cpu();
+
+    cpu.write(0x10, 0x43);
+
+    // memory decremented, then compared against a
+    cpu.exec_asm("lda #$42");
+    cpu.exec_asm("dcp $10");
+    assert_eq!(cpu.read(0x10), 0x42);
+    assert!(cpu.processor_status.zero);
+    assert!(cpu.processor_status.carry_flag);
+}
+
+#[test]
+fn test_strict_opcode_set_rejects_unofficial() {
+    let mut cpu = new_cpu();
+    cpu.set_opcode_set(OpcodeSet::Official);
+    cpu.write(0x10, 0x42);
+
+    // lax $10 gets reported instead of run, and the cpu stays put
+    cpu.exec_asm("lax $10");
+    cpu.step_instruction();
+    assert_eq!(cpu.illegal_opcode(), Some((0xa7, 0x8000)));
+    assert_eq!(cpu.reg_program_counter, 0x8000);
+    assert_eq!(cpu.reg_accumulator, 0);
+    assert_eq!(cpu.reg_index_x, 0);
+
+    // until it's reset
+    cpu.reset();
+    cpu.step_instruction();
+    assert_eq!(cpu.illegal_opcode(), None);
+}
+
+#[test]
+fn test_jam_locks_up_until_reset() {
+    let mut cpu = new_cpu();
+    cpu.memory_map.write_u16(memory_map::NMI_VECTOR, 0xa000);
+
+    cpu.exec_asm("jam");
+    assert!(cpu.jammed());
+
+    // not even an nmi gets it going
+    cpu.set_nmi_line(true);
+    cpu.step_instruction();
+    cpu.step_instruction();
+    assert_eq!(cpu.reg_program_counter, 0x8001);
+
+    cpu.reset();
+    cpu.step_instruction();
+    assert!(!cpu.jammed());
+}
+
+#[test]
+fn test_reset_reads_vector() {
+    let mut cpu: Cpu<memory_map::FlatMemoryMap> = Cpu::default();
+    cpu.memory_map.write_u16(memory_map::RESET_VECTOR, 0xc000);
+    cpu.power_on();
+    cpu.step_instruction();
+
+    assert_eq!(cpu.reg_program_counter, 0xc000);
+    assert_eq!(cpu.reg_stack_pointer, 0xfd);
+    assert!(cpu.processor_status.interrupts_disabled);
+    assert_eq!(cpu.cycles(), 7);
+}
+
+#[test]
+fn test_nmi_is_edge_triggered() {
+    let mut cpu = new_cpu();
+    cpu.memory_map.write_u16(memory_map::NMI_VECTOR, 0x9000);
+    cpu.write(0x8000, 0xea);
+    cpu.write(0x9000, 0xea);
+    cpu.write(0x9001, 0xea);
+
+    // the line is only sampled at the end of the nop's cycles, so the nop
+    // still runs first
+    cpu.set_nmi_line(true);
+    cpu.step_instruction();
+    assert_eq!(cpu.reg_program_counter, 0x8001);
+
+    cpu.step_instruction();
+    assert_eq!(cpu.reg_program_counter, 0x9000);
+
+    // nmi pushes p with b clear
+    assert_eq!(cpu.read(cpu.resolve_stack_pointer() + 1) & 0b0001_0000, 0);
+
+    // holding the line doesn't fire it again
+    cpu.step_instruction();
+    cpu.step_instruction();
+    assert_eq!(cpu.reg_program_counter, 0x9002);
+}
+
+#[test]
+fn test_irq_waits_an_instruction_after_cli() {
+    let mut cpu = new_cpu();
+    cpu.memory_map.write_u16(memory_map::IRQ_VECTOR, 0x9000);
+    cpu.set_irq_line(true);
+
+    // masked while i is set
+    cpu.exec_asm("nop");
+    assert_eq!(cpu.reg_program_counter, 0x8001);
+
+    // the nop after cli still runs before the irq is taken
+    cpu.exec_asm("cli");
+    cpu.exec_asm("nop");
+    assert_eq!(cpu.reg_program_counter, 0x8003);
+
+    cpu.step_instruction();
+    assert_eq!(cpu.reg_program_counter, 0x9000);
+    assert!(cpu.processor_status.interrupts_disabled);
+}
+
+#[test]
+fn test_brk_rti() {
+    let mut cpu = new_cpu();
+    cpu.memory_map.write_u16(memory_map::IRQ_VECTOR, 0x9000);
+
+    // brk skips the padding byte and pushes p with b set
+    cpu.exec_asm("brk\n.byte $ff");
+    assert_eq!(cpu.reg_program_counter, 0x9000);
+    assert_eq!(cpu.read(cpu.resolve_stack_pointer() + 1) & 0b0001_0000, 0b0001_0000);
+
+    // rti returns after the padding byte with i restored
+    cpu.exec_asm("rti");
+    assert_eq!(cpu.reg_program_counter, 0x8002);
+    assert!(cpu.processor_status.interrupts_disabled);
+    assert!(!cpu.processor_status.bit_four);
+}
+
+#[test]
+fn test_nmi_hijacks_brk() {
+    let mut cpu = new_cpu();
+    cpu.memory_map.write_u16(memory_map::IRQ_VECTOR, 0x9000);
+    cpu.memory_map.write_u16(memory_map::NMI_VECTOR, 0xa000);
+
+    // an nmi arriving mid-brk steals the vector but keeps brk's b flag
+    cpu.write(0x8000, 0x00);
+    cpu.tick();
+    cpu.tick();
+    cpu.tick();
+    cpu.set_nmi_line(true);
+    cpu.step_instruction();
+
+    assert_eq!(cpu.reg_program_counter, 0xa000);
+    assert!(!cpu.nmi_pending);
+    assert_eq!(cpu.read(cpu.resolve_stack_pointer() + 1) & 0b0001_0000, 0b0001_0000);
+}
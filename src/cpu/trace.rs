@@ -0,0 +1,118 @@
+use ::cpu::Cpu;
+use ::cpu::ProcessorStatus;
+use ::cpu::addressing::AddressingMode;
+use ::cpu::disasm;
+use ::cpu::instructions::Instruction;
+use ::cpu::opcodes;
+use ::memory_map::MemoryMapper;
+
+// [Trace]
+// Formats the instruction the cpu is about to run the same way Nintendulator
+// (and so nestest.log) does, on top of the disassembler's syntax, e.g.
+//
+// C72C  B0 04     BCS $C732                       A:00 X:00 Y:00 P:27 SP:FB PPU:  0, 30 CYC:10
+//
+// Operands that touch memory are followed by what's there before the
+// instruction runs ("@ addr" for the effective address when it's indexed or
+// indirect, "= value" for the value), and unofficial opcodes get a * in front
+// of their mnemonic.
+//
+// The ppu position comes from the memory map's ppu. Without one (e.g. a bare
+// test memory map) it's worked out from the cycle count instead, as 3 dots per
+// cycle, 341 dots per scanline and 262 scanlines per frame.
+//
+// [Resources]
+// nestest => http://www.qmtpro.com/~nes/misc/nestest.txt
+
+const DOTS_PER_SCANLINE: u64 = 341;
+const SCANLINES_PER_FRAME: u64 = 262;
+
+// Traces the instruction at pc; only makes sense between instructions
+pub fn trace<T: MemoryMapper>(cpu: &Cpu<T>) -> String {
+    let pc = cpu.reg_program_counter;
+    let decoded = opcodes::OPCODES[cpu.peek(pc) as usize];
+
+    let len = decoded.mode.operand_len() + 1;
+    let bytes = (0..len).map(|i| cpu.peek(pc.wrapping_add(i))).collect::<Vec<u8>>();
+    let line = disasm::disassemble_one(&bytes, pc).unwrap();
+
+    // the * for unofficial opcodes hangs off into the column before
+    let disassembly = format!("{}{}", line.text, annotation(cpu, decoded.instruction, decoded.mode, pc));
+    let disassembly = match decoded.official {
+        true => format!(" {}", disassembly),
+        false => disassembly,
+    };
+    let bytes = bytes.iter().map(|byte| format!("{:02X}", byte)).collect::<Vec<String>>().join(" ");
+
+    let (scanline, dot) = cpu.memory_map.ppu_position().unwrap_or_else(|| {
+        let dots = cpu.cycles * 3;
+
+        (((dots / DOTS_PER_SCANLINE) % SCANLINES_PER_FRAME) as u16, (dots % DOTS_PER_SCANLINE) as u16)
+    });
+
+    format!("{:04X}  {:<8} {:<33}A:{:02X} X:{:02X} Y:{:02X} P:{:02X} SP:{:02X} PPU:{:>3},{:>3} CYC:{}",
+            pc,
+            bytes,
+            disassembly,
+            cpu.reg_accumulator,
+            cpu.reg_index_x,
+            cpu.reg_index_y,
+            ProcessorStatus::to_u8(&cpu.processor_status),
+            cpu.reg_stack_pointer,
+            scanline,
+            dot,
+            cpu.cycles)
+}
+
+// What's in memory at the operand, as Nintendulator shows it
+fn annotation<T: MemoryMapper>(cpu: &Cpu<T>, instruction: Instruction, mode: AddressingMode, pc: u16) -> String {
+    let lo = cpu.peek(pc.wrapping_add(1));
+    let word = (cpu.peek(pc.wrapping_add(2)) as u16) << 8 | lo as u16;
+
+    // reads a pointer out of the zero page, wrapping within it
+    let zero_page_u16 = |pointer: u8| (cpu.peek(pointer.wrapping_add(1) as u16) as u16) << 8 | cpu.peek(pointer as u16) as u16;
+
+    match mode {
+        AddressingMode::ZeroPage => format!(" = {:02X}", cpu.peek(lo as u16)),
+        AddressingMode::ZeroPageX | AddressingMode::ZeroPageY => {
+            let index = match mode {
+                AddressingMode::ZeroPageX => cpu.reg_index_x,
+                _ => cpu.reg_index_y,
+            };
+
+            let address = lo.wrapping_add(index);
+            format!(" @ {:02X} = {:02X}", address, cpu.peek(address as u16))
+        }
+        AddressingMode::Absolute => {
+            match instruction {
+                Instruction::Jmp | Instruction::Jsr => String::new(),
+                _ => format!(" = {:02X}", cpu.peek(word)),
+            }
+        }
+        AddressingMode::AbsoluteX | AddressingMode::AbsoluteY => {
+            let index = match mode {
+                AddressingMode::AbsoluteX => cpu.reg_index_x,
+                _ => cpu.reg_index_y,
+            };
+
+            let address = word.wrapping_add(index as u16);
+            format!(" @ {:04X} = {:02X}", address, cpu.peek(address))
+        }
+        AddressingMode::Indirect => {
+            // same page wrapping bug as jmp itself
+            let hi = cpu.peek((word & 0xff00) | (word.wrapping_add(1) & 0x00ff)) as u16;
+            format!(" = {:04X}", (hi << 8) | cpu.peek(word) as u16)
+        }
+        AddressingMode::IndirectX => {
+            let pointer = lo.wrapping_add(cpu.reg_index_x);
+            let address = zero_page_u16(pointer);
+            format!(" @ {:02X} = {:04X} = {:02X}", pointer, address, cpu.peek(address))
+        }
+        AddressingMode::IndirectY => {
+            let base = zero_page_u16(lo);
+            let address = base.wrapping_add(cpu.reg_index_y as u16);
+            format!(" = {:04X} @ {:04X} = {:02X}", base, address, cpu.peek(address))
+        }
+        _ => String::new(),
+    }
+}
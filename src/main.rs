@@ -3,40 +3,322 @@ pub mod ppu;
 pub mod memory_map;
 pub mod rom;
 pub mod bits;
+pub mod save;
+pub mod state;
 
+use std::env;
+use std::fs;
 use std::fs::File;
-use memory_map::MemoryMapper;
+use std::io;
+use std::io::BufRead;
+use std::io::Write;
+use std::path::Path;
+use std::process;
+use std::sync::mpsc;
+use std::thread;
+use memory_map::Bus;
+use memory_map::Cartridge;
 
 extern crate byteorder;
 
-#[derive(Debug, Default)]
-struct Nes<T: MemoryMapper> {
-    cpu: cpu::Cpu<T>,
+// How often the save ram gets written out: about 5 seconds' worth of cycles
+const SAVE_INTERVAL_CYCLES: u64 = 5 * 1_789_773;
+
+// How often we check for commands: about a frame's worth of cycles
+const COMMAND_INTERVAL_CYCLES: u64 = 29_781;
+
+struct Nes<C: Cartridge> {
+    cpu: cpu::Cpu<Bus<C>>,
+
+    // Which game this is, for telling save states apart
+    rom_checksum: u32,
+
+    save_file: Option<save::SaveFile>,
+    last_saved_at: u64,
+}
+
+impl Nes<Box<dyn Cartridge>> {
+    // Plugs in whichever mapper the rom asks for
+    pub fn from_rom(rom: &rom::NesRom) -> Result<Self, rom::RomError> {
+        let cartridge = memory_map::create_mapper(rom)?;
+
+        Ok(Nes {
+            cpu: cpu::Cpu::new(Bus::new(cartridge)),
+            rom_checksum: rom.checksum(),
+            save_file: None,
+            last_saved_at: 0,
+        })
+    }
+}
+
+impl<C: Cartridge> Nes<C> {
+    // Loads the save ram from save_file, and keeps it up to date from now on
+    pub fn use_save_file(&mut self, mut save_file: save::SaveFile) -> io::Result<()> {
+        save_file.load(self.cpu.memory_map_mut().cartridge_mut())?;
+        self.save_file = Some(save_file);
+
+        Ok(())
+    }
+
+    pub fn flush_save_ram(&mut self) {
+        self.last_saved_at = self.cpu.cycles();
+
+        if let Some(ref mut save_file) = self.save_file {
+            if let Err(error) = save_file.flush(self.cpu.memory_map().cartridge()) {
+                eprintln!("nesc: couldn't write {}: {}", save_file.path().display(), error);
+            }
+        }
+    }
+
+    pub fn save_state(&self) -> Vec<u8> {
+        state::save(&self.cpu, self.rom_checksum)
+    }
+
+    pub fn load_state(&mut self, data: &[u8]) -> Result<(), state::StateError> {
+        state::load(&mut self.cpu, self.rom_checksum, data)
+    }
+
+    pub fn power_on(&mut self) {
+        self.cpu.power_on();
+    }
+
+    // Runs whole instructions for (at least) the given number of cycles
+    pub fn run_for(&mut self, cycles: u64) {
+        let until = self.cpu.cycles() + cycles;
+
+        while self.cpu.cycles() < until {
+            self.cpu.step_instruction();
+
+            if self.cpu.cycles() - self.last_saved_at >= SAVE_INTERVAL_CYCLES {
+                self.flush_save_ram();
+            }
+        }
+    }
+}
+
+// What can be typed at a running nesc, one per line
+enum Command {
+    SaveState(u8),
+    LoadState(u8),
+    Quit,
 }
 
-impl<T: MemoryMapper> Nes<T> {
-    pub fn power_on(&mut self) {}
+fn parse_command(line: &str) -> Option<Command> {
+    let words = line.split_whitespace().collect::<Vec<&str>>();
 
-    pub fn run(&mut self) {
-        self.cpu.run();
+    match words.as_slice() {
+        ["save", slot] => slot.parse().ok().map(Command::SaveState),
+        ["load", slot] => slot.parse().ok().map(Command::LoadState),
+        ["quit"] => Some(Command::Quit),
+        _ => None,
     }
+}
+
+// Reads commands off stdin without holding up the emulator
+fn read_commands() -> mpsc::Receiver<Command> {
+    let (sender, receiver) = mpsc::channel();
+
+    thread::spawn(move || {
+        let stdin = io::stdin();
 
-    pub fn load_rom(&mut self, rom: rom::NesRom) {
-        self.cpu.load(&rom);
+        for line in stdin.lock().lines() {
+            let line = match line {
+                Ok(line) => line,
+                Err(_) => return,
+            };
 
-        // TODO: load rom into ppu
+            match parse_command(&line) {
+                Some(command) => {
+                    if sender.send(command).is_err() {
+                        return;
+                    }
+                }
+                None => eprintln!("nesc: commands are save <slot>, load <slot> and quit"),
+            }
+        }
+    });
+
+    receiver
+}
+
+impl<C: Cartridge> Drop for Nes<C> {
+    fn drop(&mut self) {
+        self.flush_save_ram();
     }
 }
 
 fn main() {
-    let rom_path = "c:/users/eric lauffenburger/downloads/roms/nes/nestest.nes";
-    let rom_file = File::open(rom_path).unwrap();
+    let all_args: Vec<String> = env::args().collect();
+    let mut args = all_args[1..].iter().map(|arg| arg.as_str()).collect::<Vec<&str>>();
+
+    let mut options = RunOptions::default();
+    loop {
+        match args.as_slice() {
+            ["--save-dir", save_dir, ..] => options.save_dir = Some(Path::new(*save_dir)),
+            ["--load-state", slot, ..] => options.load_state = slot.parse().ok().or_else(|| usage()),
+            _ => break,
+        }
+
+        args.drain(..2);
+    }
+
+    match args.as_slice() {
+        ["disasm", rom_path] => disasm(rom_path),
+        [rom_path] if *rom_path != "disasm" => run(rom_path, &options),
+        _ => usage(),
+    }
+}
 
-    let rom = rom::NesRom::from_nes_file(rom_file);
-    let mut nes: Nes<memory_map::NROMMemoryMap> = Nes::default();
+fn usage() -> ! {
+    eprintln!("usage: nesc [--save-dir <dir>] [--load-state <slot>] <rom>\n       nesc disasm <rom>\n\n\
+               while running, type save <slot>, load <slot> or quit");
+    process::exit(1);
+}
+
+#[derive(Default)]
+struct RunOptions<'a> {
+    // Where .sav files and save states go, instead of next to the rom
+    save_dir: Option<&'a Path>,
+
+    // A save state slot to start from
+    load_state: Option<u8>,
+}
+
+fn open_rom(rom_path: &str) -> rom::NesRom {
+    let rom = File::open(rom_path).map_err(rom::RomError::from).and_then(rom::NesRom::from_nes_file);
+
+    match rom {
+        Ok(rom) => rom,
+        Err(error) => exit_with(error),
+    }
+}
+
+fn exit_with(error: rom::RomError) -> ! {
+    eprintln!("nesc: {}", error);
+    process::exit(1);
+}
+
+fn run(rom_path: &str, options: &RunOptions) {
+    let rom = open_rom(rom_path);
+    let save_dir = options.save_dir;
+    let mut nes = match Nes::from_rom(&rom) {
+        Ok(nes) => nes,
+        Err(error) => exit_with(error),
+    };
+
+    if rom.has_battery_backed_ram() {
+        let save_file = save::SaveFile::new(save::save_path(Path::new(rom_path), save_dir));
+
+        if let Err(error) = nes.use_save_file(save_file) {
+            eprintln!("nesc: couldn't load the save: {}", error);
+        }
+    }
 
-    nes.load_rom(rom);
     nes.power_on();
 
-    nes.run();
-}
\ No newline at end of file
+    if let Some(slot) = options.load_state {
+        load_state_slot(&mut nes, rom_path, save_dir, slot);
+    }
+
+    let commands = read_commands();
+    loop {
+        nes.run_for(COMMAND_INTERVAL_CYCLES);
+
+        if let Some((opcode, pc)) = nes.cpu.illegal_opcode() {
+            eprintln!("nesc: illegal opcode ${:02X} at ${:04X}", opcode, pc);
+            break;
+        }
+
+        match commands.try_recv() {
+            Ok(Command::SaveState(slot)) => save_state_slot(&nes, rom_path, save_dir, slot),
+            Ok(Command::LoadState(slot)) => load_state_slot(&mut nes, rom_path, save_dir, slot),
+            Ok(Command::Quit) => break,
+            Err(_) => {}
+        }
+    }
+}
+
+fn save_state_slot<C: Cartridge>(nes: &Nes<C>, rom_path: &str, save_dir: Option<&Path>, slot: u8) {
+    let path = state::slot_path(Path::new(rom_path), save_dir, slot);
+
+    match fs::write(&path, nes.save_state()) {
+        Ok(()) => eprintln!("nesc: saved state {}", slot),
+        Err(error) => eprintln!("nesc: couldn't write {}: {}", path.display(), error),
+    }
+}
+
+fn load_state_slot<C: Cartridge>(nes: &mut Nes<C>, rom_path: &str, save_dir: Option<&Path>, slot: u8) {
+    let path = state::slot_path(Path::new(rom_path), save_dir, slot);
+    let result = fs::read(&path).map_err(state::StateError::from).and_then(|data| nes.load_state(&data));
+
+    match result {
+        Ok(()) => eprintln!("nesc: loaded state {}", slot),
+        Err(error) => eprintln!("nesc: couldn't load {}: {}", path.display(), error),
+    }
+}
+
+// Dumps every prg bank as a listing. There's no telling where a mapper will
+// put a bank, so the last one is shown at $c000 (where the vectors need it to
+// be) and the rest at $8000.
+#[allow(unused_must_use)]
+fn disasm(rom_path: &str) {
+    let rom = open_rom(rom_path);
+    let banks = rom.prg_rom.chunks(memory_map::PRG_ROM_BANK_SIZE).collect::<Vec<&[u8]>>();
+
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+
+    for (i, bank) in banks.iter().enumerate() {
+        let origin = match i == banks.len() - 1 {
+            true => 0xc000,
+            false => memory_map::PRG_ROM_START,
+        };
+
+        writeln!(out, "; prg bank {} at ${:04X}", i, origin);
+        for line in cpu::disasm::disassemble(bank, origin) {
+            writeln!(out, "{}", line);
+        }
+        writeln!(out);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use ::cpu::asm;
+    use ::memory_map::MemoryMapper;
+
+    // A tiny NROM game: turns on the nmi and counts frames in $00
+    const FRAME_COUNTER: &str = "
+        .org $c000
+        reset: sei
+               ldx #$ff
+               txs
+               lda #$80
+               sta $2000
+        loop:  jmp loop
+
+        nmi:   inc $00
+        irq:   rti
+
+        .org $fffa
+        .word nmi, reset, irq
+    ";
+
+    #[test]
+    pub fn test_runs_a_rom_from_the_reset_vector() {
+        let program = asm::assemble(FRAME_COUNTER).unwrap();
+
+        let mut rom = rom::NesRom::default();
+        rom.prg_rom = program.image(0xc000, memory_map::PRG_ROM_BANK_SIZE);
+        rom.chr_ram = vec![0; memory_map::CHR_SIZE];
+
+        let mut nes = Nes::from_rom(&rom).unwrap();
+        nes.power_on();
+        nes.run_for(3 * COMMAND_INTERVAL_CYCLES);
+
+        let bus = nes.cpu.memory_map();
+        assert_eq!(bus.ppu().frames(), 3);
+        assert_eq!(bus.peek(0x0000), 3);
+    }
+}
@@ -0,0 +1,373 @@
+use ::memory_map::cartridge::Cartridge;
+use ::memory_map::constants::*;
+use ::memory_map::common::*;
+use ::memory_map::dma::*;
+use ::memory_map::MemoryMapper;
+use ::ppu;
+use ::ppu::Ppu;
+use ::state::SaveState;
+use ::state::StateError;
+use ::state::StateReader;
+use ::state::StateWriter;
+
+use std::fmt;
+use std::fmt::Debug;
+
+// [Bus]
+// The cpu's view of the console. Everything below $4020 belongs to the
+// console itself and the rest goes to the cartridge:
+//
+// $0000-$1fff => 2 KiB of ram, mirrored every $800
+// $2000-$3fff => the 8 ppu registers, mirrored every 8 bytes
+// $4000-$401f => apu and controller registers
+// $4020-$ffff => cartridge
+//
+// The ppu runs 3 dots for every cpu cycle, and gets at the cartridge through
+// the bus. Writing $4014 starts an oam dma (see dma.rs), which halts the cpu
+// and copies a page into the ppu's oam. There's no apu or controllers to hand
+// their registers to yet, so for now those are plain bytes.
+//
+// [Open bus]
+// The data bus holds on to the last value that went across it, so a read
+// that nothing answers gets that value back. That's usually the last byte of
+// the instruction doing the read, e.g. lda $5000 gets $50. The same goes for
+// write-only registers and the bits of a register that aren't wired up:
+//
+// $2000-$3fff  => the ppu's own data bus (see ppu/registers.rs)
+// $4015        => bit 5 isn't driven (and the read happens inside the cpu, so
+//                 it doesn't reach the data bus at all)
+// $4016, $4017 => only bits 0-4 are driven
+// other $40xx  => write only
+//
+// [Resources]
+// cpu memory map => http://wiki.nesdev.com/w/index.php/CPU_memory_map
+// open bus => http://wiki.nesdev.com/w/index.php/Open_bus_behavior
+
+const PPU_DOTS_PER_CPU_CYCLE: usize = 3;
+
+const APU_STATUS: u16 = 0x4015;
+const JOYPAD_1: u16 = 0x4016;
+const JOYPAD_2: u16 = 0x4017;
+
+pub struct Bus<C: Cartridge> {
+    ram: Vec<u8>,
+    ppu: Ppu,
+    io_registers: [u8; IO_REGISTERS_HI_SIZE],
+    oam_dma: OamDma,
+
+    // The last value on the data bus
+    data_bus: u8,
+
+    cartridge: C,
+}
+
+impl<C: Cartridge> Bus<C> {
+    pub fn new(cartridge: C) -> Self {
+        Bus {
+            ram: vec![0; CONSOLE_RAM_SIZE],
+            ppu: Ppu::new(),
+            io_registers: [0; IO_REGISTERS_HI_SIZE],
+            oam_dma: OamDma::new(),
+            data_bus: 0,
+            cartridge,
+        }
+    }
+
+    pub fn cartridge(&self) -> &C {
+        &self.cartridge
+    }
+
+    pub fn cartridge_mut(&mut self) -> &mut C {
+        &mut self.cartridge
+    }
+
+    pub fn ppu(&self) -> &Ppu {
+        &self.ppu
+    }
+
+    pub fn ppu_mut(&mut self) -> &mut Ppu {
+        &mut self.ppu
+    }
+
+    pub fn data_bus(&self) -> u8 {
+        self.data_bus
+    }
+
+    // Works out what a read would see, filling in anything undriven from the
+    // data bus
+    fn decode(&self, address: u16) -> u8 {
+        match address {
+            RAM_START..=RAM_END => self.ram[resolve_console_address(address) as usize],
+            PPU_REGISTERS_START..=PPU_REGISTERS_END => self.ppu.peek_register(resolve_ppu_register(address)),
+            IO_REGISTERS_START..=IO_REGISTERS_END => {
+                let val = self.io_registers[(address - IO_REGISTERS_START) as usize];
+
+                match address {
+                    APU_STATUS => (val & 0b1101_1111) | (self.data_bus & 0b0010_0000),
+                    JOYPAD_1 | JOYPAD_2 => (val & 0b0001_1111) | (self.data_bus & 0b1110_0000),
+                    _ => self.data_bus,
+                }
+            }
+            _ => self.cartridge.cpu_read(address).unwrap_or(self.data_bus),
+        }
+    }
+}
+
+// Which of the 8 ppu registers an address in $2000-$3fff is
+fn resolve_ppu_register(address: u16) -> u16 {
+    resolve_console_address(address) - PPU_REGISTERS_START
+}
+
+impl<C: Cartridge> MemoryMapper for Bus<C> {
+    fn read(&mut self, address: u16) -> u8 {
+        let val = match address {
+            PPU_REGISTERS_START..=PPU_REGISTERS_END => {
+                self.ppu.read_register(resolve_ppu_register(address), &mut self.cartridge)
+            }
+            _ => self.decode(address),
+        };
+
+        if address != APU_STATUS {
+            self.data_bus = val;
+        }
+
+        val
+    }
+
+    fn read_u16(&mut self, address: u16) -> u16 {
+        let lo = self.read(address) as u16;
+        let hi = self.read(address.wrapping_add(1)) as u16;
+
+        (hi << 8) | lo
+    }
+
+    fn write(&mut self, address: u16, val: u8) {
+        self.data_bus = val;
+
+        match address {
+            RAM_START..=RAM_END => self.ram[resolve_console_address(address) as usize] = val,
+            PPU_REGISTERS_START..=PPU_REGISTERS_END => {
+                self.ppu.write_register(resolve_ppu_register(address), val, &mut self.cartridge)
+            }
+            OAM_DMA => self.oam_dma.start(val),
+            IO_REGISTERS_START..=IO_REGISTERS_END => self.io_registers[(address - IO_REGISTERS_START) as usize] = val,
+            _ => self.cartridge.cpu_write(address, val),
+        }
+    }
+
+    fn write_u16(&mut self, address: u16, val: u16) {
+        self.write(address, val as u8);
+        self.write(address.wrapping_add(1), (val >> 8) as u8);
+    }
+
+    fn peek(&self, address: u16) -> u8 {
+        self.decode(address)
+    }
+
+    fn ppu_position(&self) -> Option<(u16, u16)> {
+        Some((self.ppu.scanline(), self.ppu.dot()))
+    }
+
+    fn nmi(&self) -> bool {
+        self.ppu.nmi()
+    }
+
+    fn irq(&self) -> bool {
+        self.cartridge.irq()
+    }
+
+    fn dma_pending(&self) -> bool {
+        self.oam_dma.active()
+    }
+
+    fn dma_cycle(&mut self, cycle: u64) {
+        match self.oam_dma.step(cycle) {
+            DmaAccess::Read(address) => {
+                let val = self.read(address);
+                self.oam_dma.latch(val);
+            }
+            DmaAccess::Write(val) => self.ppu.write_register(ppu::OAM_DATA, val, &mut self.cartridge),
+            DmaAccess::None => {}
+        }
+    }
+
+    fn tick(&mut self) {
+        self.cartridge.tick();
+
+        for _ in 0..PPU_DOTS_PER_CPU_CYCLE {
+            self.ppu.tick(&mut self.cartridge);
+        }
+    }
+}
+
+// The ppu and cartridge save their own state
+impl<C: Cartridge> SaveState for Bus<C> {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.bytes(&self.ram);
+        state.bytes(&self.io_registers);
+        state.u8(self.data_bus);
+        self.oam_dma.save_state(state);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), StateError> {
+        state.bytes_into(&mut self.ram, "ram")?;
+        state.bytes_into(&mut self.io_registers, "io registers")?;
+        self.data_bus = state.u8();
+
+        self.oam_dma.load_state(state)
+    }
+}
+
+impl<C: Cartridge> Debug for Bus<C> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Bus {{ ppu: {:?}, oam_dma: {:?}, cartridge: {:?} }}", self.ppu, self.oam_dma, self.cartridge)
+    }
+}
+
+impl<C: Cartridge + Default> Default for Bus<C> {
+    fn default() -> Self {
+        Bus::new(C::default())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use ::cpu::Cpu;
+    use ::cpu::CpuDebug;
+    use ::memory_map::NROMMemoryMap;
+
+    #[test]
+    pub fn test_ram_is_mirrored() {
+        let mut bus: Bus<NROMMemoryMap> = Bus::default();
+
+        bus.write(0x0000, 1);
+
+        // test we wrote to the right places
+        assert_eq!(bus.read(0x0000), 1);
+        assert_eq!(bus.read(0x0800), 1);
+        assert_eq!(bus.read(0x1000), 1);
+        assert_eq!(bus.read(0x1800), 1);
+
+        // test we stayed within our limits
+        assert_eq!(bus.read(0x2007), 0);
+    }
+
+    #[test]
+    pub fn test_ppu_registers_are_mirrored() {
+        let mut bus: Bus<NROMMemoryMap> = Bus::default();
+
+        // $2006 and $2007 by way of their mirrors
+        bus.write(0x3ffe, 0x24);
+        bus.write(0x200e, 0x00);
+        bus.write(0x200f, 1);
+
+        // test we wrote to the right places
+        bus.write(0x2006, 0x24);
+        bus.write(0x2006, 0x00);
+        bus.read(0x3fff);
+        assert_eq!(bus.read(0x2007), 1);
+    }
+
+    #[test]
+    pub fn test_ppu_runs_with_the_cpu() {
+        let mut bus: Bus<NROMMemoryMap> = Bus::default();
+        bus.write(0x2000, 0x80);
+
+        // vblank (and so the nmi) starts on the 82,183rd dot
+        for _ in 0..27_394 {
+            bus.tick();
+        }
+        assert!(!bus.nmi());
+
+        bus.tick();
+        assert!(bus.nmi());
+    }
+
+    #[test]
+    pub fn test_open_bus() {
+        let mut rom = ::rom::NesRom::default();
+        rom.prg_rom = vec![0; PRG_ROM_BANK_SIZE];
+
+        // lda $5000
+        rom.prg_rom[0..3].copy_from_slice(&[0xad, 0x00, 0x50]);
+
+        let mut cartridge = NROMMemoryMap::default();
+        cartridge.load(&rom);
+        let mut bus = Bus::new(cartridge);
+
+        // the fetches leave the operand's high byte on the bus
+        for address in 0x8000..0x8003 {
+            bus.read(address);
+        }
+        assert_eq!(bus.peek(0x5000), 0x50);
+        assert_eq!(bus.read(0x5000), 0x50);
+
+        // write only registers read back the bus too
+        bus.write(0x2000, 0x80);
+        assert_eq!(bus.read(0x2000), 0x80);
+        assert_eq!(bus.read(0x4000), 0x80);
+    }
+
+    #[test]
+    pub fn test_partially_driven_registers() {
+        let mut bus: Bus<NROMMemoryMap> = Bus::default();
+        bus.write(0x4016, 0b0000_0001);
+
+        bus.write(0x0000, 0b0101_1010);
+        assert_eq!(bus.read(0x4016), 0b0100_0001);
+
+        // $4015 doesn't go over the data bus, so it leaves it alone
+        bus.write(0x4015, 0b0000_0011);
+        bus.write(0x0000, 0b1111_0000);
+        assert_eq!(bus.read(0x4015), 0b0010_0011);
+        assert_eq!(bus.data_bus(), 0b1111_0000);
+    }
+
+    #[test]
+    pub fn test_cartridge_space() {
+        let mut bus: Bus<NROMMemoryMap> = Bus::default();
+
+        bus.write(0x6000, 0x42);
+        assert_eq!(bus.read(0x6000), 0x42);
+        assert_eq!(bus.cartridge().cpu_read(0x6000), Some(0x42));
+        assert_eq!(bus.cartridge().cpu_read(0x5000), None);
+    }
+
+    #[test]
+    pub fn test_oam_dma() {
+        let mut cpu = Cpu::new(Bus::<NROMMemoryMap>::default());
+        cpu.power_on();
+        cpu.step_instruction();
+
+        for i in 0..0x100 {
+            cpu.memory_map_mut().write(0x0200 + i, i as u8);
+        }
+
+        // the dma goes through $2004, so it starts at the oam address
+        cpu.exec_asm("lda #$02");
+        cpu.exec_asm("sta $2003");
+        cpu.exec_asm("sta $4014");
+        assert!(cpu.memory_map().dma_pending());
+
+        // the cpu is halted before the next instruction, and the odd number of
+        // cycles that takes means the second dma has to line up
+        let mut stalls = vec![];
+        for &source in ["nop", "sta $4014", "nop"].iter() {
+            let before = cpu.cycles();
+            cpu.exec_asm(source);
+            stalls.push(cpu.cycles() - before);
+        }
+        assert_eq!(stalls[0], 2 + 513);
+        assert_eq!(stalls[2], 2 + 514);
+
+        let oam = cpu.memory_map().ppu().oam();
+        assert_eq!(oam[2], 0x00);
+        assert_eq!(oam[3], 0x01);
+        assert_eq!(oam[0], 0xfe);
+        assert_eq!(oam[1], 0xff);
+
+        // attribute bytes are missing bits 2-4
+        assert_eq!(oam[0xfe], 0xe0);
+    }
+}
@@ -0,0 +1,145 @@
+use ::ppu;
+use ::rom;
+use ::state::SaveState;
+use ::state::StateError;
+use ::state::StateReader;
+use ::state::StateWriter;
+
+use std::fmt::Debug;
+
+// [Cartridge]
+// A cartridge sits on two buses at once:
+//
+// cpu bus $4020-$ffff => prg rom, prg ram, mapper registers
+// ppu bus $0000-$3eff => chr (pattern tables) at $0000-$1fff, nametables at
+//                        $2000-$3eff
+//
+// Nametables normally live in the console's own vram and the cartridge just
+// says how they're mirrored, so ppu_read and ppu_write only decode chr.
+// Cartridges with nametable ram of their own answer those through
+// nametable_read and nametable_write instead.
+//
+// Their save states hold bank registers and any ram (prg ram, chr ram), but
+// not the roms, which come back from the rom file.
+//
+// [Resources]
+// cartridge connector => http://wiki.nesdev.com/w/index.php/Cartridge_connector
+
+pub trait Cartridge: Debug + SaveState {
+    // None when nothing on the cartridge answers, which leaves whatever was
+    // last on the data bus (open bus)
+    fn cpu_read(&self, address: u16) -> Option<u8>;
+    fn cpu_write(&mut self, address: u16, val: u8);
+
+    fn ppu_read(&mut self, address: u16) -> u8;
+    fn ppu_write(&mut self, address: u16, val: u8);
+
+    // How the nametables are laid out right now; some mappers change it on
+    // the fly
+    fn mirroring(&self) -> ppu::MirroringType;
+
+    // Where a nametable address goes: normally the console's vram, laid out
+    // the way mirroring() says
+    fn nametable(&self, address: u16) -> ppu::Nametable {
+        ppu::resolve_nametable(address, self.mirroring())
+    }
+
+    // Reads and writes of the nametables nametable() kept for the cartridge
+    fn nametable_read(&mut self, _address: u16) -> u8 {
+        0
+    }
+
+    fn nametable_write(&mut self, _address: u16, _val: u8) {}
+
+    // Whether the cartridge is asserting the cpu's irq line
+    fn irq(&self) -> bool {
+        false
+    }
+
+    // Called at the end of every cpu cycle, for mappers that care about
+    // timing
+    fn tick(&mut self) {}
+
+    // Called by the ppu at the end of every scanline, for mappers that count
+    // them without watching the ppu bus
+    fn scanline(&mut self) {}
+
+    // The battery-backed prg ram, if there is any, for writing out to a save
+    fn save_ram(&self) -> Option<&[u8]> {
+        None
+    }
+
+    // Puts a save back into the battery-backed prg ram
+    fn load_save_ram(&mut self, _data: &[u8]) {}
+
+    fn load(&mut self, rom: &rom::NesRom);
+}
+
+impl SaveState for Box<dyn Cartridge> {
+    fn save_state(&self, state: &mut StateWriter) {
+        (**self).save_state(state)
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), StateError> {
+        (**self).load_state(state)
+    }
+}
+
+// Lets a bus hold whichever mapper create_mapper picked at runtime
+impl Cartridge for Box<dyn Cartridge> {
+    fn cpu_read(&self, address: u16) -> Option<u8> {
+        (**self).cpu_read(address)
+    }
+
+    fn cpu_write(&mut self, address: u16, val: u8) {
+        (**self).cpu_write(address, val)
+    }
+
+    fn ppu_read(&mut self, address: u16) -> u8 {
+        (**self).ppu_read(address)
+    }
+
+    fn ppu_write(&mut self, address: u16, val: u8) {
+        (**self).ppu_write(address, val)
+    }
+
+    fn mirroring(&self) -> ppu::MirroringType {
+        (**self).mirroring()
+    }
+
+    fn nametable(&self, address: u16) -> ppu::Nametable {
+        (**self).nametable(address)
+    }
+
+    fn nametable_read(&mut self, address: u16) -> u8 {
+        (**self).nametable_read(address)
+    }
+
+    fn nametable_write(&mut self, address: u16, val: u8) {
+        (**self).nametable_write(address, val)
+    }
+
+    fn irq(&self) -> bool {
+        (**self).irq()
+    }
+
+    fn tick(&mut self) {
+        (**self).tick()
+    }
+
+    fn scanline(&mut self) {
+        (**self).scanline()
+    }
+
+    fn save_ram(&self) -> Option<&[u8]> {
+        (**self).save_ram()
+    }
+
+    fn load_save_ram(&mut self, data: &[u8]) {
+        (**self).load_save_ram(data)
+    }
+
+    fn load(&mut self, rom: &rom::NesRom) {
+        (**self).load(rom)
+    }
+}
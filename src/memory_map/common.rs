@@ -1,18 +1,112 @@
-pub fn write_memory(memory: &mut Vec<u8>, starting_at: usize, len: usize, buf: &[u8]) {
-    for i in 0..len {
-        let address = starting_at + i;
-        let value = buf[i];
-
-        memory[address] = value;
-    }
-}
-
-pub fn resolve_mirrored_address(address: u16, start_address: u16, region_size: u16) -> u16 {
-    let mirror_address = start_address + (address % region_size);
-
-    mirror_address
-}
-
-pub fn crosses_page_boundary(in_page: u16, address: u16) -> bool {
-    (in_page & 0xff00) == (address & 0xff00)
-}
\ No newline at end of file
+use ::memory_map::constants::*;
+use ::ppu;
+use ::state::StateError;
+use ::state::StateReader;
+use ::state::StateWriter;
+
+pub fn write_memory(memory: &mut [u8], starting_at: usize, len: usize, buf: &[u8]) {
+    memory[starting_at..starting_at + len].copy_from_slice(&buf[..len]);
+}
+
+// Copies a save into prg ram, as much of it as fits
+pub fn load_save_ram(prg_ram: &mut [u8], data: &[u8]) {
+    let len = data.len().min(prg_ram.len());
+    write_memory(prg_ram, 0, len, data);
+}
+
+// The chr a cartridge starts with, and whether it's ram. Boards without chr rom
+// get the rom's chr ram, or 8 KiB of it if the rom doesn't say how much.
+pub fn chr_from_rom(rom: &::rom::NesRom) -> (Vec<u8>, bool) {
+    match (rom.chr_rom.is_empty(), rom.chr_ram.is_empty()) {
+        (false, _) => (rom.chr_rom.clone(), false),
+        (true, false) => (rom.chr_ram.clone(), true),
+        (true, true) => (vec![0; CHR_SIZE], true),
+    }
+}
+
+// chr ram goes in a save state, chr rom doesn't
+pub fn save_chr(state: &mut StateWriter, chr: &[u8], chr_is_ram: bool) {
+    match chr_is_ram {
+        true => state.bytes(chr),
+        false => state.bytes(&[]),
+    }
+}
+
+pub fn load_chr(state: &mut StateReader, chr: &mut [u8], chr_is_ram: bool) -> Result<(), StateError> {
+    match chr_is_ram {
+        true => state.bytes_into(chr, "chr ram"),
+        false => {
+            match state.bytes().len() {
+                0 => Ok(()),
+                _ => Err(StateError::Mismatch("chr ram")),
+            }
+        }
+    }
+}
+
+// Four screen boards bring 2 KiB of ram for the two nametables the console
+// doesn't have room for
+pub const FOUR_SCREEN_RAM_SIZE: usize = 0x0800;
+
+pub fn four_screen_ram(mirroring: ppu::MirroringType) -> Vec<u8> {
+    match mirroring {
+        ppu::MirroringType::FourScreen => vec![0; FOUR_SCREEN_RAM_SIZE],
+        _ => vec![],
+    }
+}
+
+// Where $2800-$2fff (mirrored up to $3eff) lands in four screen ram
+pub fn resolve_four_screen_address(address: u16) -> usize {
+    address as usize & (FOUR_SCREEN_RAM_SIZE - 1)
+}
+
+pub fn resolve_mirrored_address(address: u16, start_address: u16, region_size: u16) -> u16 {
+    start_address + (address % region_size)
+}
+
+// Where a cpu address below the cartridge space really lives: the 2 KiB of
+// ram repeats up to $1fff, and the 8 ppu registers repeat up to $3fff
+pub fn resolve_console_address(address: u16) -> u16 {
+    match address {
+        0x0000..=0x1fff => resolve_mirrored_address(address, 0x0000, 0x0800),
+        0x2000..=0x3fff => resolve_mirrored_address(address, 0x2000, 0x0008),
+        _ => address,
+    }
+}
+
+// On boards without anything stopping it, the rom keeps driving the data bus
+// while the cpu writes to it, and whichever one drives a bit low wins
+pub fn bus_conflict(val: u8, rom_val: u8) -> u8 {
+    val & rom_val
+}
+
+// NES 2.0 submapper 2 means the board has bus conflicts, 1 means it doesn't.
+// Plain iNES roms can't say, so we go with the friendlier no.
+pub fn has_bus_conflicts(rom: &::rom::NesRom) -> bool {
+    rom.submapper_number == 2
+}
+
+pub fn crosses_page_boundary(in_page: u16, address: u16) -> bool {
+    (in_page & 0xff00) != (address & 0xff00)
+}
+
+// A rom for tests, with every prg and chr bank numbered in all of its bytes so
+// it's easy to see which one is mapped in. No chr banks means chr ram.
+#[cfg(test)]
+pub fn numbered_rom(num_prg_banks: usize, prg_bank_size: usize, num_chr_banks: usize, chr_bank_size: usize) -> ::rom::NesRom {
+    let numbered = |num_banks: usize, bank_size: usize| (0..num_banks * bank_size).map(|i| (i / bank_size) as u8).collect();
+
+    let mut rom = ::rom::NesRom::default();
+    rom.prg_rom = numbered(num_prg_banks, prg_bank_size);
+    rom.chr_rom = numbered(num_chr_banks, chr_bank_size);
+
+    rom
+}
+
+#[cfg(test)]
+pub fn load_cartridge<C: ::memory_map::Cartridge + Default>(rom: &::rom::NesRom) -> C {
+    let mut cartridge = C::default();
+    cartridge.load(rom);
+
+    cartridge
+}
@@ -1,18 +1,37 @@
-pub const MEMORY_MAP_TOTAL_SIZE: usize = 0x010000;
-
-pub const PRG_ROM_BANK_SIZE: usize = 0x004000;
-pub const PRG_ROM_TOTAL_SIZE: usize = PRG_ROM_BANK_SIZE * 2;
-pub const PRG_ROM_START: u16 = 0x8000;
-pub const PRG_ROM_END: u16 = 0xffff;
-
-pub const SRAM_SIZE: usize = 0x002000;
-pub const EXPANSION_ROM_SIZE: usize = 0x001fe0;
-pub const IO_REGISTERS_HI_SIZE: usize = 0x000020;
-pub const IO_REGISTERS_LO_SIZE: usize = 0x000008;
-pub const RAM_SIZE: usize = 0x000600;
-
-pub const STACK_SIZE: usize = 0x000100;
-pub const STACK_START: u16 = 0x000100;
-pub const STACK_END: u16 = STACK_START + (STACK_SIZE as u16) - 1;
-
-pub const ZERO_PAGE_SIZE: usize = 0x000100;
\ No newline at end of file
+pub const MEMORY_MAP_TOTAL_SIZE: usize = 0x010000;
+
+pub const PRG_ROM_BANK_SIZE: usize = 0x004000;
+pub const PRG_ROM_TOTAL_SIZE: usize = PRG_ROM_BANK_SIZE * 2;
+pub const PRG_ROM_START: u16 = 0x8000;
+pub const PRG_ROM_END: u16 = 0xffff;
+
+pub const SRAM_SIZE: usize = 0x002000;
+
+pub const PRG_RAM_SIZE: usize = 0x002000;
+pub const PRG_RAM_START: u16 = 0x6000;
+pub const PRG_RAM_END: u16 = 0x7fff;
+pub const TRAINER_START: u16 = 0x7000;
+
+pub const CHR_SIZE: usize = 0x002000;
+pub const EXPANSION_ROM_SIZE: usize = 0x001fe0;
+pub const IO_REGISTERS_HI_SIZE: usize = 0x000020;
+pub const IO_REGISTERS_LO_SIZE: usize = 0x000008;
+pub const RAM_SIZE: usize = 0x000600;
+
+pub const CONSOLE_RAM_SIZE: usize = 0x000800;
+pub const RAM_START: u16 = 0x0000;
+pub const RAM_END: u16 = 0x1fff;
+pub const PPU_REGISTERS_START: u16 = 0x2000;
+pub const PPU_REGISTERS_END: u16 = 0x3fff;
+pub const IO_REGISTERS_START: u16 = 0x4000;
+pub const IO_REGISTERS_END: u16 = 0x401f;
+
+pub const STACK_SIZE: usize = 0x000100;
+pub const STACK_START: u16 = 0x000100;
+pub const STACK_END: u16 = STACK_START + (STACK_SIZE as u16) - 1;
+
+pub const ZERO_PAGE_SIZE: usize = 0x000100;
+
+pub const NMI_VECTOR: u16 = 0xfffa;
+pub const RESET_VECTOR: u16 = 0xfffc;
+pub const IRQ_VECTOR: u16 = 0xfffe;
@@ -0,0 +1,194 @@
+use ::state::SaveState;
+use ::state::StateError;
+use ::state::StateReader;
+use ::state::StateWriter;
+
+use std::fmt;
+use std::fmt::Debug;
+
+// [OAM DMA]
+// Writing $XX to $4014 copies $XX00-$XXff into oam, through $2004 (so it
+// starts at whatever oam address is set). The cpu is halted while that
+// happens:
+//
+// 1 cycle    => waiting for the cpu to halt
+// 0-1 cycles => lining up, since reads only happen on get (even) cycles
+// 512 cycles => 256 reads on get cycles, each written on the put cycle after
+//
+// which makes 513 or 514 cycles, depending on when $4014 was written. The cpu
+// only halts on a read, and the write to $4014 is always the last cycle of
+// its instruction, so the halt lands on the next opcode fetch.
+//
+// [DMC DMA]
+// The apu's dmc fetches its samples the same way. Once there's an apu, its
+// fetches take get cycles from in between ours, and each one holds the oam
+// dma up by a get/put pair.
+//
+// [Resources]
+// DMA => http://wiki.nesdev.com/w/index.php/DMA
+// $4014 => http://wiki.nesdev.com/w/index.php/PPU_registers#OAMDMA
+
+pub const OAM_DMA: u16 = 0x4014;
+
+const OAM_DMA_LENGTH: u16 = 0x100;
+
+// What the dma does with the bus on a cycle
+#[derive(Debug, PartialEq)]
+pub enum DmaAccess {
+    None,
+    Read(u16),
+    Write(u8),
+}
+
+pub struct OamDma {
+    // The page being copied, while there's a dma going
+    page: Option<u8>,
+    halted: bool,
+
+    // How many bytes have been copied, and the one on its way
+    copied: u16,
+    latch: Option<u8>,
+}
+
+impl OamDma {
+    pub fn new() -> Self {
+        OamDma {
+            page: None,
+            halted: false,
+            copied: 0,
+            latch: None,
+        }
+    }
+
+    pub fn start(&mut self, page: u8) {
+        *self = OamDma {
+            page: Some(page),
+            ..OamDma::new()
+        };
+    }
+
+    pub fn active(&self) -> bool {
+        self.page.is_some()
+    }
+
+    // Moves the dma on by a cycle, given the cpu's cycle count to tell get
+    // cycles from put ones
+    pub fn step(&mut self, cycle: u64) -> DmaAccess {
+        let page = match self.page {
+            Some(page) => page,
+            None => return DmaAccess::None,
+        };
+
+        if !self.halted {
+            self.halted = true;
+            return DmaAccess::None;
+        }
+
+        match (cycle & 1 == 0, self.latch.take()) {
+            (true, None) => DmaAccess::Read(((page as u16) << 8) | self.copied),
+            (false, Some(val)) => {
+                self.copied += 1;
+                if self.copied == OAM_DMA_LENGTH {
+                    self.page = None;
+                }
+
+                DmaAccess::Write(val)
+            }
+
+            // lining up
+            (_, latch) => {
+                self.latch = latch;
+                DmaAccess::None
+            }
+        }
+    }
+
+    // Hands over the byte from the last DmaAccess::Read
+    pub fn latch(&mut self, val: u8) {
+        self.latch = Some(val);
+    }
+}
+
+impl SaveState for OamDma {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.bool(self.page.is_some());
+        state.u8(self.page.unwrap_or(0));
+        state.bool(self.halted);
+        state.u16(self.copied);
+        state.bool(self.latch.is_some());
+        state.u8(self.latch.unwrap_or(0));
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), StateError> {
+        let active = state.bool();
+        let page = state.u8();
+        self.page = match active {
+            true => Some(page),
+            false => None,
+        };
+        self.halted = state.bool();
+        self.copied = state.u16();
+
+        let latched = state.bool();
+        let latch = state.u8();
+        self.latch = match latched {
+            true => Some(latch),
+            false => None,
+        };
+
+        Ok(())
+    }
+}
+
+impl Debug for OamDma {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "OamDma {{ page: {:?}, copied: {} }}", self.page, self.copied)
+    }
+}
+
+impl Default for OamDma {
+    fn default() -> Self {
+        OamDma::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // Runs a dma from the given cycle, handing back page + offset for every
+    // read, and returns the cycles it took and what it wrote
+    fn run(start: u64) -> (u64, Vec<u8>) {
+        let mut dma = OamDma::new();
+        dma.start(0x02);
+
+        let mut cycle = start;
+        let mut written = vec![];
+        while dma.active() {
+            match dma.step(cycle) {
+                DmaAccess::Read(address) => {
+                    assert_eq!(cycle % 2, 0);
+                    dma.latch(address as u8)
+                }
+                DmaAccess::Write(val) => written.push(val),
+                DmaAccess::None => {}
+            }
+            cycle += 1;
+        }
+
+        (cycle - start, written)
+    }
+
+    #[test]
+    pub fn test_copies_a_page() {
+        let (_, written) = run(0);
+
+        assert_eq!(written, (0..=0xff).collect::<Vec<u8>>());
+    }
+
+    #[test]
+    pub fn test_takes_513_or_514_cycles() {
+        assert_eq!(run(1).0, 513);
+        assert_eq!(run(2).0, 514);
+    }
+}
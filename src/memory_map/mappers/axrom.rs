@@ -0,0 +1,153 @@
+use ::memory_map::constants::*;
+use ::memory_map::common::*;
+use ::memory_map::Cartridge;
+use ::ppu;
+use ::rom;
+use ::state::SaveState;
+use ::state::StateError;
+use ::state::StateReader;
+use ::state::StateWriter;
+
+use std::fmt;
+use std::fmt::Debug;
+
+// [AxROM]
+// Mapper 7. Writing anywhere in $8000-$ffff picks a 32 KiB prg bank (bits
+// 0-2) and which nametable every screen shows (bit 4). chr is 8 KiB of ram.
+//
+// [Resources]
+// AxROM => http://wiki.nesdev.com/w/index.php/AxROM
+// bus conflicts => http://wiki.nesdev.com/w/index.php/Bus_conflict
+
+const PRG_BANK_SIZE: usize = 0x8000;
+
+pub struct AxROMMemoryMap {
+    prg_rom: Vec<u8>,
+
+    chr: Vec<u8>,
+    chr_is_ram: bool,
+
+    bank_select: u8,
+    bus_conflicts: bool,
+}
+
+impl AxROMMemoryMap {
+    pub fn set_bus_conflicts(&mut self, bus_conflicts: bool) {
+        self.bus_conflicts = bus_conflicts;
+    }
+
+    fn resolve_prg_rom_address(&self, address: u16) -> usize {
+        let bank = (self.bank_select & 0b111) as usize;
+
+        (bank * PRG_BANK_SIZE + (address - PRG_ROM_START) as usize) % self.prg_rom.len()
+    }
+}
+
+impl Cartridge for AxROMMemoryMap {
+    fn cpu_read(&self, address: u16) -> Option<u8> {
+        match address {
+            PRG_ROM_START..=PRG_ROM_END => Some(self.prg_rom[self.resolve_prg_rom_address(address)]),
+            _ => None,
+        }
+    }
+
+    fn cpu_write(&mut self, address: u16, val: u8) {
+        if (PRG_ROM_START..=PRG_ROM_END).contains(&address) {
+            self.bank_select = match self.bus_conflicts {
+                true => bus_conflict(val, self.prg_rom[self.resolve_prg_rom_address(address)]),
+                false => val,
+            }
+        }
+    }
+
+    fn ppu_read(&mut self, address: u16) -> u8 {
+        self.chr[address as usize % self.chr.len()]
+    }
+
+    fn ppu_write(&mut self, address: u16, val: u8) {
+        if self.chr_is_ram {
+            let len = self.chr.len();
+            self.chr[address as usize % len] = val;
+        }
+    }
+
+    fn mirroring(&self) -> ppu::MirroringType {
+        match self.bank_select & 0b1_0000 {
+            0 => ppu::MirroringType::SingleScreenLower,
+            _ => ppu::MirroringType::SingleScreenUpper,
+        }
+    }
+
+    fn load(&mut self, rom: &rom::NesRom) {
+        self.prg_rom = rom.prg_rom.clone();
+
+        let (chr, chr_is_ram) = chr_from_rom(rom);
+        self.chr = chr;
+        self.chr_is_ram = chr_is_ram;
+
+        self.bus_conflicts = has_bus_conflicts(rom);
+    }
+}
+
+impl SaveState for AxROMMemoryMap {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.u8(self.bank_select);
+        save_chr(state, &self.chr, self.chr_is_ram);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), StateError> {
+        self.bank_select = state.u8();
+        load_chr(state, &mut self.chr, self.chr_is_ram)
+    }
+}
+
+impl Debug for AxROMMemoryMap {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "AxROM {{ bank_select: {:#x} }}", self.bank_select)
+    }
+}
+
+impl Default for AxROMMemoryMap {
+    fn default() -> Self {
+        AxROMMemoryMap {
+            prg_rom: vec![0; PRG_BANK_SIZE],
+            chr: vec![0; CHR_SIZE],
+            chr_is_ram: true,
+            bank_select: 0,
+            bus_conflicts: false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // 32 KiB prg banks and chr ram
+    fn new_map(num_prg_banks: usize) -> AxROMMemoryMap {
+        load_cartridge(&numbered_rom(num_prg_banks, PRG_BANK_SIZE, 0, CHR_SIZE))
+    }
+
+    #[test]
+    pub fn test_switches_prg_bank_and_screen() {
+        let mut map = new_map(8);
+        assert_eq!(map.cpu_read(0x8000), Some(0));
+        assert_eq!(map.mirroring(), ppu::MirroringType::SingleScreenLower);
+
+        map.cpu_write(0x8000, 0b1_0101);
+        assert_eq!(map.cpu_read(0x8000), Some(5));
+        assert_eq!(map.cpu_read(0xffff), Some(5));
+        assert_eq!(map.mirroring(), ppu::MirroringType::SingleScreenUpper);
+    }
+
+    #[test]
+    pub fn test_bus_conflicts() {
+        let mut map = new_map(8);
+        map.set_bus_conflicts(true);
+
+        // bank 0 is all zeros, so nothing gets through
+        map.cpu_write(0x8000, 0b1_0101);
+        assert_eq!(map.cpu_read(0x8000), Some(0));
+        assert_eq!(map.mirroring(), ppu::MirroringType::SingleScreenLower);
+    }
+}
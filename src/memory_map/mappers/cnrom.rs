@@ -0,0 +1,172 @@
+use ::memory_map::constants::*;
+use ::memory_map::common::*;
+use ::memory_map::Cartridge;
+use ::ppu;
+use ::rom;
+use ::state::SaveState;
+use ::state::StateError;
+use ::state::StateReader;
+use ::state::StateWriter;
+
+use std::fmt;
+use std::fmt::Debug;
+
+// [CNROM]
+// Mapper 3. prg rom is laid out like NROM (16 or 32 KiB), and writing
+// anywhere in $8000-$ffff picks the 8 KiB chr rom bank.
+//
+// [Resources]
+// CNROM => http://wiki.nesdev.com/w/index.php/CNROM
+// bus conflicts => http://wiki.nesdev.com/w/index.php/Bus_conflict
+
+const PRG_BANK_SIZE: usize = 0x4000;
+
+pub struct CNROMMemoryMap {
+    prg_rom: Vec<u8>,
+
+    chr: Vec<u8>,
+    chr_is_ram: bool,
+
+    mirroring_type: ppu::MirroringType,
+    four_screen_ram: Vec<u8>,
+
+    chr_bank: u8,
+    bus_conflicts: bool,
+}
+
+impl CNROMMemoryMap {
+    pub fn set_bus_conflicts(&mut self, bus_conflicts: bool) {
+        self.bus_conflicts = bus_conflicts;
+    }
+
+    fn resolve_prg_rom_address(&self, address: u16) -> usize {
+        (address - PRG_ROM_START) as usize % self.prg_rom.len()
+    }
+
+    fn resolve_chr_address(&self, address: u16) -> usize {
+        (self.chr_bank as usize * CHR_SIZE + (address as usize & (CHR_SIZE - 1))) % self.chr.len()
+    }
+}
+
+impl Cartridge for CNROMMemoryMap {
+    fn cpu_read(&self, address: u16) -> Option<u8> {
+        match address {
+            PRG_ROM_START..=PRG_ROM_END => Some(self.prg_rom[self.resolve_prg_rom_address(address)]),
+            _ => None,
+        }
+    }
+
+    fn cpu_write(&mut self, address: u16, val: u8) {
+        if (PRG_ROM_START..=PRG_ROM_END).contains(&address) {
+            self.chr_bank = match self.bus_conflicts {
+                true => bus_conflict(val, self.prg_rom[self.resolve_prg_rom_address(address)]),
+                false => val,
+            }
+        }
+    }
+
+    fn ppu_read(&mut self, address: u16) -> u8 {
+        self.chr[self.resolve_chr_address(address)]
+    }
+
+    fn ppu_write(&mut self, address: u16, val: u8) {
+        if self.chr_is_ram {
+            let address = self.resolve_chr_address(address);
+            self.chr[address] = val;
+        }
+    }
+
+    fn mirroring(&self) -> ppu::MirroringType {
+        self.mirroring_type
+    }
+
+    fn nametable_read(&mut self, address: u16) -> u8 {
+        self.four_screen_ram[resolve_four_screen_address(address)]
+    }
+
+    fn nametable_write(&mut self, address: u16, val: u8) {
+        self.four_screen_ram[resolve_four_screen_address(address)] = val;
+    }
+
+    fn load(&mut self, rom: &rom::NesRom) {
+        self.prg_rom = rom.prg_rom.clone();
+
+        let (chr, chr_is_ram) = chr_from_rom(rom);
+        self.chr = chr;
+        self.chr_is_ram = chr_is_ram;
+
+        self.mirroring_type = rom.mirroring_type;
+        self.four_screen_ram = four_screen_ram(rom.mirroring_type);
+        self.bus_conflicts = has_bus_conflicts(rom);
+    }
+}
+
+impl SaveState for CNROMMemoryMap {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.u8(self.chr_bank);
+        save_chr(state, &self.chr, self.chr_is_ram);
+        state.bytes(&self.four_screen_ram);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), StateError> {
+        self.chr_bank = state.u8();
+        load_chr(state, &mut self.chr, self.chr_is_ram)?;
+        state.bytes_into(&mut self.four_screen_ram, "four screen ram")
+    }
+}
+
+impl Debug for CNROMMemoryMap {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "CNROM {{ chr_bank: {:#x} }}", self.chr_bank)
+    }
+}
+
+impl Default for CNROMMemoryMap {
+    fn default() -> Self {
+        CNROMMemoryMap {
+            prg_rom: vec![0; PRG_BANK_SIZE * 2],
+            chr: vec![0; CHR_SIZE],
+            chr_is_ram: true,
+            mirroring_type: ppu::MirroringType::default(),
+            four_screen_ram: vec![],
+            chr_bank: 0,
+            bus_conflicts: false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // The given prg rom and 8 KiB chr banks
+    fn new_map(prg_rom: Vec<u8>, num_chr_banks: usize) -> CNROMMemoryMap {
+        let mut rom = numbered_rom(0, PRG_BANK_SIZE, num_chr_banks, CHR_SIZE);
+        rom.prg_rom = prg_rom;
+
+        load_cartridge(&rom)
+    }
+
+    #[test]
+    pub fn test_switches_chr_bank() {
+        let mut map = new_map(vec![0; PRG_BANK_SIZE], 4);
+        assert_eq!(map.ppu_read(0x0000), 0);
+
+        map.cpu_write(0x8000, 3);
+        assert_eq!(map.ppu_read(0x0000), 3);
+        assert_eq!(map.ppu_read(0x1fff), 3);
+
+        // chr rom stays rom
+        map.ppu_write(0x0000, 0xff);
+        assert_eq!(map.ppu_read(0x0000), 3);
+    }
+
+    #[test]
+    pub fn test_bus_conflicts() {
+        let mut map = new_map(vec![0b01; PRG_BANK_SIZE], 4);
+        map.set_bus_conflicts(true);
+
+        map.cpu_write(0x8000, 3);
+        assert_eq!(map.ppu_read(0x0000), 1);
+    }
+}
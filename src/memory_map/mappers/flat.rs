@@ -0,0 +1,48 @@
+use ::memory_map::constants::*;
+use ::memory_map::MemoryMapper;
+
+use std::fmt;
+use std::fmt::Debug;
+
+// [Flat]
+// 64 KiB of plain ram with nothing mirrored or banked. Not a real console:
+// it's for tests that want to put code and vectors wherever they like.
+
+pub struct FlatMemoryMap {
+    memory: Vec<u8>,
+}
+
+impl MemoryMapper for FlatMemoryMap {
+    fn read(&mut self, address: u16) -> u8 {
+        self.peek(address)
+    }
+
+    fn read_u16(&mut self, address: u16) -> u16 {
+        (self.read(address.wrapping_add(1)) as u16) << 8 | self.read(address) as u16
+    }
+
+    fn write(&mut self, address: u16, val: u8) {
+        self.memory[address as usize] = val;
+    }
+
+    fn write_u16(&mut self, address: u16, val: u16) {
+        self.write(address, val as u8);
+        self.write(address.wrapping_add(1), (val >> 8) as u8);
+    }
+
+    fn peek(&self, address: u16) -> u8 {
+        self.memory[address as usize]
+    }
+}
+
+impl Debug for FlatMemoryMap {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "[...]")
+    }
+}
+
+impl Default for FlatMemoryMap {
+    fn default() -> Self {
+        FlatMemoryMap { memory: vec![0; MEMORY_MAP_TOTAL_SIZE] }
+    }
+}
@@ -0,0 +1,448 @@
+use ::memory_map::constants::*;
+use ::memory_map::common::*;
+use ::memory_map::Cartridge;
+use ::ppu;
+use ::rom;
+use ::state::SaveState;
+use ::state::StateError;
+use ::state::StateReader;
+use ::state::StateWriter;
+
+use std::fmt;
+use std::fmt::Debug;
+
+// [MMC1]
+// Mapper 1. The cpu talks to it a bit at a time: every write to $8000-$ffff
+// shifts bit 0 into a 5 bit shift register, and the fifth write copies the
+// result into one of four registers picked by bits 13-14 of that write's
+// address:
+//
+// $8000-$9fff => control (mirroring, prg bank mode, chr bank mode)
+// $a000-$bfff => chr bank 0
+// $c000-$dfff => chr bank 1
+// $e000-$ffff => prg bank (and prg ram disable)
+//
+// Writing a value with bit 7 set resets the shift register and goes back to
+// prg mode 3. The MMC1 also ignores a write that lands on the cycle right
+// after another one, which is why rmw instructions (that write twice in a
+// row) only get their first value in.
+//
+// prg bank modes:
+//
+// 0, 1 => one 32 KiB bank at $8000 (the low bit of the bank is ignored)
+// 2    => first bank fixed at $8000, switchable 16 KiB bank at $c000
+// 3    => switchable 16 KiB bank at $8000, last bank fixed at $c000
+//
+// chr is either one 8 KiB bank (chr bank 0 with its low bit ignored) or two
+// 4 KiB banks.
+//
+// [Board variants]
+// SUROM (and SXROM) carts have 512 KiB of prg rom, more than the 4 bit prg
+// bank can reach, so bit 4 of the chr bank register picks which 256 KiB half
+// both prg windows look into. SOROM and SXROM have 16 and 32 KiB of prg ram
+// and use bits 3 and 2-3 of it to pick an 8 KiB ram bank. These boards always
+// have 8 KiB of chr ram, so the chr bits they borrow don't matter. We use chr
+// bank 0 for all of this, since games keep both chr registers the same on
+// these boards.
+//
+// [Resources]
+// MMC1 => http://wiki.nesdev.com/w/index.php/MMC1
+
+const PRG_BANK_SIZE: usize = 0x4000;
+const CHR_BANK_SIZE: usize = 0x1000;
+const PRG_RAM_BANK_SIZE: usize = 0x2000;
+
+// 16 banks of 16 KiB, as far as the prg bank register reaches
+const PRG_OUTER_BANK_SIZE: usize = 0x40000;
+
+const SHIFT_REGISTER_EMPTY: u8 = 0b1_0000;
+
+pub struct MMC1MemoryMap {
+    prg_rom: Vec<u8>,
+    prg_ram: Vec<u8>,
+
+    chr: Vec<u8>,
+    chr_is_ram: bool,
+
+    // Starts as 0b10000; the 1 reaching bit 0 means the fifth write is in
+    shift_register: u8,
+
+    control: u8,
+    chr_bank_0: u8,
+    chr_bank_1: u8,
+    prg_bank: u8,
+
+    // Whether there was a write this cycle / last cycle
+    wrote_this_cycle: bool,
+    wrote_last_cycle: bool,
+
+    has_battery_backed_ram: bool,
+}
+
+impl MMC1MemoryMap {
+    fn write_register(&mut self, address: u16, val: u8) {
+        if val & 0b1000_0000 != 0 {
+            self.shift_register = SHIFT_REGISTER_EMPTY;
+            self.control |= 0b0_1100;
+
+            return;
+        }
+
+        let full = self.shift_register & 1 == 1;
+        self.shift_register = (self.shift_register >> 1) | ((val & 1) << 4);
+
+        if !full {
+            return;
+        }
+
+        let value = self.shift_register;
+        self.shift_register = SHIFT_REGISTER_EMPTY;
+
+        match address {
+            0x8000..=0x9fff => self.control = value,
+            0xa000..=0xbfff => self.chr_bank_0 = value,
+            0xc000..=0xdfff => self.chr_bank_1 = value,
+            _ => self.prg_bank = value,
+        }
+    }
+
+    fn prg_bank_mode(&self) -> u8 {
+        (self.control >> 2) & 0b11
+    }
+
+    fn resolve_prg_rom_address(&self, address: u16) -> usize {
+        let bank = (self.prg_bank & 0x0f) as usize;
+        let upper = address >= 0xc000;
+
+        let bank = match (self.prg_bank_mode(), upper) {
+            (0, _) | (1, _) => (bank & !1) | upper as usize,
+            (2, false) => 0,
+            (2, true) => bank,
+            (_, false) => bank,
+            (_, true) => 0x0f,
+        };
+
+        let outer = match self.prg_rom.len() > PRG_OUTER_BANK_SIZE {
+            true => ((self.chr_bank_0 >> 4) & 1) as usize * PRG_OUTER_BANK_SIZE,
+            false => 0,
+        };
+
+        (outer + bank * PRG_BANK_SIZE + (address as usize & (PRG_BANK_SIZE - 1))) % self.prg_rom.len()
+    }
+
+    fn resolve_prg_ram_address(&self, address: u16) -> usize {
+        let bank = match self.prg_ram.len() / PRG_RAM_BANK_SIZE {
+            4 => (self.chr_bank_0 >> 2) & 0b11,
+            2 => (self.chr_bank_0 >> 3) & 1,
+            _ => 0,
+        };
+
+        (bank as usize * PRG_RAM_BANK_SIZE + (address - PRG_RAM_START) as usize) % self.prg_ram.len()
+    }
+
+    fn prg_ram_enabled(&self) -> bool {
+        self.prg_bank & 0b1_0000 == 0
+    }
+
+    fn resolve_chr_address(&self, address: u16) -> usize {
+        let upper = address & 0x1000 != 0;
+
+        let bank = match (self.control & 0b1_0000 != 0, upper) {
+            (false, _) => (self.chr_bank_0 & !1) | upper as u8,
+            (true, false) => self.chr_bank_0,
+            (true, true) => self.chr_bank_1,
+        };
+
+        (bank as usize * CHR_BANK_SIZE + (address as usize & (CHR_BANK_SIZE - 1))) % self.chr.len()
+    }
+}
+
+impl Cartridge for MMC1MemoryMap {
+    fn cpu_read(&self, address: u16) -> Option<u8> {
+        match address {
+            PRG_ROM_START..=PRG_ROM_END => Some(self.prg_rom[self.resolve_prg_rom_address(address)]),
+            PRG_RAM_START..=PRG_RAM_END => {
+                match self.prg_ram_enabled() {
+                    true => Some(self.prg_ram[self.resolve_prg_ram_address(address)]),
+                    false => None,
+                }
+            }
+            _ => None,
+        }
+    }
+
+    fn cpu_write(&mut self, address: u16, val: u8) {
+        match address {
+            PRG_ROM_START..=PRG_ROM_END => {
+                let ignored = self.wrote_last_cycle;
+                self.wrote_this_cycle = true;
+
+                if !ignored {
+                    self.write_register(address, val);
+                }
+            }
+            PRG_RAM_START..=PRG_RAM_END if self.prg_ram_enabled() => {
+                let address = self.resolve_prg_ram_address(address);
+                self.prg_ram[address] = val;
+            }
+            _ => {}
+        }
+    }
+
+    fn ppu_read(&mut self, address: u16) -> u8 {
+        self.chr[self.resolve_chr_address(address)]
+    }
+
+    fn ppu_write(&mut self, address: u16, val: u8) {
+        if self.chr_is_ram {
+            let address = self.resolve_chr_address(address);
+            self.chr[address] = val;
+        }
+    }
+
+    fn mirroring(&self) -> ppu::MirroringType {
+        match self.control & 0b11 {
+            0 => ppu::MirroringType::SingleScreenLower,
+            1 => ppu::MirroringType::SingleScreenUpper,
+            2 => ppu::MirroringType::Vertical,
+            _ => ppu::MirroringType::Horizontal,
+        }
+    }
+
+    fn tick(&mut self) {
+        self.wrote_last_cycle = self.wrote_this_cycle;
+        self.wrote_this_cycle = false;
+    }
+
+    fn save_ram(&self) -> Option<&[u8]> {
+        match self.has_battery_backed_ram {
+            true => Some(&self.prg_ram),
+            false => None,
+        }
+    }
+
+    fn load_save_ram(&mut self, data: &[u8]) {
+        load_save_ram(&mut self.prg_ram, data);
+    }
+
+    fn load(&mut self, rom: &rom::NesRom) {
+        self.prg_rom = rom.prg_rom.clone();
+
+        let (chr, chr_is_ram) = chr_from_rom(rom);
+        self.chr = chr;
+        self.chr_is_ram = chr_is_ram;
+
+        let prg_ram_size = rom.prg_ram_size + rom.prg_nvram_size;
+        self.prg_ram = vec![0; prg_ram_size.max(PRG_RAM_BANK_SIZE)];
+        self.has_battery_backed_ram = rom.has_battery_backed_ram();
+
+        if let Some(ref trainer) = rom.trainer {
+            let start = (TRAINER_START - PRG_RAM_START) as usize;
+            self.prg_ram[start..start + trainer.len()].copy_from_slice(trainer);
+        }
+    }
+}
+
+impl SaveState for MMC1MemoryMap {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.u8(self.shift_register);
+        state.u8(self.control);
+        state.u8(self.chr_bank_0);
+        state.u8(self.chr_bank_1);
+        state.u8(self.prg_bank);
+        state.bool(self.wrote_this_cycle);
+        state.bool(self.wrote_last_cycle);
+        state.bytes(&self.prg_ram);
+        save_chr(state, &self.chr, self.chr_is_ram);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), StateError> {
+        self.shift_register = state.u8();
+        self.control = state.u8();
+        self.chr_bank_0 = state.u8();
+        self.chr_bank_1 = state.u8();
+        self.prg_bank = state.u8();
+        self.wrote_this_cycle = state.bool();
+        self.wrote_last_cycle = state.bool();
+        state.bytes_into(&mut self.prg_ram, "prg ram")?;
+        load_chr(state, &mut self.chr, self.chr_is_ram)
+    }
+}
+
+impl Debug for MMC1MemoryMap {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f,
+               "MMC1 {{ control: {:#07b}, chr_bank_0: {:#x}, chr_bank_1: {:#x}, prg_bank: {:#x} }}",
+               self.control,
+               self.chr_bank_0,
+               self.chr_bank_1,
+               self.prg_bank)
+    }
+}
+
+impl Default for MMC1MemoryMap {
+    fn default() -> Self {
+        MMC1MemoryMap {
+            prg_rom: vec![0; PRG_BANK_SIZE * 2],
+            prg_ram: vec![0; PRG_RAM_BANK_SIZE],
+            chr: vec![0; CHR_SIZE],
+            chr_is_ram: true,
+            shift_register: SHIFT_REGISTER_EMPTY,
+
+            // starts out in prg mode 3, so the reset vector is in the last bank
+            control: 0b0_1100,
+            chr_bank_0: 0,
+            chr_bank_1: 0,
+            prg_bank: 0,
+            wrote_this_cycle: false,
+            wrote_last_cycle: false,
+            has_battery_backed_ram: false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // 16 KiB prg banks and 4 KiB chr banks
+    fn new_map(num_prg_banks: usize, num_chr_banks: usize, prg_ram_size: usize) -> MMC1MemoryMap {
+        let mut rom = numbered_rom(num_prg_banks, PRG_BANK_SIZE, num_chr_banks, CHR_BANK_SIZE);
+        rom.prg_ram_size = prg_ram_size;
+
+        load_cartridge(&rom)
+    }
+
+    // Shifts a value in a bit at a time, a cycle apart like sta would
+    fn write_register(map: &mut MMC1MemoryMap, address: u16, val: u8) {
+        for bit in 0..5 {
+            map.cpu_write(address, (val >> bit) & 1);
+            map.tick();
+            map.tick();
+        }
+    }
+
+    #[test]
+    pub fn test_powers_on_with_last_bank_fixed() {
+        let map = new_map(8, 0, 0);
+
+        assert_eq!(map.cpu_read(0x8000), Some(0));
+        assert_eq!(map.cpu_read(0xc000), Some(7));
+    }
+
+    #[test]
+    pub fn test_prg_bank_modes() {
+        let mut map = new_map(8, 0, 0);
+
+        // mode 3: switch $8000
+        write_register(&mut map, 0xe000, 5);
+        assert_eq!(map.cpu_read(0x8000), Some(5));
+        assert_eq!(map.cpu_read(0xc000), Some(7));
+
+        // mode 2: switch $c000
+        write_register(&mut map, 0x8000, 0b0_1000);
+        assert_eq!(map.cpu_read(0x8000), Some(0));
+        assert_eq!(map.cpu_read(0xc000), Some(5));
+
+        // mode 0: 32 KiB, low bit ignored
+        write_register(&mut map, 0x8000, 0b0_0000);
+        assert_eq!(map.cpu_read(0x8000), Some(4));
+        assert_eq!(map.cpu_read(0xc000), Some(5));
+    }
+
+    #[test]
+    pub fn test_chr_bank_modes() {
+        let mut map = new_map(2, 8, 0);
+
+        // 8 KiB mode ignores the low bit and chr bank 1
+        write_register(&mut map, 0xa000, 3);
+        write_register(&mut map, 0xc000, 6);
+        assert_eq!(map.ppu_read(0x0000), 2);
+        assert_eq!(map.ppu_read(0x1000), 3);
+
+        // 4 KiB mode
+        write_register(&mut map, 0x8000, 0b1_1100);
+        assert_eq!(map.ppu_read(0x0000), 3);
+        assert_eq!(map.ppu_read(0x1000), 6);
+    }
+
+    #[test]
+    pub fn test_mirroring_control() {
+        let mut map = new_map(2, 0, 0);
+
+        write_register(&mut map, 0x8000, 0b0_1100);
+        assert_eq!(map.mirroring(), ppu::MirroringType::SingleScreenLower);
+
+        write_register(&mut map, 0x8000, 0b0_1110);
+        assert_eq!(map.mirroring(), ppu::MirroringType::Vertical);
+
+        write_register(&mut map, 0x8000, 0b0_1111);
+        assert_eq!(map.mirroring(), ppu::MirroringType::Horizontal);
+    }
+
+    #[test]
+    pub fn test_reset_and_consecutive_writes() {
+        let mut map = new_map(8, 0, 0);
+        write_register(&mut map, 0x8000, 0b0_1000);
+
+        // half a value, then a reset throws it away and goes back to mode 3
+        map.cpu_write(0xe000, 1);
+        map.tick();
+        map.tick();
+        map.cpu_write(0xe000, 0x80);
+        map.tick();
+        map.tick();
+        assert_eq!(map.cpu_read(0xc000), Some(7));
+
+        // the second write of an rmw pair lands on the very next cycle and
+        // doesn't count, so this is 0b00001 rather than 0b00011
+        map.cpu_write(0xe000, 1);
+        map.tick();
+        map.cpu_write(0xe000, 1);
+        map.tick();
+        map.tick();
+
+        for _ in 0..4 {
+            map.cpu_write(0xe000, 0);
+            map.tick();
+            map.tick();
+        }
+
+        assert_eq!(map.cpu_read(0x8000), Some(1));
+    }
+
+    #[test]
+    pub fn test_surom_outer_bank() {
+        let mut map = new_map(32, 0, 0);
+
+        // the last bank of the first 256 KiB, until chr bank 0 picks the second
+        assert_eq!(map.cpu_read(0xc000), Some(15));
+
+        write_register(&mut map, 0xa000, 0b1_0000);
+        assert_eq!(map.cpu_read(0xc000), Some(31));
+
+        write_register(&mut map, 0xe000, 2);
+        assert_eq!(map.cpu_read(0x8000), Some(18));
+    }
+
+    #[test]
+    pub fn test_sorom_prg_ram_banks_and_disable() {
+        let mut map = new_map(2, 0, 2 * PRG_RAM_BANK_SIZE);
+
+        map.cpu_write(0x6000, 1);
+        write_register(&mut map, 0xa000, 0b0_1000);
+        map.cpu_write(0x6000, 2);
+        assert_eq!(map.cpu_read(0x6000), Some(2));
+
+        write_register(&mut map, 0xa000, 0);
+        assert_eq!(map.cpu_read(0x6000), Some(1));
+
+        // bit 4 of the prg bank turns prg ram off
+        write_register(&mut map, 0xe000, 0b1_0000);
+        map.cpu_write(0x6000, 3);
+        assert_eq!(map.cpu_read(0x6000), None);
+
+        write_register(&mut map, 0xe000, 0);
+        assert_eq!(map.cpu_read(0x6000), Some(1));
+    }
+}
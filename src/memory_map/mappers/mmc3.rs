@@ -0,0 +1,530 @@
+use ::memory_map::constants::*;
+use ::memory_map::common::*;
+use ::memory_map::Cartridge;
+use ::ppu;
+use ::rom;
+use ::state::SaveState;
+use ::state::StateError;
+use ::state::StateReader;
+use ::state::StateWriter;
+
+use std::fmt;
+use std::fmt::Debug;
+
+// [MMC3]
+// Mapper 4. Registers come in even/odd pairs, picked by bits 13-14 and bit 0
+// of the address:
+//
+// $8000 even => bank select (which bank register $8001 writes, prg mode, chr
+//               inversion)
+// $8001 odd  => bank data
+// $a000 even => mirroring (0 = vertical, 1 = horizontal), unless the board
+//               has four screen ram
+// $a001 odd  => prg ram protect (bit 7 enables it, bit 6 makes it read only)
+// $c000 even => irq latch
+// $c001 odd  => irq reload
+// $e000 even => irq disable (and acknowledge)
+// $e001 odd  => irq enable
+//
+// prg is banked in 8 KiB windows. $a000 is always r7 and $e000 is always the
+// last bank; prg mode 0 puts r6 at $8000 and the second to last bank at
+// $c000, and mode 1 swaps those two.
+//
+// chr is two 2 KiB banks (r0, r1) and four 1 KiB banks (r2-r5). Normally the
+// 2 KiB banks are at $0000 and the 1 KiB ones at $1000; chr inversion swaps
+// the halves.
+//
+// [Scanline counter]
+// The counter is clocked by A12 of the ppu's address bus going high, which
+// happens once per scanline when the background and sprites use different
+// pattern tables. A12 also blips during the sprite fetches, so the rising
+// edge only counts if A12 was low for a few cpu cycles beforehand.
+//
+// When it's clocked, a counter that's 0 (or was told to reload) gets the
+// latch, and otherwise it goes down by one. Then if it's 0 and irqs are
+// enabled, the irq line is pulled until $e000 is written.
+//
+// Rev A chips (MMC3A and some MMC3B) only fire when the counter actually
+// gets to 0 from something else or on a reload, so a latch of 0 fires once
+// rather than every scanline like rev B.
+//
+// [Resources]
+// MMC3 => http://wiki.nesdev.com/w/index.php/MMC3
+
+const PRG_BANK_SIZE: usize = 0x2000;
+const CHR_BANK_SIZE: usize = 0x0400;
+
+// How many cpu cycles A12 has to stay low for its next rising edge to count
+const A12_LOW_CYCLES: u8 = 3;
+
+// NES 2.0 submapper for boards with the old irq behaviour
+const SUBMAPPER_REV_A: u8 = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MMC3Revision {
+    A,
+    B,
+}
+
+pub struct MMC3MemoryMap {
+    prg_rom: Vec<u8>,
+    prg_ram: Vec<u8>,
+
+    chr: Vec<u8>,
+    chr_is_ram: bool,
+
+    bank_select: u8,
+    bank_registers: [u8; 8],
+
+    mirroring: u8,
+    four_screen_ram: Vec<u8>,
+
+    prg_ram_protect: u8,
+
+    irq_latch: u8,
+    irq_counter: u8,
+    irq_reload: bool,
+    irq_enabled: bool,
+    irq_pending: bool,
+
+    // The last level of A12 the ppu put out, and how long it's been low
+    a12: bool,
+    a12_low_cycles: u8,
+
+    revision: MMC3Revision,
+
+    has_battery_backed_ram: bool,
+}
+
+impl MMC3MemoryMap {
+    pub fn set_revision(&mut self, revision: MMC3Revision) {
+        self.revision = revision;
+    }
+
+    fn write_register(&mut self, address: u16, val: u8) {
+        match (address & 0xe000, address & 1) {
+            (0x8000, 0) => self.bank_select = val,
+            (0x8000, _) => self.bank_registers[(self.bank_select & 0b111) as usize] = val,
+            (0xa000, 0) => self.mirroring = val,
+            (0xa000, _) => self.prg_ram_protect = val,
+            (0xc000, 0) => self.irq_latch = val,
+            (0xc000, _) => {
+                self.irq_counter = 0;
+                self.irq_reload = true;
+            }
+            (_, 0) => {
+                self.irq_enabled = false;
+                self.irq_pending = false;
+            }
+            (_, _) => self.irq_enabled = true,
+        }
+    }
+
+    fn clock_irq_counter(&mut self) {
+        let previous = self.irq_counter;
+        let reloaded = self.irq_reload;
+
+        self.irq_counter = match previous == 0 || reloaded {
+            true => self.irq_latch,
+            false => previous - 1,
+        };
+        self.irq_reload = false;
+
+        let fires = match self.revision {
+            MMC3Revision::A => self.irq_counter == 0 && (previous != 0 || reloaded),
+            MMC3Revision::B => self.irq_counter == 0,
+        };
+
+        if fires && self.irq_enabled {
+            self.irq_pending = true;
+        }
+    }
+
+    fn resolve_prg_rom_address(&self, address: u16) -> usize {
+        let num_banks = self.prg_rom.len() / PRG_BANK_SIZE;
+        let second_last = num_banks.saturating_sub(2);
+        let swapped = self.bank_select & 0b0100_0000 != 0;
+
+        let bank = match (address & 0xe000, swapped) {
+            (0x8000, false) | (0xc000, true) => self.bank_registers[6] as usize,
+            (0x8000, true) | (0xc000, false) => second_last,
+            (0xa000, _) => self.bank_registers[7] as usize,
+            _ => num_banks.saturating_sub(1),
+        };
+
+        (bank * PRG_BANK_SIZE + (address as usize & (PRG_BANK_SIZE - 1))) % self.prg_rom.len()
+    }
+
+    fn resolve_prg_ram_address(&self, address: u16) -> usize {
+        (address - PRG_RAM_START) as usize % self.prg_ram.len()
+    }
+
+    fn prg_ram_enabled(&self) -> bool {
+        self.prg_ram_protect & 0b1000_0000 != 0
+    }
+
+    fn prg_ram_writable(&self) -> bool {
+        self.prg_ram_enabled() && self.prg_ram_protect & 0b0100_0000 == 0
+    }
+
+    fn resolve_chr_address(&self, address: u16) -> usize {
+        // inversion just flips which half of the pattern tables is which
+        let address = match self.bank_select & 0b1000_0000 != 0 {
+            true => address ^ 0x1000,
+            false => address,
+        } & 0x1fff;
+
+        let bank = match address / CHR_BANK_SIZE as u16 {
+            0 => self.bank_registers[0] & !1,
+            1 => self.bank_registers[0] | 1,
+            2 => self.bank_registers[1] & !1,
+            3 => self.bank_registers[1] | 1,
+            slot => self.bank_registers[slot as usize - 2],
+        };
+
+        (bank as usize * CHR_BANK_SIZE + (address as usize & (CHR_BANK_SIZE - 1))) % self.chr.len()
+    }
+
+    fn watch_a12(&mut self, address: u16) {
+        let a12 = address & 0x1000 != 0;
+
+        if a12 && !self.a12 && self.a12_low_cycles >= A12_LOW_CYCLES {
+            self.clock_irq_counter();
+        }
+
+        if a12 {
+            self.a12_low_cycles = 0;
+        }
+
+        self.a12 = a12;
+    }
+}
+
+impl Cartridge for MMC3MemoryMap {
+    fn cpu_read(&self, address: u16) -> Option<u8> {
+        match address {
+            PRG_ROM_START..=PRG_ROM_END => Some(self.prg_rom[self.resolve_prg_rom_address(address)]),
+            PRG_RAM_START..=PRG_RAM_END => {
+                match self.prg_ram_enabled() {
+                    true => Some(self.prg_ram[self.resolve_prg_ram_address(address)]),
+                    false => None,
+                }
+            }
+            _ => None,
+        }
+    }
+
+    fn cpu_write(&mut self, address: u16, val: u8) {
+        match address {
+            PRG_ROM_START..=PRG_ROM_END => self.write_register(address, val),
+            PRG_RAM_START..=PRG_RAM_END if self.prg_ram_writable() => {
+                let address = self.resolve_prg_ram_address(address);
+                self.prg_ram[address] = val;
+            }
+            _ => {}
+        }
+    }
+
+    fn ppu_read(&mut self, address: u16) -> u8 {
+        self.watch_a12(address);
+
+        self.chr[self.resolve_chr_address(address)]
+    }
+
+    fn ppu_write(&mut self, address: u16, val: u8) {
+        self.watch_a12(address);
+
+        if self.chr_is_ram {
+            let address = self.resolve_chr_address(address);
+            self.chr[address] = val;
+        }
+    }
+
+    fn mirroring(&self) -> ppu::MirroringType {
+        match (self.four_screen_ram.is_empty(), self.mirroring & 1) {
+            (false, _) => ppu::MirroringType::FourScreen,
+            (true, 0) => ppu::MirroringType::Vertical,
+            (true, _) => ppu::MirroringType::Horizontal,
+        }
+    }
+
+    fn nametable_read(&mut self, address: u16) -> u8 {
+        self.four_screen_ram[resolve_four_screen_address(address)]
+    }
+
+    fn nametable_write(&mut self, address: u16, val: u8) {
+        self.four_screen_ram[resolve_four_screen_address(address)] = val;
+    }
+
+    fn irq(&self) -> bool {
+        self.irq_pending
+    }
+
+    fn tick(&mut self) {
+        if !self.a12 {
+            self.a12_low_cycles = self.a12_low_cycles.saturating_add(1);
+        }
+    }
+
+    fn save_ram(&self) -> Option<&[u8]> {
+        match self.has_battery_backed_ram {
+            true => Some(&self.prg_ram),
+            false => None,
+        }
+    }
+
+    fn load_save_ram(&mut self, data: &[u8]) {
+        load_save_ram(&mut self.prg_ram, data);
+    }
+
+    fn load(&mut self, rom: &rom::NesRom) {
+        self.prg_rom = rom.prg_rom.clone();
+
+        let (chr, chr_is_ram) = chr_from_rom(rom);
+        self.chr = chr;
+        self.chr_is_ram = chr_is_ram;
+
+        let prg_ram_size = rom.prg_ram_size + rom.prg_nvram_size;
+        self.prg_ram = vec![0; prg_ram_size.max(PRG_RAM_SIZE)];
+        self.has_battery_backed_ram = rom.has_battery_backed_ram();
+
+        if let Some(ref trainer) = rom.trainer {
+            let start = (TRAINER_START - PRG_RAM_START) as usize;
+            self.prg_ram[start..start + trainer.len()].copy_from_slice(trainer);
+        }
+
+        self.four_screen_ram = four_screen_ram(rom.mirroring_type);
+        self.mirroring = match rom.mirroring_type {
+            ppu::MirroringType::Horizontal => 1,
+            _ => 0,
+        };
+
+        self.revision = match rom.submapper_number {
+            SUBMAPPER_REV_A => MMC3Revision::A,
+            _ => MMC3Revision::B,
+        };
+    }
+}
+
+impl SaveState for MMC3MemoryMap {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.u8(self.bank_select);
+        state.bytes(&self.bank_registers);
+        state.u8(self.mirroring);
+        state.u8(self.prg_ram_protect);
+        state.u8(self.irq_latch);
+        state.u8(self.irq_counter);
+        state.bool(self.irq_reload);
+        state.bool(self.irq_enabled);
+        state.bool(self.irq_pending);
+        state.bool(self.a12);
+        state.u8(self.a12_low_cycles);
+        state.bytes(&self.prg_ram);
+        save_chr(state, &self.chr, self.chr_is_ram);
+        state.bytes(&self.four_screen_ram);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), StateError> {
+        self.bank_select = state.u8();
+        state.bytes_into(&mut self.bank_registers, "bank registers")?;
+        self.mirroring = state.u8();
+        self.prg_ram_protect = state.u8();
+        self.irq_latch = state.u8();
+        self.irq_counter = state.u8();
+        self.irq_reload = state.bool();
+        self.irq_enabled = state.bool();
+        self.irq_pending = state.bool();
+        self.a12 = state.bool();
+        self.a12_low_cycles = state.u8();
+        state.bytes_into(&mut self.prg_ram, "prg ram")?;
+        load_chr(state, &mut self.chr, self.chr_is_ram)?;
+        state.bytes_into(&mut self.four_screen_ram, "four screen ram")
+    }
+}
+
+impl Debug for MMC3MemoryMap {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f,
+               "MMC3 {{ bank_select: {:#010b}, bank_registers: {:?}, irq_latch: {}, irq_counter: {}, irq_enabled: {} }}",
+               self.bank_select,
+               self.bank_registers,
+               self.irq_latch,
+               self.irq_counter,
+               self.irq_enabled)
+    }
+}
+
+impl Default for MMC3MemoryMap {
+    fn default() -> Self {
+        MMC3MemoryMap {
+            prg_rom: vec![0; PRG_BANK_SIZE * 4],
+            prg_ram: vec![0; PRG_RAM_SIZE],
+            chr: vec![0; CHR_SIZE],
+            chr_is_ram: true,
+            bank_select: 0,
+            bank_registers: [0; 8],
+            mirroring: 0,
+            four_screen_ram: vec![],
+
+            // plenty of games never touch $a001, so start with the ram usable
+            prg_ram_protect: 0b1000_0000,
+            irq_latch: 0,
+            irq_counter: 0,
+            irq_reload: false,
+            irq_enabled: false,
+            irq_pending: false,
+            a12: false,
+            a12_low_cycles: 0,
+            revision: MMC3Revision::B,
+            has_battery_backed_ram: false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // 8 KiB prg banks and 1 KiB chr banks
+    fn new_map(num_prg_banks: usize, num_chr_banks: usize) -> MMC3MemoryMap {
+        load_cartridge(&numbered_rom(num_prg_banks, PRG_BANK_SIZE, num_chr_banks, CHR_BANK_SIZE))
+    }
+
+    fn set_bank(map: &mut MMC3MemoryMap, bank_select: u8, register: u8, val: u8) {
+        map.cpu_write(0x8000, bank_select | register);
+        map.cpu_write(0x8001, val);
+    }
+
+    // Does what the ppu does to A12 over one rendering scanline: low for the
+    // background fetches, then high for the sprite fetches
+    fn scanline(map: &mut MMC3MemoryMap) {
+        map.ppu_read(0x0000);
+        for _ in 0..85 {
+            map.tick();
+        }
+
+        map.ppu_read(0x1000);
+        for _ in 0..28 {
+            map.tick();
+        }
+    }
+
+    #[test]
+    pub fn test_prg_bank_modes() {
+        let mut map = new_map(16, 8);
+        set_bank(&mut map, 0, 6, 3);
+        set_bank(&mut map, 0, 7, 5);
+
+        assert_eq!(map.cpu_read(0x8000), Some(3));
+        assert_eq!(map.cpu_read(0xa000), Some(5));
+        assert_eq!(map.cpu_read(0xc000), Some(14));
+        assert_eq!(map.cpu_read(0xe000), Some(15));
+
+        map.cpu_write(0x8000, 0b0100_0000);
+        assert_eq!(map.cpu_read(0x8000), Some(14));
+        assert_eq!(map.cpu_read(0xa000), Some(5));
+        assert_eq!(map.cpu_read(0xc000), Some(3));
+        assert_eq!(map.cpu_read(0xe000), Some(15));
+    }
+
+    #[test]
+    pub fn test_chr_banks_and_inversion() {
+        let mut map = new_map(4, 32);
+        set_bank(&mut map, 0, 0, 9);
+        set_bank(&mut map, 0, 1, 12);
+        for register in 2..6 {
+            set_bank(&mut map, 0, register, 20 + register);
+        }
+
+        // 2 KiB banks ignore their low bit
+        let banks = (0..8).map(|slot| map.ppu_read(slot * 0x400)).collect::<Vec<u8>>();
+        assert_eq!(banks, vec![8, 9, 12, 13, 22, 23, 24, 25]);
+
+        map.cpu_write(0x8000, 0b1000_0000);
+        let banks = (0..8).map(|slot| map.ppu_read(slot * 0x400)).collect::<Vec<u8>>();
+        assert_eq!(banks, vec![22, 23, 24, 25, 8, 9, 12, 13]);
+    }
+
+    #[test]
+    pub fn test_mirroring_and_prg_ram_protect() {
+        let mut map = new_map(4, 8);
+
+        map.cpu_write(0xa000, 1);
+        assert_eq!(map.mirroring(), ppu::MirroringType::Horizontal);
+        map.cpu_write(0xa000, 0);
+        assert_eq!(map.mirroring(), ppu::MirroringType::Vertical);
+
+        map.cpu_write(0x6000, 0x42);
+        assert_eq!(map.cpu_read(0x6000), Some(0x42));
+
+        // write protected
+        map.cpu_write(0xa001, 0b1100_0000);
+        map.cpu_write(0x6000, 0x24);
+        assert_eq!(map.cpu_read(0x6000), Some(0x42));
+
+        // disabled
+        map.cpu_write(0xa001, 0);
+        assert_eq!(map.cpu_read(0x6000), None);
+    }
+
+    #[test]
+    pub fn test_scanline_irq() {
+        let mut map = new_map(4, 8);
+        map.cpu_write(0xc000, 2);
+        map.cpu_write(0xc001, 0);
+        map.cpu_write(0xe001, 0);
+
+        // reload to 2, then 1, then 0
+        scanline(&mut map);
+        scanline(&mut map);
+        assert!(!map.irq());
+        scanline(&mut map);
+        assert!(map.irq());
+
+        // stays asserted until acknowledged
+        scanline(&mut map);
+        assert!(map.irq());
+        map.cpu_write(0xe000, 0);
+        assert!(!map.irq());
+    }
+
+    #[test]
+    pub fn test_a12_filter() {
+        let mut map = new_map(4, 8);
+        map.cpu_write(0xc000, 0);
+        map.cpu_write(0xc001, 0);
+        map.cpu_write(0xe001, 0);
+
+        scanline(&mut map);
+        map.cpu_write(0xe000, 0);
+        map.cpu_write(0xe001, 0);
+
+        // A12 dropping for less than a few cycles between sprite fetches
+        // isn't a new scanline
+        map.ppu_read(0x0000);
+        map.tick();
+        map.ppu_read(0x1000);
+        assert!(!map.irq());
+    }
+
+    #[test]
+    pub fn test_rev_a_and_rev_b_with_zero_latch() {
+        for &(revision, fires_again) in &[(MMC3Revision::A, false), (MMC3Revision::B, true)] {
+            let mut map = new_map(4, 8);
+            map.set_revision(revision);
+            map.cpu_write(0xc000, 0);
+            map.cpu_write(0xc001, 0);
+            map.cpu_write(0xe001, 0);
+
+            // the reload fires on both
+            scanline(&mut map);
+            assert!(map.irq());
+            map.cpu_write(0xe000, 0);
+            map.cpu_write(0xe001, 0);
+
+            // but only rev B keeps firing while the counter sits at 0
+            scanline(&mut map);
+            assert_eq!(map.irq(), fires_again);
+        }
+    }
+}
@@ -1,3 +1,69 @@
-mod nrom;
-
-pub use self::nrom::*;
\ No newline at end of file
+mod axrom;
+mod cnrom;
+mod flat;
+mod mmc1;
+mod mmc3;
+mod nrom;
+mod uxrom;
+
+pub use self::axrom::*;
+pub use self::cnrom::*;
+pub use self::flat::*;
+pub use self::mmc1::*;
+pub use self::mmc3::*;
+pub use self::nrom::*;
+pub use self::uxrom::*;
+
+use ::memory_map::Cartridge;
+use ::rom;
+
+// [Mappers]
+// Cartridges wire their banks up in all sorts of ways, and the rom header's
+// mapper number says which one we're dealing with.
+//
+// [Resources]
+// mappers => http://wiki.nesdev.com/w/index.php/Mapper
+
+// Builds (and loads) the cartridge for a rom's mapper
+pub fn create_mapper(rom: &rom::NesRom) -> Result<Box<dyn Cartridge>, rom::RomError> {
+    let mut cartridge: Box<dyn Cartridge> = match rom.mapper_number() {
+        0 if rom.num_prg_banks > 2 => return Err(rom::RomError::InvalidBankCount),
+        0 => Box::new(NROMMemoryMap::default()),
+        1 => Box::new(MMC1MemoryMap::default()),
+        2 => Box::new(UxROMMemoryMap::default()),
+        3 => Box::new(CNROMMemoryMap::default()),
+        4 => Box::new(MMC3MemoryMap::default()),
+        7 => Box::new(AxROMMemoryMap::default()),
+        mapper_number => return Err(rom::RomError::UnsupportedMapper(mapper_number)),
+    };
+
+    cartridge.load(rom);
+
+    Ok(cartridge)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn rom_with_mapper(mapper_number: u8) -> rom::NesRom {
+        let mut buf = b"NES\x1a".to_vec();
+        buf.extend(vec![1, 0, mapper_number << 4, mapper_number & 0xf0, 0, 0, 0, 0, 0, 0, 0, 0]);
+        buf.extend(vec![0; 0x4000]);
+
+        rom::NesRom::from_bytes(&buf).unwrap()
+    }
+
+    #[test]
+    pub fn test_create_mapper() {
+        assert!(create_mapper(&rom_with_mapper(0)).is_ok());
+        assert!(create_mapper(&rom_with_mapper(1)).is_ok());
+        assert!(create_mapper(&rom_with_mapper(4)).is_ok());
+        assert!(create_mapper(&rom_with_mapper(7)).is_ok());
+
+        match create_mapper(&rom_with_mapper(0xf7)) {
+            Err(rom::RomError::UnsupportedMapper(0xf7)) => {}
+            other => panic!("expected UnsupportedMapper, got {:?}", other),
+        }
+    }
+}
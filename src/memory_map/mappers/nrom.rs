@@ -1,181 +1,234 @@
-use ::memory_map::constants::*;
-use ::memory_map::common::*;
-use ::memory_map::MemoryMapper;
-use ::rom;
-
-use std::fmt;
-use std::fmt::Debug;
-
-use byteorder;
-use byteorder::ByteOrder;
-
-pub struct NROMMemoryMap {
-    memory: Vec<u8>,
-
-    num_prg_banks: u8,
-    num_chr_banks: u8,
-}
-
-impl NROMMemoryMap {
-    fn load_prg_rom_upper(&mut self, buf: &[u8]) {
-        let upper_bank_start = PRG_ROM_START + (PRG_ROM_BANK_SIZE as u16);
-
-        self.write_memory(upper_bank_start as usize, PRG_ROM_BANK_SIZE, buf);
-    }
-
-    fn load_prg_rom_lower(&mut self, buf: &[u8]) {
-        let lower_bank_start = PRG_ROM_START;
-
-        self.write_memory(lower_bank_start as usize, PRG_ROM_BANK_SIZE, buf);
-    }
-
-    fn get_mirror_region_for_address(address: u16) -> MirrorRegion {
-        // TODO: make these constants
-        match () {
-            _ if address < 0x2000 => MirrorRegion::One,
-            _ if address >= 0x2000 && address < 0x4000 => MirrorRegion::Two,
-            _ => MirrorRegion::None,
-        }
-    }
-
-    fn write_memory(&mut self, starting_at: usize, len: usize, buf: &[u8]) {
-        write_memory(&mut self.memory, starting_at, len, buf);
-    }
-
-    fn write_to_mirrored_region_one(&mut self, address: u16, val: u8) {
-        let address = NROMMemoryMap::resolve_address_in_region_one(address);
-
-        self.write_direct(address, val);
-    }
-
-    fn write_to_mirrored_region_two(&mut self, address: u16, val: u8) {
-        let address = NROMMemoryMap::resolve_address_in_region_two(address);
-
-        self.write_direct(address, val);
-    }
-
-    fn resolve_address_in_region_one(address: u16) -> u16 {
-        // TODO: make these constants
-        resolve_mirrored_address(address, 0x0000, 0x0800)
-    }
-
-    fn resolve_address_in_region_two(address: u16) -> u16 {
-        // TODO: make these constants
-        resolve_mirrored_address(address, 0x2000, 0x0008)
-    }
-
-    fn write_direct(&mut self, address: u16, val: u8) {
-        self.memory[address as usize] = val;
-    }
-
-    fn resolve_address(address: u16) -> u16 {
-        match NROMMemoryMap::get_mirror_region_for_address(address) {
-            MirrorRegion::One => NROMMemoryMap::resolve_address_in_region_one(address),
-            MirrorRegion::Two => NROMMemoryMap::resolve_address_in_region_two(address),
-            MirrorRegion::None => address,
-        }
-    }
-}
-
-impl MemoryMapper for NROMMemoryMap {
-    fn read(&self, address: u16) -> u8 {
-        let address = NROMMemoryMap::resolve_address(address);
-
-        self.memory[address as usize]
-    }
-
-    fn read_u16(&self, address: u16) -> u16 {
-        let first = self.read(address);
-        let second = self.read(address + 0x1);
-
-        byteorder::LittleEndian::read_u16(&vec![first, second])
-    }
-
-    fn write(&mut self, address: u16, val: u8) {
-        // handle mirroring
-        match NROMMemoryMap::get_mirror_region_for_address(address) {
-            MirrorRegion::One => self.write_to_mirrored_region_one(address, val),
-            MirrorRegion::Two => self.write_to_mirrored_region_two(address, val),
-            MirrorRegion::None => self.write_direct(address, val),
-        }
-    }
-
-    fn write_u16(&mut self, address: u16, val: u16) {
-        let mut bytes = [0; 2];
-        byteorder::LittleEndian::write_u16(&mut bytes, val);
-
-        self.write_memory(address as usize, 2, &bytes);
-    }
-
-    fn load(&mut self, rom: &rom::NesRom) {
-        self.num_prg_banks = rom.num_prg_banks;
-        self.num_chr_banks = rom.num_chr_banks;
-
-        // load prg_rom
-        let prg_rom = &rom.prg_rom;
-        match rom.num_prg_banks {
-            1 => {
-                self.load_prg_rom_upper(&prg_rom);
-                // TODO implement an actual memory mapper -- this is poor
-                // man's mirror for sure
-                self.load_prg_rom_lower(&prg_rom);
-            }
-            _ => {
-                panic!("invalid number of prg_banks to load into memory!");
-            }
-        }
-    }
-}
-
-impl Debug for NROMMemoryMap {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "[...]")
-    }
-}
-
-impl Default for NROMMemoryMap {
-    fn default() -> Self {
-        NROMMemoryMap {
-            memory: vec![0; MEMORY_MAP_TOTAL_SIZE],
-            num_chr_banks: 0,
-            num_prg_banks: 0,
-        }
-    }
-}
-
-enum MirrorRegion {
-    One,
-    Two,
-    None,
-}
-
-#[test]
-fn test_mirrored_region_one_writes() {
-    let mut map = super::NROMMemoryMap::default();
-
-    map.write_to_mirrored_region_one(0x0000, 1);
-
-    // test we wrote to the right places
-    assert_eq!(&map.read(0x0000), &1);
-    assert_eq!(&map.read(0x0800), &1);
-    assert_eq!(&map.read(0x1000), &1);
-    assert_eq!(&map.read(0x1800), &1);
-
-    // test we stayed within our limits
-    assert_eq!(&map.read(0x2000), &0);
-}
-
-#[test]
-fn test_mirrored_region_two_writes() {
-    let mut map = super::NROMMemoryMap::default();
-
-    map.write_to_mirrored_region_two(0x2009, 1);
-
-    // test we wrote to the right places
-    assert_eq!(&map.read(0x2001), &1);
-    assert_eq!(&map.read(0x2009), &1);
-    assert_eq!(&map.read(0x3ff9), &1);
-
-    // test we stayed within our limits
-    assert_eq!(&map.read(0x4000), &0);
-}
\ No newline at end of file
+use ::memory_map::constants::*;
+use ::memory_map::common::*;
+use ::memory_map::Cartridge;
+use ::ppu;
+use ::rom;
+use ::state::SaveState;
+use ::state::StateError;
+use ::state::StateReader;
+use ::state::StateWriter;
+
+use std::fmt;
+use std::fmt::Debug;
+
+// [NROM]
+// Mapper 0: no bank switching at all. prg rom is either one 16 KiB bank
+// (NROM-128), which shows up at both $8000 and $c000, or two (NROM-256)
+// covering $8000-$ffff. Either way, it's just the address decoding ignoring
+// the upper address lines, so reads wrap around whatever's there.
+//
+// $6000-$7fff is 8 KiB of prg ram (only Family BASIC really has it, but
+// emulators give it to every NROM game), and the ppu gets 8 KiB of chr,
+// either rom or ram.
+//
+// [Resources]
+// NROM => http://wiki.nesdev.com/w/index.php/NROM
+
+pub struct NROMMemoryMap {
+    prg_rom: Vec<u8>,
+    prg_ram: Vec<u8>,
+
+    chr: Vec<u8>,
+    chr_is_ram: bool,
+
+    mirroring_type: ppu::MirroringType,
+    four_screen_ram: Vec<u8>,
+
+    has_battery_backed_ram: bool,
+}
+
+impl NROMMemoryMap {
+    fn resolve_prg_rom_address(&self, address: u16) -> usize {
+        (address - PRG_ROM_START) as usize % self.prg_rom.len()
+    }
+
+    fn resolve_prg_ram_address(address: u16) -> usize {
+        (address - PRG_RAM_START) as usize
+    }
+}
+
+impl Cartridge for NROMMemoryMap {
+    fn cpu_read(&self, address: u16) -> Option<u8> {
+        match address {
+            PRG_ROM_START..=PRG_ROM_END => Some(self.prg_rom[self.resolve_prg_rom_address(address)]),
+            PRG_RAM_START..=PRG_RAM_END => Some(self.prg_ram[NROMMemoryMap::resolve_prg_ram_address(address)]),
+            _ => None,
+        }
+    }
+
+    fn cpu_write(&mut self, address: u16, val: u8) {
+        // prg rom is rom (and there's nothing else), so only prg ram takes writes
+        if (PRG_RAM_START..=PRG_RAM_END).contains(&address) {
+            self.prg_ram[NROMMemoryMap::resolve_prg_ram_address(address)] = val;
+        }
+    }
+
+    fn ppu_read(&mut self, address: u16) -> u8 {
+        self.chr[address as usize % self.chr.len()]
+    }
+
+    fn ppu_write(&mut self, address: u16, val: u8) {
+        if self.chr_is_ram {
+            let len = self.chr.len();
+            self.chr[address as usize % len] = val;
+        }
+    }
+
+    fn mirroring(&self) -> ppu::MirroringType {
+        self.mirroring_type
+    }
+
+    fn nametable_read(&mut self, address: u16) -> u8 {
+        self.four_screen_ram[resolve_four_screen_address(address)]
+    }
+
+    fn nametable_write(&mut self, address: u16, val: u8) {
+        self.four_screen_ram[resolve_four_screen_address(address)] = val;
+    }
+
+    fn save_ram(&self) -> Option<&[u8]> {
+        match self.has_battery_backed_ram {
+            true => Some(&self.prg_ram),
+            false => None,
+        }
+    }
+
+    fn load_save_ram(&mut self, data: &[u8]) {
+        load_save_ram(&mut self.prg_ram, data);
+    }
+
+    fn load(&mut self, rom: &rom::NesRom) {
+        self.mirroring_type = rom.mirroring_type;
+        self.four_screen_ram = four_screen_ram(rom.mirroring_type);
+        self.has_battery_backed_ram = rom.has_battery_backed_ram();
+
+        self.prg_rom = rom.prg_rom.clone();
+
+        let (chr, chr_is_ram) = chr_from_rom(rom);
+        self.chr = chr;
+        self.chr_is_ram = chr_is_ram;
+
+        if let Some(ref trainer) = rom.trainer {
+            let start = NROMMemoryMap::resolve_prg_ram_address(TRAINER_START);
+            self.prg_ram[start..start + trainer.len()].copy_from_slice(trainer);
+        }
+    }
+}
+
+impl SaveState for NROMMemoryMap {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.bytes(&self.prg_ram);
+        save_chr(state, &self.chr, self.chr_is_ram);
+        state.bytes(&self.four_screen_ram);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), StateError> {
+        state.bytes_into(&mut self.prg_ram, "prg ram")?;
+        load_chr(state, &mut self.chr, self.chr_is_ram)?;
+        state.bytes_into(&mut self.four_screen_ram, "four screen ram")
+    }
+}
+
+impl Debug for NROMMemoryMap {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "[...]")
+    }
+}
+
+impl Default for NROMMemoryMap {
+    fn default() -> Self {
+        NROMMemoryMap {
+            prg_rom: vec![0; PRG_ROM_BANK_SIZE],
+            prg_ram: vec![0; PRG_RAM_SIZE],
+            chr: vec![0; CHR_SIZE],
+            chr_is_ram: true,
+            mirroring_type: ppu::MirroringType::default(),
+            four_screen_ram: vec![],
+            has_battery_backed_ram: false,
+        }
+    }
+}
+
+#[test]
+fn test_load_maps_trainer() {
+    let mut map = super::NROMMemoryMap::default();
+    let mut rom = rom::NesRom::default();
+    rom.prg_rom = vec![0; PRG_ROM_BANK_SIZE];
+    rom.num_prg_banks = 1;
+    rom.trainer = Some(vec![0x42; 512]);
+
+    map.load(&rom);
+
+    assert_eq!(map.cpu_read(TRAINER_START), Some(0x42));
+    assert_eq!(map.cpu_read(TRAINER_START + 511), Some(0x42));
+    assert_eq!(map.cpu_read(TRAINER_START + 512), Some(0));
+}
+
+#[test]
+fn test_nrom_128_mirrors_its_bank() {
+    let mut map = super::NROMMemoryMap::default();
+
+    let mut rom = rom::NesRom::default();
+    rom.prg_rom = vec![0; PRG_ROM_BANK_SIZE];
+    rom.prg_rom[0] = 0x11;
+    rom.prg_rom[PRG_ROM_BANK_SIZE - 1] = 0x22;
+    rom.num_prg_banks = 1;
+    map.load(&rom);
+
+    assert_eq!(map.cpu_read(0x8000), Some(0x11));
+    assert_eq!(map.cpu_read(0xc000), Some(0x11));
+    assert_eq!(map.cpu_read(0xbfff), Some(0x22));
+    assert_eq!(map.cpu_read(0xffff), Some(0x22));
+}
+
+#[test]
+fn test_nrom_256_maps_both_banks() {
+    let mut map = super::NROMMemoryMap::default();
+
+    let mut rom = rom::NesRom::default();
+    rom.prg_rom = vec![0; 2 * PRG_ROM_BANK_SIZE];
+    rom.prg_rom[0] = 0x11;
+    rom.prg_rom[PRG_ROM_BANK_SIZE] = 0x22;
+    rom.num_prg_banks = 2;
+    map.load(&rom);
+
+    assert_eq!(map.cpu_read(0x8000), Some(0x11));
+    assert_eq!(map.cpu_read(0xc000), Some(0x22));
+
+    // and it's rom
+    map.cpu_write(0x8000, 0xff);
+    assert_eq!(map.cpu_read(0x8000), Some(0x11));
+}
+
+#[test]
+fn test_prg_ram_and_chr() {
+    let mut map = super::NROMMemoryMap::default();
+
+    map.cpu_write(0x6000, 0x42);
+    map.cpu_write(0x7fff, 0x43);
+    assert_eq!(map.cpu_read(0x6000), Some(0x42));
+    assert_eq!(map.cpu_read(0x7fff), Some(0x43));
+
+    // chr ram takes writes, chr rom doesn't
+    map.ppu_write(0x1000, 0x44);
+    assert_eq!(map.ppu_read(0x1000), 0x44);
+
+    let mut rom = rom::NesRom::default();
+    rom.prg_rom = vec![0; PRG_ROM_BANK_SIZE];
+    rom.chr_rom = vec![0x55; CHR_SIZE];
+    map.load(&rom);
+
+    map.ppu_write(0x1000, 0x44);
+    assert_eq!(map.ppu_read(0x1000), 0x55);
+}
+#[test]
+fn test_chr_ram_when_the_rom_has_no_chr() {
+    let mut map = super::NROMMemoryMap::default();
+
+    let mut rom = rom::NesRom::default();
+    rom.prg_rom = vec![0; PRG_ROM_BANK_SIZE];
+    map.load(&rom);
+
+    map.ppu_write(0x1fff, 0x44);
+    assert_eq!(map.ppu_read(0x1fff), 0x44);
+}
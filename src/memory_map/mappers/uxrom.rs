@@ -0,0 +1,173 @@
+use ::memory_map::constants::*;
+use ::memory_map::common::*;
+use ::memory_map::Cartridge;
+use ::ppu;
+use ::rom;
+use ::state::SaveState;
+use ::state::StateError;
+use ::state::StateReader;
+use ::state::StateWriter;
+
+use std::fmt;
+use std::fmt::Debug;
+
+// [UxROM]
+// Mapper 2. Writing anywhere in $8000-$ffff picks the 16 KiB prg bank at
+// $8000; the last bank is always at $c000. chr is a fixed 8 KiB, almost
+// always ram.
+//
+// [Resources]
+// UxROM => http://wiki.nesdev.com/w/index.php/UxROM
+// bus conflicts => http://wiki.nesdev.com/w/index.php/Bus_conflict
+
+const PRG_BANK_SIZE: usize = 0x4000;
+
+pub struct UxROMMemoryMap {
+    prg_rom: Vec<u8>,
+
+    chr: Vec<u8>,
+    chr_is_ram: bool,
+
+    mirroring_type: ppu::MirroringType,
+    four_screen_ram: Vec<u8>,
+
+    prg_bank: u8,
+    bus_conflicts: bool,
+}
+
+impl UxROMMemoryMap {
+    pub fn set_bus_conflicts(&mut self, bus_conflicts: bool) {
+        self.bus_conflicts = bus_conflicts;
+    }
+
+    fn resolve_prg_rom_address(&self, address: u16) -> usize {
+        let bank = match address {
+            0x8000..=0xbfff => self.prg_bank as usize,
+            _ => self.prg_rom.len() / PRG_BANK_SIZE - 1,
+        };
+
+        (bank * PRG_BANK_SIZE + (address as usize & (PRG_BANK_SIZE - 1))) % self.prg_rom.len()
+    }
+}
+
+impl Cartridge for UxROMMemoryMap {
+    fn cpu_read(&self, address: u16) -> Option<u8> {
+        match address {
+            PRG_ROM_START..=PRG_ROM_END => Some(self.prg_rom[self.resolve_prg_rom_address(address)]),
+            _ => None,
+        }
+    }
+
+    fn cpu_write(&mut self, address: u16, val: u8) {
+        if (PRG_ROM_START..=PRG_ROM_END).contains(&address) {
+            self.prg_bank = match self.bus_conflicts {
+                true => bus_conflict(val, self.prg_rom[self.resolve_prg_rom_address(address)]),
+                false => val,
+            }
+        }
+    }
+
+    fn ppu_read(&mut self, address: u16) -> u8 {
+        self.chr[address as usize % self.chr.len()]
+    }
+
+    fn ppu_write(&mut self, address: u16, val: u8) {
+        if self.chr_is_ram {
+            let len = self.chr.len();
+            self.chr[address as usize % len] = val;
+        }
+    }
+
+    fn mirroring(&self) -> ppu::MirroringType {
+        self.mirroring_type
+    }
+
+    fn nametable_read(&mut self, address: u16) -> u8 {
+        self.four_screen_ram[resolve_four_screen_address(address)]
+    }
+
+    fn nametable_write(&mut self, address: u16, val: u8) {
+        self.four_screen_ram[resolve_four_screen_address(address)] = val;
+    }
+
+    fn load(&mut self, rom: &rom::NesRom) {
+        self.prg_rom = rom.prg_rom.clone();
+
+        let (chr, chr_is_ram) = chr_from_rom(rom);
+        self.chr = chr;
+        self.chr_is_ram = chr_is_ram;
+
+        self.mirroring_type = rom.mirroring_type;
+        self.four_screen_ram = four_screen_ram(rom.mirroring_type);
+        self.bus_conflicts = has_bus_conflicts(rom);
+    }
+}
+
+impl SaveState for UxROMMemoryMap {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.u8(self.prg_bank);
+        save_chr(state, &self.chr, self.chr_is_ram);
+        state.bytes(&self.four_screen_ram);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), StateError> {
+        self.prg_bank = state.u8();
+        load_chr(state, &mut self.chr, self.chr_is_ram)?;
+        state.bytes_into(&mut self.four_screen_ram, "four screen ram")
+    }
+}
+
+impl Debug for UxROMMemoryMap {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "UxROM {{ prg_bank: {:#x} }}", self.prg_bank)
+    }
+}
+
+impl Default for UxROMMemoryMap {
+    fn default() -> Self {
+        UxROMMemoryMap {
+            prg_rom: vec![0; PRG_BANK_SIZE * 2],
+            chr: vec![0; CHR_SIZE],
+            chr_is_ram: true,
+            mirroring_type: ppu::MirroringType::default(),
+            four_screen_ram: vec![],
+            prg_bank: 0,
+            bus_conflicts: false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // 16 KiB prg banks and chr ram
+    fn new_map(num_prg_banks: usize) -> UxROMMemoryMap {
+        load_cartridge(&numbered_rom(num_prg_banks, PRG_BANK_SIZE, 0, CHR_SIZE))
+    }
+
+    #[test]
+    pub fn test_switches_lower_bank() {
+        let mut map = new_map(8);
+        assert_eq!(map.cpu_read(0x8000), Some(0));
+        assert_eq!(map.cpu_read(0xc000), Some(7));
+
+        map.cpu_write(0xc123, 5);
+        assert_eq!(map.cpu_read(0x8000), Some(5));
+        assert_eq!(map.cpu_read(0xffff), Some(7));
+    }
+
+    #[test]
+    pub fn test_bus_conflicts() {
+        let mut map = new_map(8);
+        map.set_bus_conflicts(true);
+
+        // the rom under $c000 drives 7 onto the bus, so 6 & 7 wins
+        map.cpu_write(0xc000, 6);
+        assert_eq!(map.cpu_read(0x8000), Some(6));
+
+        // while bank 6 is mapped in at $8000, its 6s mask the 3 down to 2
+        map.cpu_write(0x8000, 3);
+        assert_eq!(map.cpu_read(0x8000), Some(2));
+    }
+}
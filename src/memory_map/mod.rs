@@ -1,21 +1,58 @@
-// TODO: make this cpu::memory_map
-
-mod common;
-mod constants;
-mod mappers;
-
-pub use self::constants::*;
-pub use self::common::*;
-pub use self::mappers::*;
-
-use ::rom;
-
-use std::fmt::Debug;
-
-pub trait MemoryMapper: Debug {
-    fn read(&self, address: u16) -> u8;
-    fn read_u16(&self, address: u16) -> u16;
-    fn write(&mut self, address: u16, val: u8);
-    fn write_u16(&mut self, address: u16, val: u16);
-    fn load(&mut self, rom: &rom::NesRom);
-}
+// TODO: make this cpu::memory_map
+
+mod bus;
+mod cartridge;
+mod common;
+mod constants;
+mod dma;
+mod mappers;
+
+pub use self::bus::*;
+pub use self::cartridge::*;
+pub use self::constants::*;
+pub use self::common::*;
+pub use self::dma::*;
+pub use self::mappers::*;
+
+use std::fmt::Debug;
+
+// What the cpu sees: the whole 64 KiB it can address. On a console that's a
+// Bus with a Cartridge plugged in.
+pub trait MemoryMapper: Debug {
+    fn read(&mut self, address: u16) -> u8;
+    fn read_u16(&mut self, address: u16) -> u16;
+    fn write(&mut self, address: u16, val: u8);
+    fn write_u16(&mut self, address: u16, val: u16);
+
+    // Reads without disturbing anything (e.g. the open bus value), for
+    // traces and debuggers
+    fn peek(&self, address: u16) -> u8;
+
+    // Where the ppu is as (scanline, dot), if there is one
+    fn ppu_position(&self) -> Option<(u16, u16)> {
+        None
+    }
+
+    // Whether anything is asserting the cpu's nmi line
+    fn nmi(&self) -> bool {
+        false
+    }
+
+    // Whether anything is asserting the cpu's irq line
+    fn irq(&self) -> bool {
+        false
+    }
+
+    // Whether a dma wants the cpu halted
+    fn dma_pending(&self) -> bool {
+        false
+    }
+
+    // Runs a cycle of dma while the cpu is halted; cycle is the cpu's cycle
+    // count, since dma cares which cycles are even
+    fn dma_cycle(&mut self, _cycle: u64) {}
+
+    // Called at the end of every cpu cycle, for anything that cares about
+    // timing
+    fn tick(&mut self) {}
+}
@@ -1,13 +0,0 @@
-#[derive(Clone)]
-pub enum MirroringType {
-    Unknown,
-    Horizontal,
-    Vertical,
-    Both,
-}
-
-impl Default for MirroringType {
-    fn default() -> MirroringType {
-        MirroringType:: Unknown
-    }
-}
\ No newline at end of file
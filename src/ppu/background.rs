@@ -0,0 +1,306 @@
+use ::memory_map::Cartridge;
+
+use super::memory::*;
+use super::Ppu;
+use super::CTRL_BACKGROUND_TABLE;
+use super::MASK_SHOW_BACKGROUND;
+use super::MASK_SHOW_BACKGROUND_LEFT;
+use super::PRE_RENDER_SCANLINE;
+
+// [Background]
+// Each tile takes 8 dots to fetch, 2 dots per byte:
+//
+// +0 => nametable byte (which tile)
+// +2 => attribute byte (which palette)
+// +4 => low bitplane of the tile's row
+// +6 => high bitplane, then coarse x + 1
+//
+// Tiles are fetched during dots 1-256 for the scanline being drawn, and the
+// first two tiles of the next scanline during 321-336. Each fetched tile goes
+// into the low byte of a pair of 16 bit shift registers, which shift once a
+// dot, so the high byte is always the tile being drawn and the next one is
+// lined up behind it. Fine x picks which bit of the high byte is the pixel.
+//
+// Dots 337 and 339 fetch nametable bytes nobody uses (some mappers count
+// them), and the scroll updates in scroll.rs happen along the way.
+//
+// [Resources]
+// fetch timing => http://wiki.nesdev.com/w/index.php/PPU_rendering
+// attribute tables => http://wiki.nesdev.com/w/index.php/PPU_attribute_tables
+
+const ATTRIBUTE_TABLE_OFFSET: u16 = 0x03c0;
+
+impl Ppu {
+    // Does this dot's background work; only called while rendering is on
+    pub fn render_background<C: Cartridge>(&mut self, cartridge: &mut C) {
+        let dot = self.dot;
+
+        if (2..=257).contains(&dot) || (321..=337).contains(&dot) {
+            self.shift_background();
+
+            match (dot - 1) % 8 {
+                0 => {
+                    self.load_background_shifters();
+                    self.nametable_byte = self.fetch_nametable_byte(cartridge);
+                }
+                2 => self.attribute_byte = self.fetch_attribute_byte(cartridge),
+                4 => self.pattern_lo = self.fetch_pattern_byte(cartridge, 0),
+                6 => self.pattern_hi = self.fetch_pattern_byte(cartridge, 8),
+                7 => self.increment_coarse_x(),
+                _ => {}
+            }
+        }
+
+        match dot {
+            256 => self.increment_y(),
+            257 => self.copy_horizontal_scroll(),
+            280..=304 if self.scanline == PRE_RENDER_SCANLINE => self.copy_vertical_scroll(),
+            339 => {
+                self.fetch_nametable_byte(cartridge);
+            }
+            _ => {}
+        }
+    }
+
+    // The background's pixel at x, as a palette index (0-15, 0 being
+    // transparent)
+    pub fn background_pixel(&self, x: u16) -> u8 {
+        if self.mask & MASK_SHOW_BACKGROUND == 0 || (x < 8 && self.mask & MASK_SHOW_BACKGROUND_LEFT == 0) {
+            return 0;
+        }
+
+        let bit = 0x8000 >> self.fine_x;
+        let pixel = shifter_bits(self.pattern_shift_lo, self.pattern_shift_hi, bit);
+        let palette = shifter_bits(self.attribute_shift_lo, self.attribute_shift_hi, bit);
+
+        match pixel {
+            0 => 0,
+            _ => (palette << 2) | pixel,
+        }
+    }
+
+    fn shift_background(&mut self) {
+        self.pattern_shift_lo <<= 1;
+        self.pattern_shift_hi <<= 1;
+        self.attribute_shift_lo <<= 1;
+        self.attribute_shift_hi <<= 1;
+    }
+
+    fn load_background_shifters(&mut self) {
+        self.pattern_shift_lo = (self.pattern_shift_lo & 0xff00) | (self.pattern_lo as u16);
+        self.pattern_shift_hi = (self.pattern_shift_hi & 0xff00) | (self.pattern_hi as u16);
+
+        // the palette is the same for the whole tile, so every bit gets it
+        self.attribute_shift_lo = (self.attribute_shift_lo & 0xff00) | fill_byte(self.attribute_byte & 0b01);
+        self.attribute_shift_hi = (self.attribute_shift_hi & 0xff00) | fill_byte(self.attribute_byte & 0b10);
+    }
+
+    pub fn fetch_nametable_byte<C: Cartridge>(&mut self, cartridge: &mut C) -> u8 {
+        let address = NAMETABLES_START | (self.vram_address & 0x0fff);
+
+        self.read_memory(cartridge, address)
+    }
+
+    // Each attribute byte covers 4x4 tiles, 2 bits for each 2x2 quarter
+    fn fetch_attribute_byte<C: Cartridge>(&mut self, cartridge: &mut C) -> u8 {
+        let v = self.vram_address;
+        let address = NAMETABLES_START
+            | ATTRIBUTE_TABLE_OFFSET
+            | (v & 0x0c00)
+            | ((v >> 4) & 0b11_1000)
+            | ((v >> 2) & 0b00_0111);
+
+        let shift = ((v >> 4) & 0b100) | (v & 0b010);
+
+        (self.read_memory(cartridge, address) >> shift) & 0b11
+    }
+
+    fn fetch_pattern_byte<C: Cartridge>(&mut self, cartridge: &mut C, plane: u16) -> u8 {
+        let table = match self.ctrl & CTRL_BACKGROUND_TABLE {
+            0 => 0x0000,
+            _ => 0x1000,
+        };
+        let fine_y = self.vram_address >> 12;
+        let address = table + (self.nametable_byte as u16) * 16 + plane + fine_y;
+
+        self.read_memory(cartridge, address)
+    }
+}
+
+fn shifter_bits(lo: u16, hi: u16, bit: u16) -> u8 {
+    let lo = (lo & bit != 0) as u8;
+    let hi = (hi & bit != 0) as u8;
+
+    (hi << 1) | lo
+}
+
+fn fill_byte(bit: u8) -> u16 {
+    match bit {
+        0 => 0x00,
+        _ => 0xff,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use ::memory_map::NROMMemoryMap;
+    use ::ppu::MirroringType;
+    use ::ppu::FRAME_WIDTH;
+    use ::ppu::MASK_SHOW_BACKGROUND;
+    use ::ppu::PPU_ADDR;
+    use ::ppu::PPU_CTRL;
+    use ::ppu::PPU_DATA;
+    use ::ppu::PPU_MASK;
+    use ::ppu::PPU_SCROLL;
+    use ::ppu::PPU_STATUS;
+    use ::memory_map;
+
+    const BACKDROP: u8 = 0x0f;
+    const COLOUR_1: u8 = 0x21;
+    const COLOUR_3: u8 = 0x22;
+
+    fn write_vram(ppu: &mut Ppu, cartridge: &mut NROMMemoryMap, address: u16, bytes: &[u8]) {
+        ppu.write_register(PPU_ADDR, (address >> 8) as u8, cartridge);
+        ppu.write_register(PPU_ADDR, address as u8, cartridge);
+
+        for byte in bytes {
+            ppu.write_register(PPU_DATA, *byte, cartridge);
+        }
+    }
+
+    fn set_scroll(ppu: &mut Ppu, cartridge: &mut NROMMemoryMap, x: u8, y: u8) {
+        ppu.write_register(PPU_CTRL, 0, cartridge);
+        ppu.read_register(PPU_STATUS, cartridge);
+        ppu.write_register(PPU_SCROLL, x, cartridge);
+        ppu.write_register(PPU_SCROLL, y, cartridge);
+    }
+
+    fn run_until(ppu: &mut Ppu, cartridge: &mut NROMMemoryMap, scanline: u16, dot: u16) {
+        while ppu.scanline != scanline || ppu.dot != dot {
+            ppu.tick(cartridge);
+        }
+    }
+
+    fn run_frame(ppu: &mut Ppu, cartridge: &mut NROMMemoryMap) {
+        let frames = ppu.frames();
+
+        while ppu.frames() == frames {
+            ppu.tick(cartridge);
+        }
+    }
+
+    // Tile 1 (every row of it colours 3 0 0 0 0 0 0 1, in palette 1) at the
+    // top left and 12 rows down, with the first frame (which starts part way
+    // through) out of the way
+    fn new_ppu(mask: u8) -> (Ppu, NROMMemoryMap) {
+        let mut rom = memory_map::numbered_rom(1, memory_map::PRG_ROM_BANK_SIZE, 0, memory_map::CHR_SIZE);
+        rom.mirroring_type = MirroringType::Vertical;
+
+        let mut cartridge: NROMMemoryMap = memory_map::load_cartridge(&rom);
+
+        let mut ppu = Ppu::new();
+        write_vram(&mut ppu, &mut cartridge, 0x0010, &[0b1000_0001; 8]);
+        write_vram(&mut ppu, &mut cartridge, 0x0018, &[0b1000_0000; 8]);
+        write_vram(&mut ppu, &mut cartridge, 0x2000, &[1]);
+        write_vram(&mut ppu, &mut cartridge, 0x2180, &[1]);
+        write_vram(&mut ppu, &mut cartridge, 0x23c0, &[0b01]);
+        write_vram(&mut ppu, &mut cartridge, 0x23d8, &[0b01]);
+        write_vram(&mut ppu, &mut cartridge, 0x3f00, &[BACKDROP, 0, 0, 0, 0, COLOUR_1, 0, COLOUR_3]);
+        write_vram(&mut ppu, &mut cartridge, 0x2000, &[]);
+
+        set_scroll(&mut ppu, &mut cartridge, 0, 0);
+        ppu.write_register(PPU_MASK, mask, &mut cartridge);
+        run_frame(&mut ppu, &mut cartridge);
+
+        (ppu, cartridge)
+    }
+
+    fn pixel(ppu: &Ppu, x: usize, y: usize) -> u8 {
+        ppu.frame()[y * FRAME_WIDTH + x]
+    }
+
+    #[test]
+    pub fn test_draws_background() {
+        let (mut ppu, mut cartridge) = new_ppu(MASK_SHOW_BACKGROUND | MASK_SHOW_BACKGROUND_LEFT);
+        run_frame(&mut ppu, &mut cartridge);
+
+        for y in 0..8 {
+            assert_eq!(pixel(&ppu, 0, y), COLOUR_3);
+            assert_eq!(pixel(&ppu, 1, y), BACKDROP);
+            assert_eq!(pixel(&ppu, 7, y), COLOUR_1);
+        }
+        assert_eq!(pixel(&ppu, 0, 8), BACKDROP);
+        assert_eq!(pixel(&ppu, 7, 96), COLOUR_1);
+    }
+
+    #[test]
+    pub fn test_left_column_clipping() {
+        let (mut ppu, mut cartridge) = new_ppu(MASK_SHOW_BACKGROUND);
+        run_frame(&mut ppu, &mut cartridge);
+
+        assert_eq!(pixel(&ppu, 0, 0), BACKDROP);
+        assert_eq!(pixel(&ppu, 7, 0), BACKDROP);
+    }
+
+    #[test]
+    pub fn test_fine_x_scroll() {
+        let (mut ppu, mut cartridge) = new_ppu(MASK_SHOW_BACKGROUND | MASK_SHOW_BACKGROUND_LEFT);
+        set_scroll(&mut ppu, &mut cartridge, 3, 0);
+        run_frame(&mut ppu, &mut cartridge);
+
+        assert_eq!(pixel(&ppu, 0, 0), BACKDROP);
+        assert_eq!(pixel(&ppu, 4, 0), COLOUR_1);
+        assert_eq!(pixel(&ppu, 7, 0), BACKDROP);
+    }
+
+    #[test]
+    pub fn test_coarse_scroll() {
+        let mask = MASK_SHOW_BACKGROUND | MASK_SHOW_BACKGROUND_LEFT;
+        let (mut ppu, mut cartridge) = new_ppu(mask);
+
+        // the same tile 12 rows down in the second nametable
+        ppu.write_register(PPU_MASK, 0, &mut cartridge);
+        write_vram(&mut ppu, &mut cartridge, 0x2580, &[1]);
+        write_vram(&mut ppu, &mut cartridge, 0x27d8, &[0b01]);
+        ppu.write_register(PPU_MASK, mask, &mut cartridge);
+
+        set_scroll(&mut ppu, &mut cartridge, 248, 8);
+        run_frame(&mut ppu, &mut cartridge);
+
+        // the last column of the first nametable is on the left, and the
+        // second nametable carries on after it, a row up
+        assert_eq!(pixel(&ppu, 0, 88), BACKDROP);
+        assert_eq!(pixel(&ppu, 8, 88), COLOUR_3);
+        assert_eq!(pixel(&ppu, 15, 95), COLOUR_1);
+        assert_eq!(pixel(&ppu, 15, 96), BACKDROP);
+    }
+
+    #[test]
+    pub fn test_mid_frame_scroll_split() {
+        let (mut ppu, mut cartridge) = new_ppu(MASK_SHOW_BACKGROUND | MASK_SHOW_BACKGROUND_LEFT);
+
+        // changing the scroll after the picture has started only moves what's
+        // drawn from the next horizontal copy on
+        run_until(&mut ppu, &mut cartridge, 97, 300);
+        set_scroll(&mut ppu, &mut cartridge, 3, 0);
+        run_until(&mut ppu, &mut cartridge, 241, 2);
+
+        assert_eq!(pixel(&ppu, 7, 0), COLOUR_1);
+        assert_eq!(pixel(&ppu, 7, 97), COLOUR_1);
+        assert_eq!(pixel(&ppu, 4, 99), COLOUR_1);
+        assert_eq!(pixel(&ppu, 7, 99), BACKDROP);
+    }
+
+    #[test]
+    pub fn test_rendering_off_draws_backdrop() {
+        let (mut ppu, mut cartridge) = new_ppu(0);
+        run_frame(&mut ppu, &mut cartridge);
+        assert_eq!(pixel(&ppu, 0, 0), BACKDROP);
+
+        // unless v points into the palette
+        write_vram(&mut ppu, &mut cartridge, 0x3f05, &[]);
+        run_frame(&mut ppu, &mut cartridge);
+        assert_eq!(pixel(&ppu, 0, 0), COLOUR_1);
+    }
+}
@@ -0,0 +1,133 @@
+use ::memory_map::Cartridge;
+
+use super::mirroring::*;
+use super::Ppu;
+use super::MASK_GREYSCALE;
+use super::PALETTE_SIZE;
+
+// [Ppu bus]
+// The ppu has a 14 bit bus of its own, so everything above $3fff wraps
+// around:
+//
+// $0000-$1fff => pattern tables (chr, on the cartridge)
+// $2000-$3eff => nametables, in vram or on the cartridge (see mirroring.rs)
+// $3f00-$3fff => 32 bytes of palette, mirrored
+//
+// The first colour of each palette is the same byte for background and
+// sprites, so $3f10/$3f14/$3f18/$3f1c are mirrors of $3f00/$3f04/$3f08/$3f0c.
+// Palette entries are only 6 bits wide.
+//
+// [Resources]
+// ppu memory map => http://wiki.nesdev.com/w/index.php/PPU_memory_map
+// palettes => http://wiki.nesdev.com/w/index.php/PPU_palettes
+
+pub const PATTERN_TABLES_START: u16 = 0x0000;
+pub const PATTERN_TABLES_END: u16 = 0x1fff;
+pub const NAMETABLES_START: u16 = 0x2000;
+pub const NAMETABLES_END: u16 = 0x3eff;
+pub const PALETTE_START: u16 = 0x3f00;
+
+const PPU_ADDRESS_MASK: u16 = 0x3fff;
+
+impl Ppu {
+    pub fn read_memory<C: Cartridge>(&mut self, cartridge: &mut C, address: u16) -> u8 {
+        let address = address & PPU_ADDRESS_MASK;
+
+        match address {
+            PATTERN_TABLES_START..=PATTERN_TABLES_END => cartridge.ppu_read(address),
+            NAMETABLES_START..=NAMETABLES_END => match cartridge.nametable(address) {
+                Nametable::Vram(index) => self.vram[index],
+                Nametable::Cartridge => cartridge.nametable_read(address),
+            },
+            _ => self.palette[resolve_palette_address(address)],
+        }
+    }
+
+    pub fn write_memory<C: Cartridge>(&mut self, cartridge: &mut C, address: u16, val: u8) {
+        let address = address & PPU_ADDRESS_MASK;
+
+        match address {
+            PATTERN_TABLES_START..=PATTERN_TABLES_END => cartridge.ppu_write(address, val),
+            NAMETABLES_START..=NAMETABLES_END => match cartridge.nametable(address) {
+                Nametable::Vram(index) => self.vram[index] = val,
+                Nametable::Cartridge => cartridge.nametable_write(address, val),
+            },
+            _ => self.palette[resolve_palette_address(address)] = val & 0b0011_1111,
+        }
+    }
+
+    // Palette reads don't touch the cartridge, so they don't need it
+    pub fn read_palette(&self, address: u16) -> u8 {
+        let val = self.palette[resolve_palette_address(address)];
+
+        match self.mask & MASK_GREYSCALE {
+            0 => val,
+            _ => val & 0b0011_0000,
+        }
+    }
+}
+
+pub fn resolve_palette_address(address: u16) -> usize {
+    let index = address as usize & (PALETTE_SIZE - 1);
+
+    match index & 0b11 {
+        0 => index & 0b0_1111,
+        _ => index,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use ::memory_map::AxROMMemoryMap;
+    use ::memory_map::NROMMemoryMap;
+    use ::ppu::MirroringType;
+    use ::memory_map;
+
+    fn load<C: Cartridge + Default>(mirroring_type: MirroringType) -> C {
+        let mut rom = memory_map::numbered_rom(1, memory_map::PRG_ROM_BANK_SIZE, 0, memory_map::CHR_SIZE);
+        rom.mirroring_type = mirroring_type;
+
+        memory_map::load_cartridge(&rom)
+    }
+
+    #[test]
+    pub fn test_four_screen() {
+        let mut ppu = Ppu::new();
+        let mut cartridge: NROMMemoryMap = load(MirroringType::FourScreen);
+
+        for (i, address) in [0x2000, 0x2400, 0x2800, 0x2c00].iter().enumerate() {
+            ppu.write_memory(&mut cartridge, *address, i as u8 + 1);
+        }
+
+        // every nametable is its own, and the last two aren't in vram
+        assert_eq!(ppu.read_memory(&mut cartridge, 0x2000), 1);
+        assert_eq!(ppu.read_memory(&mut cartridge, 0x2400), 2);
+        assert_eq!(ppu.read_memory(&mut cartridge, 0x2800), 3);
+        assert_eq!(ppu.read_memory(&mut cartridge, 0x3c00), 4);
+        assert_eq!(ppu.vram.iter().filter(|byte| **byte != 0).count(), 2);
+    }
+
+    #[test]
+    pub fn test_mirroring_follows_the_cartridge() {
+        let mut ppu = Ppu::new();
+        let mut cartridge: AxROMMemoryMap = load(MirroringType::Unknown);
+
+        ppu.write_memory(&mut cartridge, 0x2000, 1);
+        cartridge.cpu_write(0x8000, 0b1_0000);
+        ppu.write_memory(&mut cartridge, 0x2000, 2);
+
+        assert_eq!(ppu.read_memory(&mut cartridge, 0x2c00), 2);
+        cartridge.cpu_write(0x8000, 0b0_0000);
+        assert_eq!(ppu.read_memory(&mut cartridge, 0x2c00), 1);
+    }
+
+    #[test]
+    pub fn test_resolve_palette_address() {
+        assert_eq!(resolve_palette_address(0x3f01), 0x01);
+        assert_eq!(resolve_palette_address(0x3f11), 0x11);
+        assert_eq!(resolve_palette_address(0x3f10), 0x00);
+        assert_eq!(resolve_palette_address(0x3f1c), 0x0c);
+        assert_eq!(resolve_palette_address(0x3fe5), 0x05);
+    }
+}
@@ -0,0 +1,79 @@
+// [Mirroring]
+// The ppu has room for four 1 KiB nametables at $2000-$2fff, but the console
+// only has 2 KiB of vram, so normally two of them are copies of the other
+// two. Which two is up to the cartridge, which wires up the vram's A10:
+//
+// Horizontal    => $2000 = $2400, $2800 = $2c00 (for vertical scrolling)
+// Vertical      => $2000 = $2800, $2400 = $2c00 (for horizontal scrolling)
+// Single screen => all four are the first (or second) 1 KiB
+// Four screen   => the cartridge brings another 2 KiB for $2800 and $2c00
+//
+// $3000-$3eff is a mirror of $2000-$2eff.
+//
+// The cartridge gets asked on every nametable access (see
+// Cartridge::nametable), so a mapper switching mirroring mid-frame takes
+// effect straight away, and mappers with their own ideas (MMC5's fill mode
+// and ex ram, say) can answer whichever nametables they like.
+//
+// [Resources]
+// mirroring => http://wiki.nesdev.com/w/index.php/Mirroring
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MirroringType {
+    #[default]
+    Unknown,
+    Horizontal,
+    Vertical,
+    FourScreen,
+
+    // Every nametable is the first (or second) one; mappers like MMC1 and
+    // AxROM can switch to these at runtime
+    SingleScreenLower,
+    SingleScreenUpper,
+}
+
+// Where a nametable access goes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Nametable {
+    // An offset into the console's 2 KiB of vram
+    Vram(usize),
+
+    // The cartridge answers it itself
+    Cartridge,
+}
+
+pub const NAMETABLE_SIZE: usize = 0x0400;
+
+// The usual wiring for each kind of mirroring
+pub fn resolve_nametable(address: u16, mirroring: MirroringType) -> Nametable {
+    let table = (address as usize >> 10) & 0b11;
+    let offset = address as usize & (NAMETABLE_SIZE - 1);
+
+    let vram_table = match mirroring {
+        MirroringType::Horizontal => table >> 1,
+        MirroringType::SingleScreenLower => 0,
+        MirroringType::SingleScreenUpper => 1,
+        MirroringType::FourScreen if table >= 2 => return Nametable::Cartridge,
+        MirroringType::Vertical | MirroringType::FourScreen | MirroringType::Unknown => table & 1,
+    };
+
+    Nametable::Vram(vram_table * NAMETABLE_SIZE + offset)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    pub fn test_resolve_nametable() {
+        assert_eq!(resolve_nametable(0x2400, MirroringType::Horizontal), Nametable::Vram(0x0000));
+        assert_eq!(resolve_nametable(0x2c01, MirroringType::Horizontal), Nametable::Vram(0x0401));
+        assert_eq!(resolve_nametable(0x2801, MirroringType::Vertical), Nametable::Vram(0x0001));
+        assert_eq!(resolve_nametable(0x2c00, MirroringType::Vertical), Nametable::Vram(0x0400));
+        assert_eq!(resolve_nametable(0x2c00, MirroringType::SingleScreenLower), Nametable::Vram(0x0000));
+        assert_eq!(resolve_nametable(0x3000, MirroringType::SingleScreenUpper), Nametable::Vram(0x0400));
+        assert_eq!(resolve_nametable(0x2401, MirroringType::FourScreen), Nametable::Vram(0x0401));
+        assert_eq!(resolve_nametable(0x2800, MirroringType::FourScreen), Nametable::Cartridge);
+        assert_eq!(resolve_nametable(0x3eff, MirroringType::FourScreen), Nametable::Cartridge);
+    }
+}
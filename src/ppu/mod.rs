@@ -0,0 +1,393 @@
+mod background;
+mod memory;
+mod mirroring;
+mod registers;
+mod scroll;
+mod sprites;
+
+pub use self::mirroring::*;
+pub use self::registers::*;
+pub use self::sprites::*;
+
+use ::memory_map::Cartridge;
+use ::state::SaveState;
+use ::state::StateError;
+use ::state::StateReader;
+use ::state::StateWriter;
+
+use std::fmt;
+use std::fmt::Debug;
+
+// [Ppu]
+// The 2C02, which draws the picture. It runs three dots for every cpu cycle,
+// 341 dots a scanline and 262 scanlines a frame:
+//
+// 0-239   => visible scanlines
+// 240     => idle
+// 241-260 => vblank (flagged, and nmi raised, at dot 1 of 241)
+// 261     => pre-render, which clears the flags again at dot 1
+//
+// With rendering on, odd frames skip the last dot of the pre-render line.
+//
+// The visible scanlines draw a pixel a dot, for dots 1-256, into frame().
+// Pixels are colours out of the palette (0-63), which is as far as the ppu
+// goes: turning those into rgb is up to whoever shows the frame. The colour
+// emphasis bits of $2001 aren't applied.
+//
+// With rendering off, the ppu just draws the backdrop colour, or whichever
+// palette entry v points at if it points into the palette.
+//
+// The cpu only sees the eight registers at $2000-$2007 (see registers.rs);
+// everything else goes over the ppu's own bus (see memory.rs).
+//
+// [Loopy registers]
+// Scrolling and $2006/$2007 share the same internal registers, named after
+// the person who worked them out:
+//
+// v => the current vram address
+// t => the vram address for the top left of the screen
+// x => fine x scroll (0-7)
+// w => which write of a $2005/$2006 pair we're on
+//
+// While rendering, v and t are laid out as:
+//
+// yyy NN YYYYY XXXXX
+// |   |  |     +---- coarse x scroll
+// |   |  +---------- coarse y scroll
+// |   +------------- nametable select
+// +----------------- fine y scroll
+//
+// [Resources]
+// ppu => http://wiki.nesdev.com/w/index.php/PPU
+// frame timing => http://wiki.nesdev.com/w/index.php/PPU_frame_timing
+// scrolling => http://wiki.nesdev.com/w/index.php/PPU_scrolling
+
+pub const DOTS_PER_SCANLINE: u16 = 341;
+pub const SCANLINES_PER_FRAME: u16 = 262;
+pub const VBLANK_SCANLINE: u16 = 241;
+pub const PRE_RENDER_SCANLINE: u16 = 261;
+
+pub const FRAME_WIDTH: usize = 256;
+pub const FRAME_HEIGHT: usize = 240;
+pub const FRAME_SIZE: usize = FRAME_WIDTH * FRAME_HEIGHT;
+
+pub const OAM_SIZE: usize = 0x0100;
+pub const VRAM_SIZE: usize = 0x0800;
+pub const PALETTE_SIZE: usize = 0x0020;
+
+pub struct Ppu {
+    // $2000, $2001 and $2002
+    ctrl: u8,
+    mask: u8,
+    status: u8,
+
+    // $2003, and the sprite memory it points into
+    oam_address: u8,
+    oam: Vec<u8>,
+
+    // v, t, x and w
+    vram_address: u16,
+    temp_vram_address: u16,
+    fine_x: u8,
+    write_toggle: bool,
+
+    // What the last $2007 read fetched, which the next one hands out
+    read_buffer: u8,
+
+    // The ppu's own data bus, which write only registers read back
+    io_latch: u8,
+
+    // The console's 2 KiB of nametables, and the palette
+    vram: Vec<u8>,
+    palette: [u8; PALETTE_SIZE],
+
+    // The background tile being fetched, and the shift registers the
+    // fetched tiles go into (see background.rs)
+    nametable_byte: u8,
+    attribute_byte: u8,
+    pattern_lo: u8,
+    pattern_hi: u8,
+    pattern_shift_lo: u16,
+    pattern_shift_hi: u16,
+    attribute_shift_lo: u16,
+    attribute_shift_hi: u16,
+
+    // What sprite evaluation found for the next scanline, and the sprites
+    // being drawn on this one (see sprites.rs)
+    secondary_oam: [u8; SECONDARY_OAM_SIZE],
+    sprites_found: u8,
+    sprite_zero_found: bool,
+    sprites: [Sprite; SPRITE_SLOTS],
+    sprite_zero_loaded: bool,
+
+    // Where we are in the frame, and how many frames we've finished
+    scanline: u16,
+    dot: u16,
+    odd_frame: bool,
+    frames: u64,
+
+    frame: Box<[u8; FRAME_SIZE]>,
+
+    // Set by a $2002 read just before vblank starts, which stops vblank
+    // being flagged that frame
+    suppress_vblank: bool,
+}
+
+impl Ppu {
+    pub fn new() -> Self {
+        Ppu {
+            ctrl: 0,
+            mask: 0,
+            status: 0,
+            oam_address: 0,
+            oam: vec![0; OAM_SIZE],
+            vram_address: 0,
+            temp_vram_address: 0,
+            fine_x: 0,
+            write_toggle: false,
+            read_buffer: 0,
+            io_latch: 0,
+            vram: vec![0; VRAM_SIZE],
+            palette: [0; PALETTE_SIZE],
+            nametable_byte: 0,
+            attribute_byte: 0,
+            pattern_lo: 0,
+            pattern_hi: 0,
+            pattern_shift_lo: 0,
+            pattern_shift_hi: 0,
+            attribute_shift_lo: 0,
+            attribute_shift_hi: 0,
+            secondary_oam: [0xff; SECONDARY_OAM_SIZE],
+            sprites_found: 0,
+            sprite_zero_found: false,
+            sprites: [Sprite::default(); SPRITE_SLOTS],
+            sprite_zero_loaded: false,
+            scanline: 0,
+            dot: 0,
+            odd_frame: false,
+            frames: 0,
+            frame: Box::new([0; FRAME_SIZE]),
+            suppress_vblank: false,
+        }
+    }
+
+    // Runs a single dot
+    pub fn tick<C: Cartridge>(&mut self, cartridge: &mut C) {
+        if self.rendering_enabled() && self.on_render_scanline() {
+            self.render_background(cartridge);
+            self.render_sprites(cartridge);
+        }
+
+        if self.scanline < FRAME_HEIGHT as u16 && (1..=FRAME_WIDTH as u16).contains(&self.dot) {
+            self.draw_pixel();
+        }
+
+        match (self.scanline, self.dot) {
+            (VBLANK_SCANLINE, 1) => {
+                if !self.suppress_vblank {
+                    self.status |= STATUS_VBLANK;
+                }
+
+                self.suppress_vblank = false;
+                self.frames += 1;
+            }
+            (PRE_RENDER_SCANLINE, 1) => {
+                self.status &= !(STATUS_VBLANK | STATUS_SPRITE_0_HIT | STATUS_SPRITE_OVERFLOW);
+            }
+            _ => {}
+        }
+
+        self.next_dot();
+
+        if self.dot == 0 {
+            cartridge.scanline();
+        }
+    }
+
+    fn draw_pixel(&mut self) {
+        let x = self.dot - 1;
+
+        let colour = match self.rendering_enabled() {
+            true => {
+                let pixel = self.composite_pixel(x);
+                self.read_palette(memory::PALETTE_START + pixel as u16)
+            }
+            false => match self.vram_address & 0x3fff >= memory::PALETTE_START {
+                true => self.read_palette(self.vram_address),
+                false => self.read_palette(memory::PALETTE_START),
+            },
+        };
+
+        self.frame[self.scanline as usize * FRAME_WIDTH + x as usize] = colour;
+    }
+
+    // Puts the sprites in front of or behind the background, and checks for
+    // a sprite 0 hit on the way
+    fn composite_pixel(&mut self, x: u16) -> u8 {
+        let background = self.background_pixel(x);
+
+        let sprite = match self.sprite_pixel(x) {
+            Some(sprite) => sprite,
+            None => return background,
+        };
+
+        if sprite.sprite_zero && background != 0 && x != 255 {
+            self.status |= STATUS_SPRITE_0_HIT;
+        }
+
+        match background != 0 && sprite.behind_background {
+            true => background,
+            false => sprite.pixel,
+        }
+    }
+
+    fn next_dot(&mut self) {
+        let skip_last_dot = self.scanline == PRE_RENDER_SCANLINE
+            && self.dot == DOTS_PER_SCANLINE - 2
+            && self.odd_frame
+            && self.rendering_enabled();
+
+        self.dot += 1;
+        if self.dot < DOTS_PER_SCANLINE && !skip_last_dot {
+            return;
+        }
+
+        self.dot = 0;
+        self.scanline += 1;
+
+        if self.scanline == SCANLINES_PER_FRAME {
+            self.scanline = 0;
+            self.odd_frame = !self.odd_frame;
+        }
+    }
+
+    // The ppu's nmi output, which stays active for as long as vblank is
+    // flagged and nmis are enabled
+    pub fn nmi(&self) -> bool {
+        self.status & STATUS_VBLANK != 0 && self.ctrl & CTRL_NMI != 0
+    }
+
+    pub fn rendering_enabled(&self) -> bool {
+        self.mask & (MASK_SHOW_BACKGROUND | MASK_SHOW_SPRITES) != 0
+    }
+
+    // Whether this scanline fetches anything (when rendering is on)
+    pub fn on_render_scanline(&self) -> bool {
+        self.scanline < FRAME_HEIGHT as u16 || self.scanline == PRE_RENDER_SCANLINE
+    }
+
+    // The last picture drawn, a row at a time; complete whenever frames()
+    // goes up
+    pub fn frame(&self) -> &[u8; FRAME_SIZE] {
+        &self.frame
+    }
+
+    pub fn frames(&self) -> u64 {
+        self.frames
+    }
+
+    // The 64 sprites, 4 bytes each
+    pub fn oam(&self) -> &[u8] {
+        &self.oam
+    }
+
+    pub fn scanline(&self) -> u16 {
+        self.scanline
+    }
+
+    pub fn dot(&self) -> u16 {
+        self.dot
+    }
+}
+
+impl SaveState for Ppu {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.u8(self.ctrl);
+        state.u8(self.mask);
+        state.u8(self.status);
+        state.u8(self.oam_address);
+        state.bytes(&self.oam);
+        state.u16(self.vram_address);
+        state.u16(self.temp_vram_address);
+        state.u8(self.fine_x);
+        state.bool(self.write_toggle);
+        state.u8(self.read_buffer);
+        state.u8(self.io_latch);
+        state.bytes(&self.vram);
+        state.bytes(&self.palette);
+        state.u16(self.scanline);
+        state.u16(self.dot);
+        state.bool(self.odd_frame);
+        state.bool(self.suppress_vblank);
+        state.u8(self.nametable_byte);
+        state.u8(self.attribute_byte);
+        state.u8(self.pattern_lo);
+        state.u8(self.pattern_hi);
+        state.u16(self.pattern_shift_lo);
+        state.u16(self.pattern_shift_hi);
+        state.u16(self.attribute_shift_lo);
+        state.u16(self.attribute_shift_hi);
+        state.u64(self.frames);
+        state.bytes(&self.secondary_oam);
+        state.u8(self.sprites_found);
+        state.bool(self.sprite_zero_found);
+        for sprite in self.sprites.iter() {
+            sprite.save_state(state);
+        }
+        state.bool(self.sprite_zero_loaded);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), StateError> {
+        self.ctrl = state.u8();
+        self.mask = state.u8();
+        self.status = state.u8();
+        self.oam_address = state.u8();
+        state.bytes_into(&mut self.oam, "oam")?;
+        self.vram_address = state.u16();
+        self.temp_vram_address = state.u16();
+        self.fine_x = state.u8();
+        self.write_toggle = state.bool();
+        self.read_buffer = state.u8();
+        self.io_latch = state.u8();
+        state.bytes_into(&mut self.vram, "vram")?;
+        state.bytes_into(&mut self.palette, "palette")?;
+        self.scanline = state.u16() % SCANLINES_PER_FRAME;
+        self.dot = state.u16() % DOTS_PER_SCANLINE;
+        self.odd_frame = state.bool();
+        self.suppress_vblank = state.bool();
+        self.nametable_byte = state.u8();
+        self.attribute_byte = state.u8();
+        self.pattern_lo = state.u8();
+        self.pattern_hi = state.u8();
+        self.pattern_shift_lo = state.u16();
+        self.pattern_shift_hi = state.u16();
+        self.attribute_shift_lo = state.u16();
+        self.attribute_shift_hi = state.u16();
+        self.frames = state.u64();
+        state.bytes_into(&mut self.secondary_oam, "secondary oam")?;
+        self.sprites_found = state.u8().min(SPRITE_SLOTS as u8);
+        self.sprite_zero_found = state.bool();
+        for sprite in self.sprites.iter_mut() {
+            sprite.load_state(state)?;
+        }
+        self.sprite_zero_loaded = state.bool();
+
+        Ok(())
+    }
+}
+
+impl Debug for Ppu {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Ppu {{ ctrl: {:#04x}, mask: {:#04x}, status: {:#04x}, v: {:#06x}, scanline: {}, dot: {} }}",
+            self.ctrl, self.mask, self.status, self.vram_address, self.scanline, self.dot
+        )
+    }
+}
+
+impl Default for Ppu {
+    fn default() -> Self {
+        Ppu::new()
+    }
+}
@@ -0,0 +1,357 @@
+use ::memory_map::Cartridge;
+
+use super::memory::*;
+use super::Ppu;
+use super::VBLANK_SCANLINE;
+
+// [Registers]
+// What the cpu sees at $2000-$2007 (mirrored up to $3fff):
+//
+// $2000 PPUCTRL   (write) => nmi enable, sprite size, pattern tables,
+//                            vram increment, base nametable
+// $2001 PPUMASK   (write) => what gets drawn
+// $2002 PPUSTATUS (read)  => vblank, sprite 0 hit, sprite overflow; reading
+//                            it clears vblank and w
+// $2003 OAMADDR   (write) => where $2004 goes in oam
+// $2004 OAMDATA   (r/w)   => oam at OAMADDR; writes increment OAMADDR
+// $2005 PPUSCROLL (write) => x then y scroll, into t and x
+// $2006 PPUADDR   (write) => vram address high then low byte, into t then v
+// $2007 PPUDATA   (r/w)   => vram at v, which then goes up by 1 or 32
+//
+// $2005 and $2006 share w, so a $2002 read is how a game makes sure its next
+// write is the first of a pair.
+//
+// [PPUDATA reads]
+// Reads below the palette come out of a buffer holding the previous read,
+// so the first read after setting the address is junk. Palette reads come
+// straight back, but still refill the buffer with the nametable byte
+// "underneath" the palette.
+//
+// [Open bus]
+// Writing any register (and reading a readable one) leaves the value on the
+// ppu's data bus, which is what the write only registers read back, along
+// with the low 5 bits of $2002 and the top 2 of a palette read. On hardware
+// it decays after a while; here it doesn't.
+//
+// [Resources]
+// registers => http://wiki.nesdev.com/w/index.php/PPU_registers
+// loopy register writes => http://wiki.nesdev.com/w/index.php/PPU_scrolling#Register_controls
+
+pub const PPU_CTRL: u16 = 0;
+pub const PPU_MASK: u16 = 1;
+pub const PPU_STATUS: u16 = 2;
+pub const OAM_ADDR: u16 = 3;
+pub const OAM_DATA: u16 = 4;
+pub const PPU_SCROLL: u16 = 5;
+pub const PPU_ADDR: u16 = 6;
+pub const PPU_DATA: u16 = 7;
+
+pub const CTRL_NAMETABLE: u8 = 0b0000_0011;
+pub const CTRL_INCREMENT_32: u8 = 0b0000_0100;
+pub const CTRL_SPRITE_TABLE: u8 = 0b0000_1000;
+pub const CTRL_BACKGROUND_TABLE: u8 = 0b0001_0000;
+pub const CTRL_SPRITE_SIZE: u8 = 0b0010_0000;
+pub const CTRL_NMI: u8 = 0b1000_0000;
+
+pub const MASK_GREYSCALE: u8 = 0b0000_0001;
+pub const MASK_SHOW_BACKGROUND_LEFT: u8 = 0b0000_0010;
+pub const MASK_SHOW_SPRITES_LEFT: u8 = 0b0000_0100;
+pub const MASK_SHOW_BACKGROUND: u8 = 0b0000_1000;
+pub const MASK_SHOW_SPRITES: u8 = 0b0001_0000;
+
+pub const STATUS_SPRITE_OVERFLOW: u8 = 0b0010_0000;
+pub const STATUS_SPRITE_0_HIT: u8 = 0b0100_0000;
+pub const STATUS_VBLANK: u8 = 0b1000_0000;
+
+// Sprite attribute bytes don't have bits 2-4
+const OAM_ATTRIBUTE_MASK: u8 = 0b1110_0011;
+
+impl Ppu {
+    // register is 0-7, i.e. already unmirrored
+    pub fn read_register<C: Cartridge>(&mut self, register: u16, cartridge: &mut C) -> u8 {
+        let val = self.peek_register(register);
+
+        match register {
+            PPU_STATUS => {
+                // reading just as vblank starts sees it clear, and stops it
+                // being flagged at all
+                if self.scanline == VBLANK_SCANLINE && self.dot == 1 {
+                    self.suppress_vblank = true;
+                }
+
+                self.status &= !STATUS_VBLANK;
+                self.write_toggle = false;
+            }
+            PPU_DATA => {
+                let address = self.vram_address & 0x3fff;
+
+                self.read_buffer = match address >= PALETTE_START {
+                    true => self.read_memory(cartridge, address - 0x1000),
+                    false => self.read_memory(cartridge, address),
+                };
+
+                self.increment_vram_address();
+            }
+            _ => {}
+        }
+
+        self.io_latch = val;
+
+        val
+    }
+
+    // What a read would see, without any of its side effects
+    pub fn peek_register(&self, register: u16) -> u8 {
+        match register {
+            PPU_STATUS => (self.status & 0b1110_0000) | (self.io_latch & 0b0001_1111),
+            OAM_DATA => self.oam[self.oam_address as usize],
+            PPU_DATA => match self.vram_address & 0x3fff >= PALETTE_START {
+                true => self.read_palette(self.vram_address) | (self.io_latch & 0b1100_0000),
+                false => self.read_buffer,
+            },
+            _ => self.io_latch,
+        }
+    }
+
+    pub fn write_register<C: Cartridge>(&mut self, register: u16, val: u8, cartridge: &mut C) {
+        self.io_latch = val;
+
+        match register {
+            PPU_CTRL => {
+                self.ctrl = val;
+                self.temp_vram_address = (self.temp_vram_address & !0x0c00) | (((val & CTRL_NAMETABLE) as u16) << 10);
+            }
+            PPU_MASK => self.mask = val,
+            OAM_ADDR => self.oam_address = val,
+            OAM_DATA => {
+                let val = match self.oam_address & 0b11 {
+                    2 => val & OAM_ATTRIBUTE_MASK,
+                    _ => val,
+                };
+
+                self.oam[self.oam_address as usize] = val;
+                self.oam_address = self.oam_address.wrapping_add(1);
+            }
+            PPU_SCROLL => {
+                match self.write_toggle {
+                    // coarse x into t, fine x into x
+                    false => {
+                        self.temp_vram_address = (self.temp_vram_address & !0x001f) | ((val >> 3) as u16);
+                        self.fine_x = val & 0b111;
+                    }
+                    // fine and coarse y into t
+                    true => {
+                        self.temp_vram_address = (self.temp_vram_address & !0x73e0)
+                            | (((val & 0b111) as u16) << 12)
+                            | (((val & 0b1111_1000) as u16) << 2);
+                    }
+                }
+
+                self.write_toggle = !self.write_toggle;
+            }
+            PPU_ADDR => {
+                match self.write_toggle {
+                    // the high byte only has 6 bits, and clears bit 14 of t
+                    false => {
+                        self.temp_vram_address = (self.temp_vram_address & 0x00ff) | (((val & 0b0011_1111) as u16) << 8);
+                    }
+                    true => {
+                        self.temp_vram_address = (self.temp_vram_address & 0xff00) | (val as u16);
+                        self.vram_address = self.temp_vram_address;
+                    }
+                }
+
+                self.write_toggle = !self.write_toggle;
+            }
+            PPU_DATA => {
+                let address = self.vram_address;
+                self.write_memory(cartridge, address, val);
+
+                self.increment_vram_address();
+            }
+            _ => {}
+        }
+    }
+
+    fn increment_vram_address(&mut self) {
+        // mid-render, v is busy being the scroll position, and $2007 bumps it
+        // the way rendering does instead
+        if self.rendering_enabled() && self.on_render_scanline() {
+            self.increment_coarse_x();
+            self.increment_y();
+            return;
+        }
+
+        let increment = match self.ctrl & CTRL_INCREMENT_32 {
+            0 => 1,
+            _ => 32,
+        };
+
+        self.vram_address = self.vram_address.wrapping_add(increment) & 0x7fff;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use ::memory_map::NROMMemoryMap;
+    use ::ppu::MirroringType;
+    use ::ppu::PRE_RENDER_SCANLINE;
+    use ::memory_map;
+
+    fn new_cartridge() -> NROMMemoryMap {
+        let mut rom = memory_map::numbered_rom(1, memory_map::PRG_ROM_BANK_SIZE, 0, memory_map::CHR_SIZE);
+        rom.mirroring_type = MirroringType::Vertical;
+
+        memory_map::load_cartridge(&rom)
+    }
+
+    fn set_address(ppu: &mut Ppu, cartridge: &mut NROMMemoryMap, address: u16) {
+        ppu.write_register(PPU_ADDR, (address >> 8) as u8, cartridge);
+        ppu.write_register(PPU_ADDR, address as u8, cartridge);
+    }
+
+    fn run_until(ppu: &mut Ppu, cartridge: &mut NROMMemoryMap, scanline: u16, dot: u16) {
+        while ppu.scanline != scanline || ppu.dot != dot {
+            ppu.tick(cartridge);
+        }
+    }
+
+    #[test]
+    pub fn test_data_reads_are_buffered() {
+        let mut ppu = Ppu::new();
+        let mut cartridge = new_cartridge();
+
+        set_address(&mut ppu, &mut cartridge, 0x2400);
+        ppu.write_register(PPU_DATA, 0x42, &mut cartridge);
+        ppu.write_register(PPU_DATA, 0x43, &mut cartridge);
+
+        set_address(&mut ppu, &mut cartridge, 0x2400);
+        assert_eq!(ppu.read_register(PPU_DATA, &mut cartridge), 0x00);
+        assert_eq!(ppu.read_register(PPU_DATA, &mut cartridge), 0x42);
+        assert_eq!(ppu.read_register(PPU_DATA, &mut cartridge), 0x43);
+
+        // vertical mirroring puts $2c00 on top of $2400
+        set_address(&mut ppu, &mut cartridge, 0x2c00);
+        ppu.read_register(PPU_DATA, &mut cartridge);
+        assert_eq!(ppu.read_register(PPU_DATA, &mut cartridge), 0x42);
+    }
+
+    #[test]
+    pub fn test_palette_reads_are_not_buffered() {
+        let mut ppu = Ppu::new();
+        let mut cartridge = new_cartridge();
+
+        set_address(&mut ppu, &mut cartridge, 0x2f10);
+        ppu.write_register(PPU_DATA, 0x42, &mut cartridge);
+        set_address(&mut ppu, &mut cartridge, 0x3f10);
+        ppu.write_register(PPU_DATA, 0xff, &mut cartridge);
+
+        // $3f10 is $3f00, palette entries are 6 bits and the top 2 come from
+        // the open bus
+        set_address(&mut ppu, &mut cartridge, 0x3f00);
+        ppu.write_register(PPU_MASK, 0b1000_0000, &mut cartridge);
+        assert_eq!(ppu.read_register(PPU_DATA, &mut cartridge), 0b1011_1111);
+
+        // and the buffer gets the nametable byte under it
+        set_address(&mut ppu, &mut cartridge, 0x3f10);
+        ppu.read_register(PPU_DATA, &mut cartridge);
+        assert_eq!(ppu.read_buffer, 0x42);
+    }
+
+    #[test]
+    pub fn test_vram_increment() {
+        let mut ppu = Ppu::new();
+        let mut cartridge = new_cartridge();
+
+        set_address(&mut ppu, &mut cartridge, 0x2000);
+        ppu.write_register(PPU_DATA, 0, &mut cartridge);
+        assert_eq!(ppu.vram_address, 0x2001);
+
+        ppu.write_register(PPU_CTRL, CTRL_INCREMENT_32, &mut cartridge);
+        ppu.read_register(PPU_DATA, &mut cartridge);
+        assert_eq!(ppu.vram_address, 0x2021);
+    }
+
+    // the literals are grouped like yyy NN YYYYY XXXXX
+    #[test]
+    #[allow(clippy::unusual_byte_groupings)]
+    pub fn test_loopy_registers() {
+        let mut ppu = Ppu::new();
+        let mut cartridge = new_cartridge();
+
+        ppu.write_register(PPU_CTRL, 0b10, &mut cartridge);
+        assert_eq!(ppu.temp_vram_address, 0b000_10_00000_00000);
+
+        ppu.read_register(PPU_STATUS, &mut cartridge);
+        ppu.write_register(PPU_SCROLL, 0b01111_101, &mut cartridge);
+        assert_eq!(ppu.temp_vram_address, 0b000_10_00000_01111);
+        assert_eq!(ppu.fine_x, 0b101);
+        assert!(ppu.write_toggle);
+
+        ppu.write_register(PPU_SCROLL, 0b01011_110, &mut cartridge);
+        assert_eq!(ppu.temp_vram_address, 0b110_10_01011_01111);
+        assert!(!ppu.write_toggle);
+
+        // $2006 shares the toggle with $2005, and only copies t to v on the
+        // second write
+        ppu.write_register(PPU_ADDR, 0b1111_1101, &mut cartridge);
+        assert_eq!(ppu.temp_vram_address, 0b011_11_01011_01111);
+        assert_eq!(ppu.vram_address, 0);
+
+        ppu.write_register(PPU_ADDR, 0b1110_0001, &mut cartridge);
+        assert_eq!(ppu.temp_vram_address, 0b011_11_01111_00001);
+        assert_eq!(ppu.vram_address, ppu.temp_vram_address);
+
+        // a $2002 read resets the toggle halfway through a pair
+        ppu.write_register(PPU_SCROLL, 0, &mut cartridge);
+        ppu.read_register(PPU_STATUS, &mut cartridge);
+        assert!(!ppu.write_toggle);
+    }
+
+    #[test]
+    pub fn test_vblank() {
+        let mut ppu = Ppu::new();
+        let mut cartridge = new_cartridge();
+
+        run_until(&mut ppu, &mut cartridge, VBLANK_SCANLINE, 2);
+        assert_eq!(ppu.peek_register(PPU_STATUS) & STATUS_VBLANK, STATUS_VBLANK);
+        assert!(!ppu.nmi());
+
+        // turning nmis on during vblank raises one straight away
+        ppu.write_register(PPU_CTRL, CTRL_NMI, &mut cartridge);
+        assert!(ppu.nmi());
+
+        // reading clears the flag (and so the nmi)
+        assert_eq!(ppu.read_register(PPU_STATUS, &mut cartridge) & STATUS_VBLANK, STATUS_VBLANK);
+        assert_eq!(ppu.read_register(PPU_STATUS, &mut cartridge) & STATUS_VBLANK, 0);
+        assert!(!ppu.nmi());
+
+        // reading right before it's set means it never is
+        run_until(&mut ppu, &mut cartridge, VBLANK_SCANLINE, 1);
+        assert_eq!(ppu.read_register(PPU_STATUS, &mut cartridge) & STATUS_VBLANK, 0);
+        ppu.tick(&mut cartridge);
+        assert_eq!(ppu.peek_register(PPU_STATUS) & STATUS_VBLANK, 0);
+
+        // the pre-render line clears it
+        run_until(&mut ppu, &mut cartridge, VBLANK_SCANLINE, 2);
+        run_until(&mut ppu, &mut cartridge, PRE_RENDER_SCANLINE, 2);
+        assert_eq!(ppu.peek_register(PPU_STATUS) & STATUS_VBLANK, 0);
+    }
+
+    #[test]
+    pub fn test_open_bus() {
+        let mut ppu = Ppu::new();
+        let mut cartridge = new_cartridge();
+
+        ppu.write_register(PPU_CTRL, 0b0101_0101, &mut cartridge);
+        assert_eq!(ppu.read_register(PPU_MASK, &mut cartridge), 0b0101_0101);
+        assert_eq!(ppu.read_register(PPU_STATUS, &mut cartridge), 0b0001_0101);
+
+        // oam attribute bytes don't keep bits 2-4
+        ppu.write_register(OAM_ADDR, 2, &mut cartridge);
+        ppu.write_register(OAM_DATA, 0xff, &mut cartridge);
+        ppu.write_register(OAM_ADDR, 2, &mut cartridge);
+        assert_eq!(ppu.read_register(OAM_DATA, &mut cartridge), 0b1110_0011);
+    }
+}
@@ -0,0 +1,125 @@
+use super::Ppu;
+
+// [Scrolling]
+// While rendering, v walks across the screen (see the layout in mod.rs):
+//
+// every 8th dot of a fetch => coarse x + 1, wrapping into the next nametable
+//                             across
+// dot 256                  => y + 1, fine y first, wrapping at row 30 into
+//                             the next nametable down
+// dot 257                  => the horizontal bits come back from t
+// dots 280-304 (pre-render) => the vertical bits come back from t
+//
+// Games that change t mid-frame (with $2005/$2006) get split screens, since
+// the next copy picks the change up.
+//
+// [Resources]
+// wrapping => http://wiki.nesdev.com/w/index.php/PPU_scrolling#Wrapping_around
+
+const COARSE_X: u16 = 0x001f;
+const COARSE_Y: u16 = 0x03e0;
+const NAMETABLE_X: u16 = 0x0400;
+const NAMETABLE_Y: u16 = 0x0800;
+const FINE_Y: u16 = 0x7000;
+
+const HORIZONTAL_BITS: u16 = NAMETABLE_X | COARSE_X;
+const VERTICAL_BITS: u16 = FINE_Y | NAMETABLE_Y | COARSE_Y;
+
+// Rows 30 and 31 of a nametable are its attribute table
+const LAST_TILE_ROW: u16 = 29;
+
+impl Ppu {
+    pub fn increment_coarse_x(&mut self) {
+        match self.vram_address & COARSE_X {
+            COARSE_X => self.vram_address = (self.vram_address & !COARSE_X) ^ NAMETABLE_X,
+            _ => self.vram_address += 1,
+        }
+    }
+
+    pub fn increment_y(&mut self) {
+        if self.vram_address & FINE_Y != FINE_Y {
+            self.vram_address += 0x1000;
+            return;
+        }
+
+        self.vram_address &= !FINE_Y;
+
+        // a coarse y set past row 29 (by a write) wraps without switching
+        // nametables
+        let coarse_y = match (self.vram_address & COARSE_Y) >> 5 {
+            LAST_TILE_ROW => {
+                self.vram_address ^= NAMETABLE_Y;
+                0
+            }
+            31 => 0,
+            coarse_y => coarse_y + 1,
+        };
+
+        self.vram_address = (self.vram_address & !COARSE_Y) | (coarse_y << 5);
+    }
+
+    pub fn copy_horizontal_scroll(&mut self) {
+        self.vram_address = (self.vram_address & !HORIZONTAL_BITS) | (self.temp_vram_address & HORIZONTAL_BITS);
+    }
+
+    pub fn copy_vertical_scroll(&mut self) {
+        self.vram_address = (self.vram_address & !VERTICAL_BITS) | (self.temp_vram_address & VERTICAL_BITS);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    pub fn test_increment_coarse_x() {
+        let mut ppu = Ppu::new();
+
+        ppu.vram_address = 0x201e;
+        ppu.increment_coarse_x();
+        assert_eq!(ppu.vram_address, 0x201f);
+
+        // off the right of one nametable onto the left of the next
+        ppu.increment_coarse_x();
+        assert_eq!(ppu.vram_address, 0x2400);
+        ppu.vram_address = 0x241f;
+        ppu.increment_coarse_x();
+        assert_eq!(ppu.vram_address, 0x2000);
+    }
+
+    #[test]
+    pub fn test_increment_y() {
+        let mut ppu = Ppu::new();
+
+        ppu.vram_address = 0x6000;
+        ppu.increment_y();
+        assert_eq!(ppu.vram_address, 0x7000);
+
+        // fine y carries into coarse y
+        ppu.increment_y();
+        assert_eq!(ppu.vram_address, 0x0020);
+
+        // off the bottom of one nametable onto the top of the next
+        ppu.vram_address = 0x73a0;
+        ppu.increment_y();
+        assert_eq!(ppu.vram_address, 0x0800);
+
+        // but rows 30 and 31 just wrap
+        ppu.vram_address = 0x73e0;
+        ppu.increment_y();
+        assert_eq!(ppu.vram_address, 0x0000);
+    }
+
+    #[test]
+    pub fn test_copy_scroll() {
+        let mut ppu = Ppu::new();
+        ppu.vram_address = 0x7fff;
+        ppu.temp_vram_address = 0x0000;
+
+        ppu.copy_horizontal_scroll();
+        assert_eq!(ppu.vram_address, 0x7be0);
+
+        ppu.copy_vertical_scroll();
+        assert_eq!(ppu.vram_address, 0x0000);
+    }
+}
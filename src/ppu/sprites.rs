@@ -0,0 +1,505 @@
+use ::memory_map::Cartridge;
+use ::state::SaveState;
+use ::state::StateError;
+use ::state::StateReader;
+use ::state::StateWriter;
+
+use super::Ppu;
+use super::CTRL_SPRITE_SIZE;
+use super::CTRL_SPRITE_TABLE;
+use super::MASK_SHOW_SPRITES;
+use super::MASK_SHOW_SPRITES_LEFT;
+use super::PRE_RENDER_SCANLINE;
+use super::STATUS_SPRITE_OVERFLOW;
+
+// [Sprites]
+// oam holds 64 sprites of 4 bytes each:
+//
+// 0 => y, minus 1 (sprites are found a scanline early)
+// 1 => tile; for 8x16 sprites bit 0 picks the pattern table instead
+// 2 => attributes: vflip hflip priority - - - palette palette
+// 3 => x
+//
+// Each visible scanline looks for the sprites on it and draws them on the
+// next one, at most 8 at a time:
+//
+// dots 1-64    => secondary oam cleared to $ff
+// dots 65-256  => evaluation: up to 8 sprites copied into secondary oam
+// dots 257-320 => each of the 8 slots fetches its sprite's pattern row
+//                 (empty slots fetch tile $ff and stay transparent)
+//
+// Evaluation is done all at once at dot 65 here, rather than spread over the
+// dots it takes on hardware. The pre-render line does no evaluation, so
+// nothing is drawn on scanline 0.
+//
+// [Overflow]
+// After 8 sprites, evaluation carries on looking for a 9th to set the
+// overflow flag, but it's buggy: instead of checking each sprite's y, it
+// steps through the bytes as well as the sprites, so it checks the 9th
+// sprite's y, the 10th's tile, the 11th's attributes and so on. That gives
+// both false positives and misses.
+//
+// [Sprite 0 hit]
+// An opaque pixel of sprite 0 over an opaque background pixel sets the flag,
+// whatever the priority, except at x = 255 or where either is clipped off
+// the left 8 pixels. Games wait on it to split the screen mid-frame.
+//
+// [Resources]
+// oam => http://wiki.nesdev.com/w/index.php/PPU_OAM
+// evaluation => http://wiki.nesdev.com/w/index.php/PPU_sprite_evaluation
+// sprite 0 hit => http://wiki.nesdev.com/w/index.php/PPU_OAM#Sprite_zero_hits
+
+pub const SPRITE_SLOTS: usize = 8;
+pub const SECONDARY_OAM_SIZE: usize = SPRITE_SLOTS * 4;
+
+const OAM_SPRITES: usize = 64;
+
+const ATTRIBUTE_PALETTE: u8 = 0b0000_0011;
+const ATTRIBUTE_BEHIND_BACKGROUND: u8 = 0b0010_0000;
+const ATTRIBUTE_FLIP_HORIZONTAL: u8 = 0b0100_0000;
+const ATTRIBUTE_FLIP_VERTICAL: u8 = 0b1000_0000;
+
+// The tile empty slots fetch
+const EMPTY_SLOT_TILE: u8 = 0xff;
+
+// One of the 8 sprites being drawn on the current scanline, with its pattern
+// row already flipped if it needs to be
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Sprite {
+    x: u8,
+    attributes: u8,
+    pattern_lo: u8,
+    pattern_hi: u8,
+}
+
+// A sprite's pixel, as a palette index into the sprite palettes (16-31)
+pub struct SpritePixel {
+    pub pixel: u8,
+    pub behind_background: bool,
+    pub sprite_zero: bool,
+}
+
+impl Ppu {
+    // Does this dot's sprite work; only called while rendering is on
+    pub fn render_sprites<C: Cartridge>(&mut self, cartridge: &mut C) {
+        match self.dot {
+            64 if self.scanline != PRE_RENDER_SCANLINE => {
+                for byte in self.secondary_oam.iter_mut() {
+                    *byte = 0xff;
+                }
+            }
+            65 if self.scanline != PRE_RENDER_SCANLINE => self.evaluate_sprites(),
+            257..=320 => {
+                self.oam_address = 0;
+                self.fetch_sprite(cartridge);
+            }
+            _ => {}
+        }
+    }
+
+    // The sprites' pixel at x, if any of them has one
+    pub fn sprite_pixel(&self, x: u16) -> Option<SpritePixel> {
+        if self.mask & MASK_SHOW_SPRITES == 0 || (x < 8 && self.mask & MASK_SHOW_SPRITES_LEFT == 0) {
+            return None;
+        }
+
+        // lower slots win
+        for (slot, sprite) in self.sprites.iter().enumerate() {
+            let column = x.wrapping_sub(sprite.x as u16);
+            if column >= 8 {
+                continue;
+            }
+
+            let bit = 7 - column;
+            let pixel = ((sprite.pattern_lo >> bit) & 1) | (((sprite.pattern_hi >> bit) & 1) << 1);
+            if pixel == 0 {
+                continue;
+            }
+
+            return Some(SpritePixel {
+                pixel: 0x10 | ((sprite.attributes & ATTRIBUTE_PALETTE) << 2) | pixel,
+                behind_background: sprite.attributes & ATTRIBUTE_BEHIND_BACKGROUND != 0,
+                sprite_zero: slot == 0 && self.sprite_zero_loaded,
+            });
+        }
+
+        None
+    }
+
+    fn sprite_height(&self) -> u16 {
+        match self.ctrl & CTRL_SPRITE_SIZE {
+            0 => 8,
+            _ => 16,
+        }
+    }
+
+    fn evaluate_sprites(&mut self) {
+        let scanline = self.scanline;
+        let height = self.sprite_height();
+        let on_scanline = |y: u8| scanline.wrapping_sub(y as u16) < height;
+
+        let mut found = 0;
+        let mut byte = 0;
+        self.sprite_zero_found = false;
+
+        for sprite in 0..OAM_SPRITES {
+            let start = sprite * 4;
+
+            match found < SPRITE_SLOTS {
+                true => {
+                    if on_scanline(self.oam[start]) {
+                        self.secondary_oam[found * 4..found * 4 + 4].copy_from_slice(&self.oam[start..start + 4]);
+                        self.sprite_zero_found |= sprite == 0;
+                        found += 1;
+                    }
+                }
+                false => {
+                    if on_scanline(self.oam[start + byte]) {
+                        self.status |= STATUS_SPRITE_OVERFLOW;
+                        break;
+                    }
+
+                    // the bug: this should stay on y
+                    byte = (byte + 1) & 0b11;
+                }
+            }
+        }
+
+        self.sprites_found = found as u8;
+    }
+
+    // Each slot gets 8 dots: two nametable fetches nobody uses, then the two
+    // halves of its pattern row
+    fn fetch_sprite<C: Cartridge>(&mut self, cartridge: &mut C) {
+        let slot = (self.dot as usize - 257) / 8;
+
+        let found = match self.scanline {
+            PRE_RENDER_SCANLINE => 0,
+            _ => self.sprites_found as usize,
+        };
+
+        let (y, tile, attributes, x) = match slot < found {
+            true => {
+                let sprite = &self.secondary_oam[slot * 4..slot * 4 + 4];
+                (sprite[0], sprite[1], sprite[2], sprite[3])
+            }
+            false => (self.scanline as u8, EMPTY_SLOT_TILE, 0, 0xff),
+        };
+
+        match (self.dot - 257) % 8 {
+            0 => {
+                self.fetch_nametable_byte(cartridge);
+
+                self.sprites[slot] = Sprite {
+                    x,
+                    attributes,
+                    pattern_lo: 0,
+                    pattern_hi: 0,
+                };
+
+                if slot == 0 {
+                    self.sprite_zero_loaded = self.sprite_zero_found && found > 0;
+                }
+            }
+            2 => {
+                self.fetch_nametable_byte(cartridge);
+            }
+            4 | 6 => {
+                let plane = match (self.dot - 257) % 8 {
+                    4 => 0,
+                    _ => 8,
+                };
+
+                let address = self.sprite_pattern_address(tile, attributes, self.scanline.wrapping_sub(y as u16)) + plane;
+                let mut val = self.read_memory(cartridge, address);
+
+                if slot >= found {
+                    val = 0;
+                } else if attributes & ATTRIBUTE_FLIP_HORIZONTAL != 0 {
+                    val = val.reverse_bits();
+                }
+
+                match plane {
+                    0 => self.sprites[slot].pattern_lo = val,
+                    _ => self.sprites[slot].pattern_hi = val,
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn sprite_pattern_address(&self, tile: u8, attributes: u8, row: u16) -> u16 {
+        let height = self.sprite_height();
+
+        let row = match attributes & ATTRIBUTE_FLIP_VERTICAL {
+            0 => row,
+            _ => height - 1 - row,
+        } & (height - 1);
+
+        match height {
+            8 => {
+                let table = match self.ctrl & CTRL_SPRITE_TABLE {
+                    0 => 0x0000,
+                    _ => 0x1000,
+                };
+
+                table + (tile as u16) * 16 + row
+            }
+            // the bottom half is the next tile along
+            _ => {
+                let table = (tile as u16 & 1) * 0x1000;
+                let tile = tile as u16 & 0xfe;
+
+                table + tile * 16 + (row & 0b1000) * 2 + (row & 0b0111)
+            }
+        }
+    }
+}
+
+impl SaveState for Sprite {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.u8(self.x);
+        state.u8(self.attributes);
+        state.u8(self.pattern_lo);
+        state.u8(self.pattern_hi);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), StateError> {
+        self.x = state.u8();
+        self.attributes = state.u8();
+        self.pattern_lo = state.u8();
+        self.pattern_hi = state.u8();
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use ::memory_map::NROMMemoryMap;
+    use ::ppu::MirroringType;
+    use ::ppu::FRAME_WIDTH;
+    use ::ppu::MASK_SHOW_BACKGROUND;
+    use ::ppu::MASK_SHOW_BACKGROUND_LEFT;
+    use ::ppu::PPU_STATUS;
+    use ::ppu::STATUS_SPRITE_0_HIT;
+    use ::memory_map;
+
+    const BACKDROP: u8 = 0x0f;
+    const BACKGROUND: u8 = 0x01;
+    const SPRITE_PALETTE_0: u8 = 0x11;
+    const SPRITE_PALETTE_1: u8 = 0x15;
+
+    const SOLID_TILE: u8 = 1;
+    const LEFT_COLUMN_TILE: u8 = 2;
+    const TOP_ROW_TILE: u8 = 3;
+
+    const ALL_SPRITES: u8 = MASK_SHOW_SPRITES | MASK_SHOW_SPRITES_LEFT;
+    const EVERYTHING: u8 = ALL_SPRITES | MASK_SHOW_BACKGROUND | MASK_SHOW_BACKGROUND_LEFT;
+
+    fn write_tile(cartridge: &mut NROMMemoryMap, address: u16, rows: [u8; 8]) {
+        for (row, val) in rows.iter().enumerate() {
+            cartridge.ppu_write(address + row as u16, *val);
+        }
+    }
+
+    // No sprites on screen, a few tiles to draw them with, and colour 1 of
+    // background palette 0 and sprite palettes 0 and 1 set up
+    fn new_ppu() -> (Ppu, NROMMemoryMap) {
+        let mut rom = memory_map::numbered_rom(1, memory_map::PRG_ROM_BANK_SIZE, 0, memory_map::CHR_SIZE);
+        rom.mirroring_type = MirroringType::Vertical;
+
+        let mut cartridge: NROMMemoryMap = memory_map::load_cartridge(&rom);
+
+        write_tile(&mut cartridge, 0x0010, [0xff; 8]);
+        write_tile(&mut cartridge, 0x0020, [0b1000_0000; 8]);
+        write_tile(&mut cartridge, 0x0030, [0xff, 0, 0, 0, 0, 0, 0, 0]);
+
+        let mut ppu = Ppu::new();
+        ppu.palette[0x00] = BACKDROP;
+        ppu.palette[0x01] = BACKGROUND;
+        ppu.palette[0x11] = SPRITE_PALETTE_0;
+        ppu.palette[0x15] = SPRITE_PALETTE_1;
+
+        for byte in ppu.oam.iter_mut() {
+            *byte = 0xff;
+        }
+
+        (ppu, cartridge)
+    }
+
+    fn set_sprite(ppu: &mut Ppu, sprite: usize, x: u8, y: u8, tile: u8, attributes: u8) {
+        ppu.oam[sprite * 4..sprite * 4 + 4].copy_from_slice(&[y, tile, attributes, x]);
+    }
+
+    // Runs the frame with the first one (which starts part way through) out
+    // of the way
+    fn run_frames(ppu: &mut Ppu, cartridge: &mut NROMMemoryMap, mask: u8) {
+        ppu.mask = mask;
+
+        for _ in 0..2 {
+            let frames = ppu.frames();
+            while ppu.frames() == frames {
+                ppu.tick(cartridge);
+            }
+        }
+    }
+
+    fn pixel(ppu: &Ppu, x: usize, y: usize) -> u8 {
+        ppu.frame()[y * FRAME_WIDTH + x]
+    }
+
+    fn sprite_zero_hit(ppu: &Ppu) -> bool {
+        ppu.peek_register(PPU_STATUS) & STATUS_SPRITE_0_HIT != 0
+    }
+
+    fn evaluate_on(ppu: &mut Ppu, scanline: u16) {
+        ppu.scanline = scanline;
+        ppu.status = 0;
+        ppu.evaluate_sprites();
+    }
+
+    #[test]
+    pub fn test_draws_sprites() {
+        let (mut ppu, mut cartridge) = new_ppu();
+
+        // sprite 0 wins over sprite 1 where they overlap
+        set_sprite(&mut ppu, 0, 10, 20, SOLID_TILE, 1);
+        set_sprite(&mut ppu, 1, 14, 20, SOLID_TILE, 0);
+        run_frames(&mut ppu, &mut cartridge, ALL_SPRITES);
+
+        assert_eq!(pixel(&ppu, 10, 20), BACKDROP);
+        assert_eq!(pixel(&ppu, 9, 21), BACKDROP);
+        assert_eq!(pixel(&ppu, 10, 21), SPRITE_PALETTE_1);
+        assert_eq!(pixel(&ppu, 17, 28), SPRITE_PALETTE_1);
+        assert_eq!(pixel(&ppu, 18, 28), SPRITE_PALETTE_0);
+        assert_eq!(pixel(&ppu, 21, 28), SPRITE_PALETTE_0);
+        assert_eq!(pixel(&ppu, 22, 28), BACKDROP);
+        assert_eq!(pixel(&ppu, 10, 29), BACKDROP);
+    }
+
+    #[test]
+    pub fn test_flipping() {
+        let (mut ppu, mut cartridge) = new_ppu();
+
+        set_sprite(&mut ppu, 0, 10, 20, LEFT_COLUMN_TILE, ATTRIBUTE_FLIP_HORIZONTAL);
+        set_sprite(&mut ppu, 1, 30, 20, TOP_ROW_TILE, ATTRIBUTE_FLIP_VERTICAL);
+        run_frames(&mut ppu, &mut cartridge, ALL_SPRITES);
+
+        assert_eq!(pixel(&ppu, 10, 21), BACKDROP);
+        assert_eq!(pixel(&ppu, 17, 21), SPRITE_PALETTE_0);
+
+        assert_eq!(pixel(&ppu, 30, 21), BACKDROP);
+        assert_eq!(pixel(&ppu, 30, 28), SPRITE_PALETTE_0);
+    }
+
+    #[test]
+    pub fn test_8x16_sprites() {
+        let (mut ppu, mut cartridge) = new_ppu();
+        ppu.ctrl = CTRL_SPRITE_SIZE;
+
+        // tile 3 is tiles 2 and 3 of the $1000 table
+        write_tile(&mut cartridge, 0x1030, [0xff, 0, 0, 0, 0, 0, 0, 0]);
+        set_sprite(&mut ppu, 0, 10, 20, 0x03, 0);
+        set_sprite(&mut ppu, 1, 30, 20, 0x03, ATTRIBUTE_FLIP_VERTICAL);
+        run_frames(&mut ppu, &mut cartridge, ALL_SPRITES);
+
+        assert_eq!(pixel(&ppu, 10, 21), BACKDROP);
+        assert_eq!(pixel(&ppu, 10, 29), SPRITE_PALETTE_0);
+
+        // flipping swaps the halves too
+        assert_eq!(pixel(&ppu, 30, 28), SPRITE_PALETTE_0);
+        assert_eq!(pixel(&ppu, 30, 29), BACKDROP);
+    }
+
+    #[test]
+    pub fn test_priority() {
+        let (mut ppu, mut cartridge) = new_ppu();
+        ppu.vram[0] = SOLID_TILE;
+
+        set_sprite(&mut ppu, 0, 4, 0, SOLID_TILE, ATTRIBUTE_BEHIND_BACKGROUND);
+        set_sprite(&mut ppu, 1, 4, 0, LEFT_COLUMN_TILE, 0);
+        set_sprite(&mut ppu, 2, 20, 0, SOLID_TILE, ATTRIBUTE_BEHIND_BACKGROUND);
+        run_frames(&mut ppu, &mut cartridge, EVERYTHING);
+
+        // sprite 0 is behind the background, and still hides sprite 1 there
+        assert_eq!(pixel(&ppu, 4, 1), BACKGROUND);
+        assert_eq!(pixel(&ppu, 8, 1), SPRITE_PALETTE_0);
+
+        // behind the background only matters where the background is opaque
+        assert_eq!(pixel(&ppu, 20, 1), SPRITE_PALETTE_0);
+    }
+
+    #[test]
+    pub fn test_sprite_zero_hit() {
+        let (mut ppu, mut cartridge) = new_ppu();
+        ppu.vram[0] = SOLID_TILE;
+        ppu.vram[31] = SOLID_TILE;
+
+        // whatever the priority
+        set_sprite(&mut ppu, 0, 4, 0, SOLID_TILE, ATTRIBUTE_BEHIND_BACKGROUND);
+        run_frames(&mut ppu, &mut cartridge, EVERYTHING);
+        assert!(sprite_zero_hit(&ppu));
+
+        // only sprite 0 counts
+        set_sprite(&mut ppu, 0, 100, 100, SOLID_TILE, 0);
+        set_sprite(&mut ppu, 1, 4, 0, SOLID_TILE, 0);
+        run_frames(&mut ppu, &mut cartridge, EVERYTHING);
+        assert!(!sprite_zero_hit(&ppu));
+
+        // nothing at x = 255
+        set_sprite(&mut ppu, 0, 255, 0, SOLID_TILE, 0);
+        run_frames(&mut ppu, &mut cartridge, EVERYTHING);
+        assert!(!sprite_zero_hit(&ppu));
+
+        // or where either is clipped on the left
+        set_sprite(&mut ppu, 0, 0, 0, LEFT_COLUMN_TILE, ATTRIBUTE_FLIP_HORIZONTAL);
+        run_frames(&mut ppu, &mut cartridge, EVERYTHING & !MASK_SHOW_SPRITES_LEFT);
+        assert!(!sprite_zero_hit(&ppu));
+        run_frames(&mut ppu, &mut cartridge, EVERYTHING & !MASK_SHOW_BACKGROUND_LEFT);
+        assert!(!sprite_zero_hit(&ppu));
+        run_frames(&mut ppu, &mut cartridge, EVERYTHING);
+        assert!(sprite_zero_hit(&ppu));
+
+        // or with the background off
+        run_frames(&mut ppu, &mut cartridge, ALL_SPRITES);
+        assert!(!sprite_zero_hit(&ppu));
+    }
+
+    #[test]
+    pub fn test_eight_sprites_a_scanline() {
+        let (mut ppu, _) = new_ppu();
+
+        for sprite in 0..8 {
+            set_sprite(&mut ppu, sprite, 0, 10, 0, 0);
+        }
+        evaluate_on(&mut ppu, 10);
+        assert_eq!(ppu.sprites_found, 8);
+        assert_eq!(ppu.status & STATUS_SPRITE_OVERFLOW, 0);
+
+        set_sprite(&mut ppu, 20, 0, 10, 0, 0);
+        evaluate_on(&mut ppu, 10);
+        assert_eq!(ppu.sprites_found, 8);
+        assert_eq!(ppu.status & STATUS_SPRITE_OVERFLOW, STATUS_SPRITE_OVERFLOW);
+    }
+
+    #[test]
+    pub fn test_overflow_bug() {
+        let (mut ppu, _) = new_ppu();
+
+        for sprite in 0..8 {
+            set_sprite(&mut ppu, sprite, 0, 10, 0, 0);
+        }
+
+        // the 10th sprite's tile gets checked as if it were a y
+        set_sprite(&mut ppu, 9, 0xff, 0xff, 5, 0);
+        evaluate_on(&mut ppu, 10);
+        assert_eq!(ppu.status & STATUS_SPRITE_OVERFLOW, STATUS_SPRITE_OVERFLOW);
+
+        // and so its y doesn't
+        set_sprite(&mut ppu, 9, 0xff, 10, 0xff, 0);
+        evaluate_on(&mut ppu, 10);
+        assert_eq!(ppu.status & STATUS_SPRITE_OVERFLOW, 0);
+    }
+}
@@ -1,101 +1,558 @@
-use ::bits;
-use ::ppu;
-
-use std::fs::File;
-use std::io::Read;
-use std::fmt;
-
-#[derive(Default)]
-#[allow(dead_code)]
-pub struct NesRom {
-    pub prg_rom: Vec<u8>,
-
-    identifier: Vec<u8>,
-    format: u8,
-
-    pub num_prg_banks: u8,
-    pub num_chr_banks: u8,
-    pub num_ram_banks: u8,
-    pub mirroring_type: ppu::MirroringType,
-    has_battery_backed_ram: bool,
-    has_trainer: bool,
-    mapper_number: u8,
-}
-
-impl NesRom {
-    pub fn from_nes_file(mut nes_file: File) -> Self {
-        fn take_one_byte(slice: &[u8]) -> u8 {
-            slice[0]
-        }
-
-        let mut buf: Vec<u8> = vec![];
-        nes_file.read_to_end(&mut buf).unwrap();
-
-        let header_size = 16;
-        let header = &buf[0..header_size];
-
-        // TODO verify identifier eq 'NES'
-        let identifier = &header[0..3];
-
-        // TODO verify identifier eq 1A
-        let format = take_one_byte(&header[3..4]);
-
-        let num_prg_banks = take_one_byte(&header[4..5]);
-        let num_chr_banks = take_one_byte(&header[5..6]);
-        let control_byte_one = take_one_byte(&header[6..7]);
-        let control_byte_two = take_one_byte(&header[7..8]);
-        let num_ram_banks = take_one_byte(&header[8..9]);
-
-        // TODO actually verify these are all 0
-        // let future_usage = &header[9..16];
-
-        let mirroring_type = NesRom::get_mirroring_type(control_byte_one);
-        let has_battery_backed_ram = (control_byte_one & 0b10) >> 1 == 1;
-        let has_trainer = (control_byte_one & 0b100) >> 2 == 1;
-        let mapper_number = NesRom::get_maper_number(control_byte_one, control_byte_two);
-
-        let prg_rom_banks = match num_prg_banks {
-            0 => panic!("no prg_rom banks found in header!"),
-            _ => &buf[header_size..header_size + (((num_prg_banks as u16)*0x4000) as usize)]   
-        };
-
-        NesRom {
-            prg_rom: Vec::from(prg_rom_banks),
-            identifier: Vec::from(identifier),
-            format: format,
-            num_prg_banks: num_prg_banks,
-            num_chr_banks: num_chr_banks,
-            num_ram_banks: num_ram_banks,
-            mirroring_type: mirroring_type,
-            has_battery_backed_ram: has_battery_backed_ram,
-            has_trainer: has_trainer,
-            mapper_number: mapper_number,
-        }
-    }
-
-    fn get_mirroring_type(control_byte_one: u8) -> ppu::MirroringType {
-        match (control_byte_one & 0b1000) >> 3 == 1 {
-            true => ppu::MirroringType::Both,
-            _ => {
-                match control_byte_one & 1 == 1 {
-                    false => ppu::MirroringType::Horizontal,
-                    _ => ppu::MirroringType::Vertical,
-                }
-            }
-        }
-    }
-
-    fn get_maper_number(control_byte_one: u8, control_byte_two: u8) -> u8 {
-        bits::overlay(control_byte_two, control_byte_one)
-    }
-}
-
-impl fmt::Debug for NesRom {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f,
-               "NesRom {{ has_trainer: {}, mapper_number: {}, num_prg_banks: {} }}",
-               self.has_trainer,
-               self.mapper_number,
-               self.num_prg_banks)
-    }
-}
\ No newline at end of file
+use ::bits;
+use ::memory_map::PRG_ROM_BANK_SIZE;
+use ::ppu;
+
+use std::error;
+use std::fs::File;
+use std::io;
+use std::io::Read;
+use std::fmt;
+
+// [Rom]
+// Loads .nes files in the iNES format: a 16 byte header, an optional 512 byte
+// trainer, the prg rom banks, and then the chr rom banks. Carts without chr
+// rom have chr ram instead, which gets allocated here so there's always
+// something for the ppu to draw from.
+//
+// NES 2.0 headers (flagged by 0b10 in bits 2-3 of byte 7) use bytes 8-15 to
+// describe the cartridge properly: bigger mapper numbers plus a submapper,
+// bigger roms, exact ram sizes, and what console and timing it's for. Plain
+// iNES headers just get the defaults for all of that.
+//
+// [Resources]
+// iNES => http://wiki.nesdev.com/w/index.php/INES
+// NES 2.0 => http://wiki.nesdev.com/w/index.php/NES_2.0
+
+const HEADER_SIZE: usize = 16;
+const TRAINER_SIZE: usize = 512;
+const CHR_ROM_BANK_SIZE: usize = 0x2000;
+const PRG_RAM_BANK_SIZE: usize = 0x2000;
+const CRC32_POLYNOMIAL: u32 = 0xedb8_8320;
+
+// "NES" followed by an MS-DOS end of file
+const MAGIC: &[u8] = b"NES\x1a";
+
+#[derive(Debug)]
+pub enum RomError {
+    // The file doesn't start with "NES\x1a"
+    BadMagic,
+
+    // The file ends before the header says it should
+    Truncated { expected: usize, actual: usize },
+
+    // We don't have a memory map for the rom's mapper
+    UnsupportedMapper(u16),
+
+    // The header asks for a number of banks that can't work (e.g. no prg rom)
+    InvalidBankCount,
+
+    Io(io::Error),
+}
+
+impl fmt::Display for RomError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            RomError::BadMagic => write!(f, "not an iNES file"),
+            RomError::Truncated { expected, actual } => write!(f, "rom is truncated: expected {} bytes, got {}", expected, actual),
+            RomError::UnsupportedMapper(mapper_number) => write!(f, "mapper {} isn't supported", mapper_number),
+            RomError::InvalidBankCount => write!(f, "rom has no prg rom"),
+            RomError::Io(ref error) => write!(f, "couldn't read rom: {}", error),
+        }
+    }
+}
+
+impl error::Error for RomError {}
+
+impl From<io::Error> for RomError {
+    fn from(error: io::Error) -> Self {
+        RomError::Io(error)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RomFormat {
+    #[default]
+    INes,
+    Nes2,
+}
+
+// Which video standard the rom was made for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Timing {
+    #[default]
+    Ntsc,
+    Pal,
+    // Works on both
+    Multi,
+    Dendy,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ConsoleType {
+    #[default]
+    Nes,
+    VsSystem { ppu_type: u8, hardware_type: u8 },
+    Playchoice10,
+    // One of the NES 2.0 extended console types (famiclones, etc)
+    Extended(u8),
+}
+
+#[derive(Default)]
+#[allow(dead_code)]
+pub struct NesRom {
+    pub prg_rom: Vec<u8>,
+
+    // Pattern tables, in 8 KiB banks
+    pub chr_rom: Vec<u8>,
+
+    // What the ppu gets to draw from when the cartridge has no chr rom
+    pub chr_ram: Vec<u8>,
+
+    // 512 bytes that get loaded at $7000 before the game starts
+    pub trainer: Option<Vec<u8>>,
+
+    identifier: Vec<u8>,
+    pub format: RomFormat,
+
+    pub num_prg_banks: u16,
+    pub num_chr_banks: u16,
+    pub num_ram_banks: u8,
+    pub mirroring_type: ppu::MirroringType,
+    has_battery_backed_ram: bool,
+    has_trainer: bool,
+    mapper_number: u16,
+    pub submapper_number: u8,
+
+    // Sizes in bytes. iNES headers can only describe the rom sizes and prg
+    // ram, so the rest are filled in with what iNES roms are assumed to have.
+    pub prg_rom_size: usize,
+    pub chr_rom_size: usize,
+    pub prg_ram_size: usize,
+    pub prg_nvram_size: usize,
+    pub chr_ram_size: usize,
+    pub chr_nvram_size: usize,
+
+    pub timing: Timing,
+    pub console_type: ConsoleType,
+    pub num_misc_roms: u8,
+    pub default_expansion_device: u8,
+}
+
+impl NesRom {
+    pub fn from_nes_file(nes_file: File) -> Result<Self, RomError> {
+        NesRom::from_reader(nes_file)
+    }
+
+    pub fn from_reader<R: Read>(mut reader: R) -> Result<Self, RomError> {
+        let mut buf: Vec<u8> = vec![];
+        reader.read_to_end(&mut buf)?;
+
+        NesRom::from_bytes(&buf)
+    }
+
+    pub fn from_bytes(buf: &[u8]) -> Result<Self, RomError> {
+        fn take_one_byte(slice: &[u8]) -> u8 {
+            slice[0]
+        }
+
+        let header = match buf.get(0..HEADER_SIZE) {
+            Some(header) => header,
+            None => {
+                return Err(RomError::Truncated {
+                    expected: HEADER_SIZE,
+                    actual: buf.len(),
+                })
+            }
+        };
+
+        let identifier = &header[0..4];
+        if identifier != MAGIC {
+            return Err(RomError::BadMagic);
+        }
+
+        let control_byte_one = take_one_byte(&header[6..7]);
+        let control_byte_two = take_one_byte(&header[7..8]);
+
+        let format = match control_byte_two & 0b1100 {
+            0b1000 => RomFormat::Nes2,
+            _ => RomFormat::INes,
+        };
+
+        let mirroring_type = NesRom::get_mirroring_type(control_byte_one);
+        let has_battery_backed_ram = (control_byte_one & 0b10) >> 1 == 1;
+        let has_trainer = (control_byte_one & 0b100) >> 2 == 1;
+
+        let mut rom = NesRom {
+            identifier: Vec::from(&identifier[0..3]),
+            format,
+            mirroring_type,
+            has_battery_backed_ram,
+            has_trainer,
+            ..NesRom::default()
+        };
+
+        match format {
+            RomFormat::INes => rom.read_ines_header(header),
+            RomFormat::Nes2 => rom.read_nes2_header(header),
+        }
+
+        if rom.prg_rom_size == 0 {
+            return Err(RomError::InvalidBankCount);
+        }
+
+        rom.num_prg_banks = banks(rom.prg_rom_size, PRG_ROM_BANK_SIZE) as u16;
+        rom.num_chr_banks = banks(rom.chr_rom_size, CHR_ROM_BANK_SIZE) as u16;
+
+        // the trainer (if any) sits between the header and prg rom, and chr
+        // rom comes right after prg rom
+        let trainer_size = match has_trainer {
+            true => TRAINER_SIZE,
+            false => 0,
+        };
+
+        let prg_rom_start = HEADER_SIZE + trainer_size;
+        let chr_rom_start = prg_rom_start.saturating_add(rom.prg_rom_size);
+        let expected = chr_rom_start.saturating_add(rom.chr_rom_size);
+
+        if buf.len() < expected {
+            return Err(RomError::Truncated {
+                expected,
+                actual: buf.len(),
+            });
+        }
+
+        if has_trainer {
+            rom.trainer = Some(Vec::from(&buf[HEADER_SIZE..prg_rom_start]));
+        }
+
+        rom.prg_rom = Vec::from(&buf[prg_rom_start..chr_rom_start]);
+        rom.chr_rom = Vec::from(&buf[chr_rom_start..expected]);
+        rom.chr_ram = vec![0; rom.chr_ram_size + rom.chr_nvram_size];
+
+        Ok(rom)
+    }
+
+    pub fn mapper_number(&self) -> u16 {
+        self.mapper_number
+    }
+
+    pub fn has_battery_backed_ram(&self) -> bool {
+        self.has_battery_backed_ram
+    }
+
+    // A CRC-32 of the prg and chr rom, which is enough to tell games apart
+    pub fn checksum(&self) -> u32 {
+        !self.prg_rom.iter().chain(self.chr_rom.iter()).fold(!0, |crc, byte| crc32_byte(crc, *byte))
+    }
+
+    fn read_ines_header(&mut self, header: &[u8]) {
+        let num_prg_banks = header[4] as usize;
+        let num_chr_banks = header[5] as usize;
+        let num_ram_banks = header[8];
+
+        // Some old dumping tools left their name in bytes 7-15, which makes
+        // garbage of the upper mapper nibble; real iNES headers zero 12-15
+        let mapper_number = match header[12..16].iter().all(|byte| *byte == 0) {
+            true => NesRom::get_maper_number(header[6], header[7]),
+            false => (header[6] >> 4) as u16,
+        };
+
+        self.mapper_number = mapper_number;
+        self.prg_rom_size = num_prg_banks * PRG_ROM_BANK_SIZE;
+        self.chr_rom_size = num_chr_banks * CHR_ROM_BANK_SIZE;
+        self.num_ram_banks = num_ram_banks;
+
+        // 0 ram banks means 1, for compatibility with roms from before the
+        // field existed
+        self.prg_ram_size = (num_ram_banks.max(1) as usize) * PRG_RAM_BANK_SIZE;
+        self.chr_ram_size = match num_chr_banks {
+            0 => CHR_ROM_BANK_SIZE,
+            _ => 0,
+        };
+
+        self.timing = match header[9] & 1 {
+            0 => Timing::Ntsc,
+            _ => Timing::Pal,
+        };
+
+        self.console_type = match header[7] & 0b11 {
+            1 => {
+                ConsoleType::VsSystem {
+                    ppu_type: 0,
+                    hardware_type: 0,
+                }
+            }
+            2 => ConsoleType::Playchoice10,
+            _ => ConsoleType::Nes,
+        };
+    }
+
+    fn read_nes2_header(&mut self, header: &[u8]) {
+        self.mapper_number = ((header[8] as u16 & 0x0f) << 8) | NesRom::get_maper_number(header[6], header[7]);
+        self.submapper_number = header[8] >> 4;
+
+        self.prg_rom_size = nes2_rom_size(header[4], header[9] & 0x0f, PRG_ROM_BANK_SIZE);
+        self.chr_rom_size = nes2_rom_size(header[5], header[9] >> 4, CHR_ROM_BANK_SIZE);
+
+        self.prg_ram_size = nes2_ram_size(header[10] & 0x0f);
+        self.prg_nvram_size = nes2_ram_size(header[10] >> 4);
+        self.chr_ram_size = nes2_ram_size(header[11] & 0x0f);
+        self.chr_nvram_size = nes2_ram_size(header[11] >> 4);
+
+        // a board has to have chr of some kind, so no chr rom and no chr ram
+        // means the usual 8 KiB of ram, same as iNES
+        if self.chr_rom_size == 0 && self.chr_ram_size + self.chr_nvram_size == 0 {
+            self.chr_ram_size = CHR_ROM_BANK_SIZE;
+        }
+        self.num_ram_banks = banks(self.prg_ram_size + self.prg_nvram_size, PRG_RAM_BANK_SIZE) as u8;
+
+        self.timing = match header[12] & 0b11 {
+            0 => Timing::Ntsc,
+            1 => Timing::Pal,
+            2 => Timing::Multi,
+            _ => Timing::Dendy,
+        };
+
+        self.console_type = match header[7] & 0b11 {
+            0 => ConsoleType::Nes,
+            1 => {
+                ConsoleType::VsSystem {
+                    ppu_type: header[13] & 0x0f,
+                    hardware_type: header[13] >> 4,
+                }
+            }
+            2 => ConsoleType::Playchoice10,
+            _ => ConsoleType::Extended(header[13] & 0x0f),
+        };
+
+        self.num_misc_roms = header[14] & 0b11;
+        self.default_expansion_device = header[15] & 0b0011_1111;
+    }
+
+    fn get_mirroring_type(control_byte_one: u8) -> ppu::MirroringType {
+        match (control_byte_one & 0b1000) >> 3 == 1 {
+            true => ppu::MirroringType::FourScreen,
+            _ => {
+                match control_byte_one & 1 == 1 {
+                    false => ppu::MirroringType::Horizontal,
+                    _ => ppu::MirroringType::Vertical,
+                }
+            }
+        }
+    }
+
+    fn get_maper_number(control_byte_one: u8, control_byte_two: u8) -> u16 {
+        bits::overlay(control_byte_two, control_byte_one) as u16
+    }
+}
+
+// Number of bank_size banks it takes to hold size bytes
+// The usual reflected CRC-32 (as in zip and the No-Intro databases), a byte at
+// a time
+fn crc32_byte(crc: u32, byte: u8) -> u32 {
+    (0..8).fold(crc ^ byte as u32, |crc, _| {
+        match crc & 1 {
+            1 => (crc >> 1) ^ CRC32_POLYNOMIAL,
+            _ => crc >> 1,
+        }
+    })
+}
+
+fn banks(size: usize, bank_size: usize) -> usize {
+    size.div_ceil(bank_size)
+}
+
+// NES 2.0 rom sizes are a 12 bit bank count, unless the top nibble is all
+// ones, in which case the low byte is EEEEEEMM and the size in bytes is
+// 2^E * (MM * 2 + 1)
+fn nes2_rom_size(lsb: u8, msb: u8, bank_size: usize) -> usize {
+    match msb {
+        0x0f => {
+            let exponent = (lsb >> 2) as u32;
+            let multiplier = (lsb & 0b11) as usize * 2 + 1;
+
+            2usize.checked_pow(exponent).map_or(usize::MAX, |size| size.saturating_mul(multiplier))
+        }
+        _ => (((msb as usize) << 8) | lsb as usize) * bank_size,
+    }
+}
+
+// NES 2.0 ram sizes are shift counts: 0 means none, otherwise 64 << shift
+fn nes2_ram_size(shift: u8) -> usize {
+    match shift {
+        0 => 0,
+        _ => 64 << shift,
+    }
+}
+
+impl fmt::Debug for NesRom {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f,
+               "NesRom {{ has_trainer: {}, mapper_number: {}, num_prg_banks: {} }}",
+               self.has_trainer,
+               self.mapper_number,
+               self.num_prg_banks)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn header(num_prg_banks: u8) -> Vec<u8> {
+        let mut header = vec![0; HEADER_SIZE];
+        header[0..4].copy_from_slice(MAGIC);
+        header[4] = num_prg_banks;
+
+        header
+    }
+
+    #[test]
+    pub fn test_loads_prg_rom() {
+        let mut buf = header(1);
+        buf.extend(vec![0xea; PRG_ROM_BANK_SIZE]);
+
+        let rom = NesRom::from_reader(io::Cursor::new(buf)).unwrap();
+        assert_eq!(rom.num_prg_banks, 1);
+        assert_eq!(rom.prg_rom.len(), PRG_ROM_BANK_SIZE);
+        assert_eq!(rom.mapper_number(), 0);
+    }
+
+    #[test]
+    pub fn test_loads_trainer_and_chr_rom() {
+        let mut buf = header(1);
+        buf[5] = 1;
+        buf[6] = 0b0000_0100;
+        buf.extend(vec![0x11; TRAINER_SIZE]);
+        buf.extend(vec![0x22; PRG_ROM_BANK_SIZE]);
+        buf.extend(vec![0x33; CHR_ROM_BANK_SIZE]);
+
+        let rom = NesRom::from_bytes(&buf).unwrap();
+        assert_eq!(rom.trainer, Some(vec![0x11; TRAINER_SIZE]));
+        assert_eq!(rom.prg_rom, vec![0x22; PRG_ROM_BANK_SIZE]);
+        assert_eq!(rom.chr_rom, vec![0x33; CHR_ROM_BANK_SIZE]);
+        assert!(rom.chr_ram.is_empty());
+
+        // chr rom missing its last byte
+        match NesRom::from_bytes(&buf[..buf.len() - 1]) {
+            Err(RomError::Truncated { expected, .. }) => assert_eq!(expected, buf.len()),
+            other => panic!("expected truncated chr rom, got {:?}", other),
+        }
+    }
+
+    #[test]
+    pub fn test_allocates_chr_ram_without_chr_rom() {
+        let mut buf = header(1);
+        buf.extend(vec![0; PRG_ROM_BANK_SIZE]);
+
+        let rom = NesRom::from_bytes(&buf).unwrap();
+        assert_eq!(rom.trainer, None);
+        assert!(rom.chr_rom.is_empty());
+        assert_eq!(rom.chr_ram.len(), CHR_ROM_BANK_SIZE);
+    }
+
+    #[test]
+    pub fn test_ines_header() {
+        let mut buf = header(1);
+        buf[6] = 0b0001_0010;
+        buf[7] = 0b0100_0000;
+        buf.extend(vec![0; PRG_ROM_BANK_SIZE]);
+
+        let rom = NesRom::from_bytes(&buf).unwrap();
+        assert_eq!(rom.format, RomFormat::INes);
+        assert_eq!(rom.mapper_number(), 0x41);
+        assert_eq!(rom.prg_ram_size, 0x2000);
+        assert_eq!(rom.chr_ram_size, 0x2000);
+
+        // junk in 12-15 means the upper mapper nibble can't be trusted
+        buf[12..16].copy_from_slice(b"Dude");
+        assert_eq!(NesRom::from_bytes(&buf).unwrap().mapper_number(), 0x01);
+    }
+
+    #[test]
+    pub fn test_nes2_header() {
+        let mut buf = header(2);
+        buf[5] = 1;
+        buf[6] = 0b0100_0000;
+        buf[7] = 0b0001_1001;
+        buf[8] = 0b0011_0001;
+        buf[10] = 0x70;
+        buf[11] = 0x07;
+        buf[12] = 3;
+        buf[13] = 0x21;
+        buf[15] = 0x01;
+        buf.extend(vec![0; 2 * PRG_ROM_BANK_SIZE + CHR_ROM_BANK_SIZE]);
+
+        let rom = NesRom::from_bytes(&buf).unwrap();
+        assert_eq!(rom.format, RomFormat::Nes2);
+        assert_eq!(rom.mapper_number(), 0x114);
+        assert_eq!(rom.submapper_number, 3);
+        assert_eq!(rom.prg_rom_size, 0x8000);
+        assert_eq!(rom.chr_rom_size, 0x2000);
+        assert_eq!(rom.prg_ram_size, 0);
+        assert_eq!(rom.prg_nvram_size, 0x2000);
+        assert_eq!(rom.chr_ram_size, 0x2000);
+        assert_eq!(rom.timing, Timing::Dendy);
+        assert_eq!(rom.console_type,
+                   ConsoleType::VsSystem {
+                       ppu_type: 1,
+                       hardware_type: 2,
+                   });
+        assert_eq!(rom.default_expansion_device, 1);
+    }
+
+    #[test]
+    pub fn test_nes2_header_without_any_chr() {
+        let mut buf = header(1);
+        buf[7] = 0b0000_1000;
+        buf.extend(vec![0; PRG_ROM_BANK_SIZE]);
+
+        let rom = NesRom::from_bytes(&buf).unwrap();
+        assert_eq!(rom.format, RomFormat::Nes2);
+        assert_eq!(rom.chr_ram.len(), CHR_ROM_BANK_SIZE);
+    }
+
+    #[test]
+    pub fn test_checksum() {
+        let rom = NesRom {
+            prg_rom: b"1234".to_vec(),
+            chr_rom: b"56789".to_vec(),
+            ..NesRom::default()
+        };
+
+        assert_eq!(rom.checksum(), 0xcbf4_3926);
+    }
+
+    #[test]
+    pub fn test_nes2_exponent_sizes() {
+        // 2^14 * 3 bytes
+        assert_eq!(nes2_rom_size(0b0011_1001, 0x0f, PRG_ROM_BANK_SIZE), 0xc000);
+        assert_eq!(nes2_rom_size(0x02, 0x01, PRG_ROM_BANK_SIZE), 0x102 * PRG_ROM_BANK_SIZE);
+    }
+
+    #[test]
+    pub fn test_rejects_bad_files() {
+        let mut bad_magic = header(1);
+        bad_magic[3] = 0;
+
+        match NesRom::from_bytes(&bad_magic) {
+            Err(RomError::BadMagic) => {}
+            other => panic!("expected BadMagic, got {:?}", other),
+        }
+
+        match NesRom::from_bytes(&header(0)) {
+            Err(RomError::InvalidBankCount) => {}
+            other => panic!("expected InvalidBankCount, got {:?}", other),
+        }
+
+        match NesRom::from_bytes(&header(1)[0..10]) {
+            Err(RomError::Truncated { expected: 16, actual: 10 }) => {}
+            other => panic!("expected a truncated header, got {:?}", other),
+        }
+
+        match NesRom::from_bytes(&header(2)) {
+            Err(RomError::Truncated { expected: 0x8010, actual: 16 }) => {}
+            other => panic!("expected truncated prg rom, got {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,145 @@
+use ::memory_map::Cartridge;
+
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::path::PathBuf;
+
+// [Save files]
+// Carts with a battery keep their prg ram when the console is off, which is
+// how RPGs save. We keep it in a .sav file next to the rom (or in a save
+// directory of your choosing) holding nothing but the raw ram.
+//
+// The save is written out every so often while running, and again when the
+// Nes goes away, so killing the emulator loses at most a few seconds. Writes
+// go to a temporary file that's renamed over the real one, so a crash mid
+// write doesn't leave half a save behind.
+//
+// [Resources]
+// battery-backed ram => http://wiki.nesdev.com/w/index.php/PRG_RAM_circuit
+
+pub struct SaveFile {
+    path: PathBuf,
+
+    // What's on disk, so we only write when the game changed something
+    saved: Vec<u8>,
+}
+
+impl SaveFile {
+    pub fn new(path: PathBuf) -> Self {
+        SaveFile {
+            path,
+            saved: vec![],
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    // Puts the save back into the cartridge; not having one yet is fine
+    pub fn load<C: Cartridge>(&mut self, cartridge: &mut C) -> io::Result<()> {
+        let data = match fs::read(&self.path) {
+            Ok(data) => data,
+            Err(ref error) if error.kind() == io::ErrorKind::NotFound => return Ok(()),
+            Err(error) => return Err(error),
+        };
+
+        cartridge.load_save_ram(&data);
+        self.saved = data;
+
+        Ok(())
+    }
+
+    // Writes the cartridge's save ram out, if it has any and it changed
+    pub fn flush<C: Cartridge>(&mut self, cartridge: &C) -> io::Result<()> {
+        let save_ram = match cartridge.save_ram() {
+            Some(save_ram) => save_ram,
+            None => return Ok(()),
+        };
+
+        if save_ram == self.saved.as_slice() {
+            return Ok(());
+        }
+
+        let temp_path = self.path.with_extension("sav.tmp");
+        fs::write(&temp_path, save_ram)?;
+        fs::rename(&temp_path, &self.path)?;
+
+        self.saved = save_ram.to_vec();
+
+        Ok(())
+    }
+}
+
+// Where a rom's save goes: <rom>.sav, in save_dir if there is one or next to
+// the rom if not
+pub fn save_path(rom_path: &Path, save_dir: Option<&Path>) -> PathBuf {
+    let next_to_rom = rom_path.with_extension("sav");
+
+    match (save_dir, next_to_rom.file_name()) {
+        (Some(save_dir), Some(file_name)) => save_dir.join(file_name),
+        _ => next_to_rom,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use ::memory_map::NROMMemoryMap;
+    use ::rom;
+
+    use std::env;
+    use std::process;
+
+    fn battery_cartridge() -> NROMMemoryMap {
+        let mut buf = b"NES\x1a".to_vec();
+        buf.extend(vec![1, 0, 0b10, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+        buf.extend(vec![0; 0x4000]);
+
+        ::memory_map::load_cartridge(&rom::NesRom::from_bytes(&buf).unwrap())
+    }
+
+    fn temp_save_path(name: &str) -> PathBuf {
+        env::temp_dir().join(format!("nesc-{}-{}.sav", name, process::id()))
+    }
+
+    #[test]
+    pub fn test_save_path() {
+        assert_eq!(save_path(Path::new("roms/zelda.nes"), None), PathBuf::from("roms/zelda.sav"));
+        assert_eq!(save_path(Path::new("roms/zelda.nes"), Some(Path::new("saves"))), PathBuf::from("saves/zelda.sav"));
+    }
+
+    #[test]
+    pub fn test_round_trip() {
+        let path = temp_save_path("round-trip");
+
+        let mut cartridge = battery_cartridge();
+        cartridge.cpu_write(0x6000, 0x42);
+        cartridge.cpu_write(0x7fff, 0x43);
+
+        let mut save_file = SaveFile::new(path.clone());
+        save_file.flush(&cartridge).unwrap();
+
+        let mut cartridge = battery_cartridge();
+        SaveFile::new(path.clone()).load(&mut cartridge).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(cartridge.cpu_read(0x6000), Some(0x42));
+        assert_eq!(cartridge.cpu_read(0x7fff), Some(0x43));
+    }
+
+    #[test]
+    pub fn test_no_battery_no_save() {
+        let path = temp_save_path("no-battery");
+
+        let mut cartridge = NROMMemoryMap::default();
+        cartridge.cpu_write(0x6000, 0x42);
+
+        let mut save_file = SaveFile::new(path.clone());
+        save_file.load(&mut cartridge).unwrap();
+        save_file.flush(&cartridge).unwrap();
+
+        assert!(!path.exists());
+    }
+}
@@ -0,0 +1,427 @@
+use ::cpu::Cpu;
+use ::memory_map::Bus;
+use ::memory_map::Cartridge;
+
+use std::error;
+use std::fmt;
+use std::io;
+use std::path::Path;
+use std::path::PathBuf;
+
+use byteorder;
+use byteorder::ByteOrder;
+
+// [Save states]
+// A snapshot of everything that changes while running, so it can be put back
+// later. The file is a small header followed by one section per part of the
+// console:
+//
+// "NESCSTAT"      magic
+// u16             format version
+// u32             checksum of the rom it's for (see NesRom::checksum)
+// [u8; 4], u32    section tag and length, then that many bytes of section
+// ...
+//
+// Everything is little-endian. Inside a section, fields are just written one
+// after another, and byte arrays get a u32 length in front.
+//
+// [Adding things]
+// New fields go at the end of their section, and new parts of the console get
+// a new section. Reading an older state runs off the end of a section before
+// it gets to the new fields, and those come back as 0 (false, empty) rather
+// than an error; sections we don't know about are skipped, so newer states
+// still load as far as they can. The version only goes up for changes that
+// can't work like that.
+
+const MAGIC: &[u8; 8] = b"NESCSTAT";
+const VERSION: u16 = 3;
+
+const CPU_SECTION: &[u8; 4] = b"CPU ";
+const BUS_SECTION: &[u8; 4] = b"BUS ";
+const PPU_SECTION: &[u8; 4] = b"PPU ";
+const CARTRIDGE_SECTION: &[u8; 4] = b"CART";
+
+#[derive(Debug)]
+pub enum StateError {
+    BadMagic,
+    UnsupportedVersion(u16),
+    Truncated,
+
+    // The state doesn't fit this console, e.g. it's from another game
+    Mismatch(&'static str),
+    Io(io::Error),
+}
+
+impl fmt::Display for StateError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            StateError::BadMagic => write!(f, "not a save state"),
+            StateError::UnsupportedVersion(version) => write!(f, "save state version {} isn't supported", version),
+            StateError::Truncated => write!(f, "save state is cut short"),
+            StateError::Mismatch(what) => write!(f, "save state doesn't match this game ({})", what),
+            StateError::Io(ref error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl error::Error for StateError {}
+
+impl From<io::Error> for StateError {
+    fn from(error: io::Error) -> Self {
+        StateError::Io(error)
+    }
+}
+
+// Anything with state worth keeping
+pub trait SaveState {
+    fn save_state(&self, state: &mut StateWriter);
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), StateError>;
+}
+
+#[derive(Default)]
+pub struct StateWriter {
+    buf: Vec<u8>,
+}
+
+impl StateWriter {
+    pub fn u8(&mut self, val: u8) {
+        self.buf.push(val);
+    }
+
+    pub fn bool(&mut self, val: bool) {
+        self.u8(val as u8);
+    }
+
+    pub fn u16(&mut self, val: u16) {
+        let mut bytes = [0; 2];
+        byteorder::LittleEndian::write_u16(&mut bytes, val);
+        self.buf.extend_from_slice(&bytes);
+    }
+
+    pub fn u32(&mut self, val: u32) {
+        let mut bytes = [0; 4];
+        byteorder::LittleEndian::write_u32(&mut bytes, val);
+        self.buf.extend_from_slice(&bytes);
+    }
+
+    pub fn u64(&mut self, val: u64) {
+        let mut bytes = [0; 8];
+        byteorder::LittleEndian::write_u64(&mut bytes, val);
+        self.buf.extend_from_slice(&bytes);
+    }
+
+    pub fn bytes(&mut self, val: &[u8]) {
+        self.u32(val.len() as u32);
+        self.buf.extend_from_slice(val);
+    }
+
+    pub fn section<T: SaveState>(&mut self, tag: &[u8; 4], val: &T) {
+        let mut section = StateWriter::default();
+        val.save_state(&mut section);
+
+        self.buf.extend_from_slice(tag);
+        self.bytes(&section.buf);
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.buf
+    }
+}
+
+pub struct StateReader<'a> {
+    buf: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> StateReader<'a> {
+    pub fn new(buf: &'a [u8]) -> Self {
+        StateReader { buf, pos: 0 }
+    }
+
+    // The next len bytes, or None if the section has run out. A field that's
+    // cut short counts as missing, along with everything after it.
+    fn take(&mut self, len: usize) -> Option<&'a [u8]> {
+        match self.buf.get(self.pos..self.pos + len) {
+            Some(bytes) => {
+                self.pos += len;
+                Some(bytes)
+            }
+            None => {
+                self.pos = self.buf.len();
+                None
+            }
+        }
+    }
+
+    pub fn u8(&mut self) -> u8 {
+        self.take(1).map(|bytes| bytes[0]).unwrap_or(0)
+    }
+
+    pub fn bool(&mut self) -> bool {
+        self.u8() != 0
+    }
+
+    pub fn u16(&mut self) -> u16 {
+        self.take(2).map(byteorder::LittleEndian::read_u16).unwrap_or(0)
+    }
+
+    pub fn u32(&mut self) -> u32 {
+        self.take(4).map(byteorder::LittleEndian::read_u32).unwrap_or(0)
+    }
+
+    pub fn u64(&mut self) -> u64 {
+        self.take(8).map(byteorder::LittleEndian::read_u64).unwrap_or(0)
+    }
+
+    pub fn bytes(&mut self) -> &'a [u8] {
+        let len = self.u32() as usize;
+
+        self.take(len).unwrap_or(&[])
+    }
+
+    // Reads a byte array back into dest, which has to be the same size (an
+    // empty one means there was nothing to save, and leaves dest alone)
+    pub fn bytes_into(&mut self, dest: &mut [u8], what: &'static str) -> Result<(), StateError> {
+        let bytes = self.bytes();
+
+        match bytes.len() {
+            0 => Ok(()),
+            len if len == dest.len() => {
+                dest.copy_from_slice(bytes);
+                Ok(())
+            }
+            _ => Err(StateError::Mismatch(what)),
+        }
+    }
+
+    // Loads a section into val; a missing section leaves val alone
+    pub fn section<T: SaveState>(&self, tag: &[u8; 4], val: &mut T) -> Result<(), StateError> {
+        let mut sections = StateReader::new(&self.buf[self.pos..]);
+
+        while let Some(found) = sections.take(4) {
+            let len = sections.u32() as usize;
+            let body = sections.take(len).ok_or(StateError::Truncated)?;
+
+            if found == tag {
+                return val.load_state(&mut StateReader::new(body));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+// Snapshots a running console, playing the rom with the given checksum
+pub fn save<C: Cartridge>(cpu: &Cpu<Bus<C>>, rom_checksum: u32) -> Vec<u8> {
+    let mut state = StateWriter::default();
+    state.buf.extend_from_slice(MAGIC);
+    state.u16(VERSION);
+    state.u32(rom_checksum);
+
+    state.section(CPU_SECTION, cpu);
+    state.section(BUS_SECTION, cpu.memory_map());
+    state.section(PPU_SECTION, cpu.memory_map().ppu());
+    state.section(CARTRIDGE_SECTION, cpu.memory_map().cartridge());
+
+    state.into_bytes()
+}
+
+// Puts a snapshot from save back. A state that turns out not to fit leaves the
+// console as it was.
+pub fn load<C: Cartridge>(cpu: &mut Cpu<Bus<C>>, rom_checksum: u32, data: &[u8]) -> Result<(), StateError> {
+    let snapshot = save(cpu, rom_checksum);
+
+    match load_sections(cpu, rom_checksum, data) {
+        Ok(()) => Ok(()),
+        Err(error) => {
+            // it can fail part way through, so put back what was there
+            load_sections(cpu, rom_checksum, &snapshot).expect("a snapshot always loads");
+            Err(error)
+        }
+    }
+}
+
+fn load_sections<C: Cartridge>(cpu: &mut Cpu<Bus<C>>, rom_checksum: u32, data: &[u8]) -> Result<(), StateError> {
+    let mut state = StateReader::new(data);
+
+    match state.take(MAGIC.len()) {
+        Some(magic) if magic == MAGIC => {}
+        Some(_) => return Err(StateError::BadMagic),
+        None => return Err(StateError::Truncated),
+    }
+
+    match state.u16() {
+        VERSION => {}
+        version => return Err(StateError::UnsupportedVersion(version)),
+    }
+
+    if state.u32() != rom_checksum {
+        return Err(StateError::Mismatch("rom checksum"));
+    }
+
+    state.section(CPU_SECTION, cpu)?;
+    state.section(BUS_SECTION, cpu.memory_map_mut())?;
+    state.section(PPU_SECTION, cpu.memory_map_mut().ppu_mut())?;
+    state.section(CARTRIDGE_SECTION, cpu.memory_map_mut().cartridge_mut())
+}
+
+// Where a numbered save state slot goes: <rom>.st<slot>, in save_dir if
+// there is one or next to the rom if not
+pub fn slot_path(rom_path: &Path, save_dir: Option<&Path>, slot: u8) -> PathBuf {
+    let next_to_rom = rom_path.with_extension(format!("st{}", slot));
+
+    match (save_dir, next_to_rom.file_name()) {
+        (Some(save_dir), Some(file_name)) => save_dir.join(file_name),
+        _ => next_to_rom,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use ::cpu::CpuDebug;
+    use ::memory_map::MemoryMapper;
+    use ::memory_map::MMC1MemoryMap;
+    use ::memory_map::NROMMemoryMap;
+
+    const ROM_CHECKSUM: u32 = 0x1234_5678;
+
+    fn new_cpu() -> Cpu<Bus<NROMMemoryMap>> {
+        let mut cpu = Cpu::new(Bus::default());
+        cpu.power_on();
+        cpu.step_instruction();
+
+        cpu
+    }
+
+    #[test]
+    pub fn test_round_trip() {
+        let mut cpu = new_cpu();
+        cpu.memory_map_mut().write(0x0300, 0x42);
+        cpu.memory_map_mut().write(0x6000, 0x43);
+        cpu.memory_map_mut().cartridge_mut().ppu_write(0x0010, 0x44);
+
+        // stop halfway through the inc $10 after it
+        cpu.exec_asm("inc $10");
+        cpu.memory_map_mut().write_u16(0x0002, 0x10e6);
+        cpu.tick();
+        cpu.tick();
+
+        let state = save(&cpu, ROM_CHECKSUM);
+        let mut restored = new_cpu();
+        load(&mut restored, ROM_CHECKSUM, &state).unwrap();
+
+        assert_eq!(save(&restored, ROM_CHECKSUM), state);
+        assert_eq!(restored.memory_map().peek(0x0300), 0x42);
+        assert_eq!(restored.memory_map().peek(0x6000), 0x43);
+        assert_eq!(restored.memory_map_mut().cartridge_mut().ppu_read(0x0010), 0x44);
+
+        // and both finish the instruction the same way
+        cpu.step_instruction();
+        restored.step_instruction();
+        assert_eq!(save(&restored, ROM_CHECKSUM), save(&cpu, ROM_CHECKSUM));
+    }
+
+    // Loads a state with just the given cpu section (and a section from the
+    // future) into a new cpu, and saves its cpu section again
+    fn reload_cpu_section(cpu_section: &[u8]) -> Vec<u8> {
+        let mut state = StateWriter::default();
+        state.buf.extend_from_slice(MAGIC);
+        state.u16(VERSION);
+        state.u32(ROM_CHECKSUM);
+        state.buf.extend_from_slice(b"NEW!");
+        state.bytes(&[1, 2, 3]);
+        state.buf.extend_from_slice(CPU_SECTION);
+        state.bytes(cpu_section);
+
+        let mut cpu = new_cpu();
+        cpu.memory_map_mut().write(0x0000, 0x42);
+        load(&mut cpu, ROM_CHECKSUM, &state.into_bytes()).unwrap();
+        assert_eq!(cpu.memory_map().peek(0x0000), 0x42);
+
+        let mut section = StateWriter::default();
+        cpu.save_state(&mut section);
+
+        section.into_bytes()
+    }
+
+    #[test]
+    pub fn test_missing_fields_and_unknown_sections() {
+        let mut cpu = new_cpu();
+        cpu.exec_asm("lda #$42");
+        cpu.exec_asm("ldx #$43");
+
+        let mut section = StateWriter::default();
+        cpu.save_state(&mut section);
+        let full = section.into_bytes();
+
+        // what every field comes back as when it's missing
+        let missing = reload_cpu_section(&[]);
+        assert_eq!(reload_cpu_section(&full), full);
+
+        // cut anywhere, the fields before the cut come back and the rest are
+        // missing
+        for cut in 0..full.len() {
+            let loaded = reload_cpu_section(&full[..cut]);
+
+            assert_eq!(loaded[cut..], missing[cut..], "cut at {}", cut);
+            for i in 0..cut {
+                assert!(loaded[i] == full[i] || loaded[i] == missing[i], "cut at {}", cut);
+            }
+        }
+    }
+
+    #[test]
+    pub fn test_rejects_bad_states() {
+        let mut cpu = new_cpu();
+
+        match load(&mut cpu, ROM_CHECKSUM, b"NESCSAVE\x01\x00") {
+            Err(StateError::BadMagic) => {}
+            result => panic!("expected BadMagic, got {:?}", result),
+        }
+
+        match load(&mut cpu, ROM_CHECKSUM, b"NESCSTAT\x01\x00") {
+            Err(StateError::UnsupportedVersion(1)) => {}
+            result => panic!("expected UnsupportedVersion, got {:?}", result),
+        }
+
+        // another game's prg ram doesn't fit
+        let mut other: Cpu<Bus<MMC1MemoryMap>> = Cpu::new(Bus::default());
+        let mut rom = ::rom::NesRom::default();
+        rom.prg_rom = vec![0; 0x8000];
+        rom.prg_ram_size = 0x8000;
+        other.memory_map_mut().cartridge_mut().load(&rom);
+
+        let state = save(&other, ROM_CHECKSUM);
+        let mut cpu: Cpu<Bus<MMC1MemoryMap>> = Cpu::new(Bus::default());
+        cpu.power_on();
+        cpu.step_instruction();
+        cpu.memory_map_mut().write(0x0000, 0x42);
+
+        let before = save(&cpu, ROM_CHECKSUM);
+        match load(&mut cpu, ROM_CHECKSUM, &state) {
+            Err(StateError::Mismatch("prg ram")) => {}
+            result => panic!("expected Mismatch, got {:?}", result),
+        }
+
+        // even though the cpu and bus sections went in fine
+        assert_eq!(save(&cpu, ROM_CHECKSUM), before);
+    }
+
+    #[test]
+    pub fn test_rejects_other_games() {
+        let mut cpu = new_cpu();
+        let state = save(&cpu, ROM_CHECKSUM);
+
+        match load(&mut cpu, ROM_CHECKSUM + 1, &state) {
+            Err(StateError::Mismatch("rom checksum")) => {}
+            result => panic!("expected Mismatch, got {:?}", result),
+        }
+    }
+
+    #[test]
+    pub fn test_slot_path() {
+        assert_eq!(slot_path(Path::new("roms/zelda.nes"), None, 3), PathBuf::from("roms/zelda.st3"));
+        assert_eq!(slot_path(Path::new("roms/zelda.nes"), Some(Path::new("saves")), 0), PathBuf::from("saves/zelda.st0"));
+    }
+}
@@ -0,0 +1,159 @@
+; A run through the instruction set for the golden log in cpu_golden.log:
+; every addressing mode, the flags arithmetic leaves behind, page crossing,
+; the stack and rti, jmp (indirect), and some unofficial opcodes. It
+; starts at $c000 like nestest's automation mode and ends up spinning on done.
+
+        .org $c000
+start:  ldx #$ff
+        txs
+        cld
+
+; loads and stores in every mode
+        lda #$80
+        sta $10
+        ldx #$02
+        sta $10,x
+        ldy #$03
+        sta $0200
+        sta $0200,x
+        sta $0200,y
+        lda #$00
+        sta $20
+        lda #$02
+        sta $21
+        lda ($20),y
+        ldx #$00
+        lda ($20,x)
+        ldx $12
+        ldy $10,x
+        stx $30
+        sty $31
+        stx $30,y
+        sty $30,x
+        lda $0200
+        ldx $0203
+        ldy $0200,x
+
+; indexing across a page costs a cycle on reads, never on writes
+        ldx #$ff
+        lda $0101,x
+        ldy #$01
+        lda $02ff,y
+        lda #$ff
+        sta $22
+        lda #$01
+        sta $23
+        lda ($22),y
+        sta $0101,x
+        sta ($22),y
+
+; arithmetic and the flags it sets
+        clc
+        lda #$7f
+        adc #$01
+        sec
+        sbc #$01
+        lda #$ff
+        clc
+        adc #$01
+        and #$0f
+        ora #$f0
+        eor #$ff
+        cmp #$01
+        cpx #$ff
+        cpy #$02
+        bit $10
+        adc $10
+        sbc $0200
+        clv
+
+; shifts, rotates, increments
+        lda #$81
+        asl
+        rol
+        lsr
+        ror
+        asl $10
+        rol $11,x
+        lsr $0200
+        ror $0101,x
+        inc $10
+        dec $10
+        inc $0200,x
+        inx
+        iny
+        dex
+        dey
+        tax
+        tay
+        txa
+        tya
+        tsx
+
+; branches, taken and not, on and off the page
+        ldx #$03
+loop:   dex
+        bne loop
+        beq forward
+        nop
+forward:
+        bmi next
+        bpl next
+next:   sec
+        bcc carry
+        bcs carry
+carry:  clc
+        bvs far
+        bvc far
+        .org $c100
+far:    lda #$00
+        beq over
+over:
+
+; the stack
+        lda #$42
+        pha
+        php
+        lda #$00
+        plp
+        pla
+        jsr subroutine
+        lda #>after
+        pha
+        lda #<after
+        pha
+        php
+        rti
+after:
+
+; jmp (indirect) doesn't carry into the high byte of the pointer
+        lda #<wrapped
+        sta $02ff
+        lda #>wrapped
+        sta $0200
+        jmp ($02ff)
+
+; unofficial opcodes
+wrapped:
+        lax $10
+        sax $40
+        dcp $40
+        isb $40
+        slo $40
+        rla $40
+        sre $40
+        rra $40
+        *nop $10
+        *nop #$01
+        anc #$80
+        alr #$ff
+        arr #$ff
+        axs #$01
+        sbc #$01
+        .byte $eb, $01
+
+done:   jmp done
+
+subroutine:
+        ldx #$10
+        rts